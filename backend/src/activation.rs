@@ -0,0 +1,295 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::eltor::{self, EltorMode};
+
+/// How often to check bootstrap progress while a job is bootstrapping
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Finished jobs kept around for polling
+const MAX_FINISHED_JOBS: usize = 50;
+
+/// How long a job may spend bootstrapping before it is failed
+///
+/// Reads APP_ELTOR_ACTIVATION_TIMEOUT_SECS, defaults to 5 minutes.
+fn bootstrap_timeout() -> Duration {
+    let secs = std::env::var("APP_ELTOR_ACTIVATION_TIMEOUT_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(300);
+    Duration::from_secs(secs)
+}
+
+/// Where an activation job is
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "state", content = "reason", rename_all = "lowercase")]
+pub enum ActivationState {
+    /// Resolving paths and cleaning up old data files
+    Preparing,
+    /// Launching the eltord process
+    Spawning,
+    /// eltord is running, waiting for Tor to bootstrap
+    Bootstrapping,
+    /// Bootstrapped to 100%
+    Ready,
+    Failed(String),
+}
+
+impl ActivationState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, ActivationState::Ready | ActivationState::Failed(_))
+    }
+}
+
+/// One eltord activation
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivationJob {
+    pub id: String,
+    pub mode: String,
+    #[serde(flatten)]
+    pub state: ActivationState,
+    pub pid: Option<u32>,
+    /// Bootstrap progress, 0-100
+    pub progress: u8,
+    /// Human-readable summary of the current state
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+struct ActivationTracker {
+    jobs: Mutex<HashMap<String, ActivationJob>>,
+    sender: broadcast::Sender<ActivationJob>,
+}
+
+static TRACKER: OnceLock<ActivationTracker> = OnceLock::new();
+
+fn tracker() -> &'static ActivationTracker {
+    TRACKER.get_or_init(|| {
+        let (sender, _) = broadcast::channel(100);
+        ActivationTracker {
+            jobs: Mutex::new(HashMap::new()),
+            sender,
+        }
+    })
+}
+
+/// Apply a change to a job and broadcast it if anything changed
+fn update(id: &str, change: impl FnOnce(&mut ActivationJob)) {
+    let job = {
+        let mut jobs = tracker().jobs.lock().unwrap();
+        let Some(job) = jobs.get_mut(id) else {
+            return;
+        };
+        let before = (job.state.clone(), job.pid, job.progress, job.message.clone());
+        change(job);
+        if before == (job.state.clone(), job.pid, job.progress, job.message.clone()) {
+            return;
+        }
+        job.updated_at = Utc::now();
+        job.clone()
+    };
+
+    match &job.state {
+        ActivationState::Failed(reason) => warn!("⚠️ [{}] Activation {} failed: {}", job.mode, job.id, reason),
+        state => info!("🚦 [{}] Activation {} is {:?}: {}", job.mode, job.id, state, job.message),
+    }
+    let _ = tracker().sender.send(job);
+}
+
+fn set_state(id: &str, state: ActivationState, message: impl Into<String>) {
+    let message = message.into();
+    update(id, move |job| {
+        job.state = state;
+        job.message = message;
+    });
+}
+
+/// Drop the oldest finished jobs so the table doesn't grow forever
+fn prune(jobs: &mut HashMap<String, ActivationJob>) {
+    let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+        .values()
+        .filter(|job| job.state.is_finished())
+        .map(|job| (job.updated_at, job.id.clone()))
+        .collect();
+    if finished.len() <= MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in finished.iter().take(finished.len() - MAX_FINISHED_JOBS) {
+        jobs.remove(id);
+    }
+}
+
+/// Start activating eltord for a mode and return the job tracking it
+///
+/// Returns the existing job if an activation for the same mode is still in
+/// progress. Spawning and bootstrap monitoring happen on their own thread,
+/// isolated from the async runtime like `activate_eltord_process` requires.
+pub fn start_activation(mode: &str, enable_logging: bool) -> Result<ActivationJob, String> {
+    let mode_enum = EltorMode::from_str(mode)?;
+    let mode = mode_enum.to_string().to_string();
+
+    let job = {
+        let mut jobs = tracker().jobs.lock().unwrap();
+        if let Some(existing) = jobs.values().find(|job| job.mode == mode && !job.state.is_finished()) {
+            info!("ℹ️  Activation for {} already in progress ({})", mode, existing.id);
+            return Ok(existing.clone());
+        }
+
+        prune(&mut jobs);
+        let now = Utc::now();
+        let job = ActivationJob {
+            id: uuid::Uuid::new_v4().to_string(),
+            mode: mode.clone(),
+            state: ActivationState::Preparing,
+            pid: None,
+            progress: 0,
+            message: format!("{} activation started", mode),
+            created_at: now,
+            updated_at: now,
+        };
+        jobs.insert(job.id.clone(), job.clone());
+        job
+    };
+    let _ = tracker().sender.send(job.clone());
+
+    let id = job.id.clone();
+    std::thread::spawn(move || run_job(&id, mode_enum, enable_logging));
+
+    Ok(job)
+}
+
+fn run_job(id: &str, mode: EltorMode, enable_logging: bool) {
+    let on_spawning = || set_state(id, ActivationState::Spawning, format!("Spawning eltord {}", mode));
+    let pid = match eltor::activate_eltord_process_with(mode.to_string().to_string(), enable_logging, &on_spawning) {
+        Ok(pid) => pid,
+        Err(e) => {
+            set_state(id, ActivationState::Failed(e.clone()), e);
+            return;
+        }
+    };
+
+    update(id, |job| {
+        job.pid = Some(pid);
+        job.state = ActivationState::Bootstrapping;
+        job.message = format!("eltord {} running (PID: {}), waiting for Tor to bootstrap", mode, pid);
+    });
+
+    let timeout = bootstrap_timeout();
+    let started = Instant::now();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+
+        if !crate::processes::is_running(eltor::eltord_process_name(&mode)) {
+            let reason = format!("eltord {} (PID: {}) exited during bootstrap", mode, pid);
+            set_state(id, ActivationState::Failed(reason.clone()), reason);
+            return;
+        }
+
+        let status = crate::bootstrap::get_bootstrap_status(mode.to_string());
+        if let Some(status) = status {
+            if status.is_ready() {
+                update(id, |job| {
+                    job.progress = 100;
+                    job.state = ActivationState::Ready;
+                    job.message = format!("eltord {} is ready", mode);
+                });
+                return;
+            }
+            // The monitor gives up on authentication failures, so will we
+            if !status.control_connected && status.warning.is_some() {
+                let reason = status.warning.unwrap_or_default();
+                set_state(id, ActivationState::Failed(reason.clone()), reason);
+                return;
+            }
+            update(id, |job| {
+                job.progress = status.progress;
+                job.message = match &status.warning {
+                    Some(warning) => format!("Bootstrapped {}%: {} ({})", status.progress, status.summary, warning),
+                    None => format!("Bootstrapped {}%: {}", status.progress, status.summary),
+                };
+            });
+        }
+
+        if started.elapsed() >= timeout {
+            let reason = format!("Tor did not finish bootstrapping within {}s", timeout.as_secs());
+            set_state(id, ActivationState::Failed(reason.clone()), reason);
+            return;
+        }
+    }
+}
+
+/// Current state of an activation job
+pub fn get_activation_job(id: &str) -> Option<ActivationJob> {
+    tracker().jobs.lock().unwrap().get(id).cloned()
+}
+
+/// All known activation jobs, newest first
+pub fn list_activation_jobs() -> Vec<ActivationJob> {
+    let mut jobs: Vec<_> = tracker().jobs.lock().unwrap().values().cloned().collect();
+    jobs.sort_by_key(|job| std::cmp::Reverse(job.created_at));
+    jobs
+}
+
+/// Subscribe to activation job changes
+pub fn subscribe_activation() -> broadcast::Receiver<ActivationJob> {
+    tracker().sender.subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_serialization() {
+        let json = serde_json::to_value(ActivationState::Bootstrapping).unwrap();
+        assert_eq!(json, serde_json::json!({ "state": "bootstrapping" }));
+
+        let json = serde_json::to_value(ActivationState::Failed("eltord binary not found".to_string())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "state": "failed", "reason": "eltord binary not found" })
+        );
+    }
+
+    #[test]
+    fn test_invalid_mode_is_rejected() {
+        assert!(start_activation("bridge", false).is_err());
+    }
+
+    #[test]
+    fn test_prune_keeps_running_jobs() {
+        let mut jobs = HashMap::new();
+        for i in 0..(MAX_FINISHED_JOBS + 5) {
+            let now = Utc::now();
+            let state = if i == 0 {
+                ActivationState::Bootstrapping
+            } else {
+                ActivationState::Failed("test".to_string())
+            };
+            let id = i.to_string();
+            jobs.insert(
+                id.clone(),
+                ActivationJob {
+                    id,
+                    mode: "client".to_string(),
+                    state,
+                    pid: None,
+                    progress: 0,
+                    message: String::new(),
+                    created_at: now,
+                    updated_at: now,
+                },
+            );
+        }
+
+        prune(&mut jobs);
+        assert_eq!(jobs.len(), MAX_FINISHED_JOBS + 1);
+        assert!(jobs.contains_key("0"));
+    }
+}
@@ -1,70 +1,29 @@
+use futures::future::BoxFuture;
 use log::{info, warn, error};
 use std::process::{Command as StdCommand, Stdio};
-use std::env;
+use std::sync::{Arc, Mutex};
 
+use crate::arti_config::{self, ArtiConfigInfo, ArtiSettings};
+use crate::arti_logs::{self, RotatingLog};
 use crate::paths::PathConfig;
 
-/// Get the Arti SOCKS port from environment variables
-/// 
-/// This function looks for the APP_ARTI_SOCKS_PORT environment variable.
-/// If not found, defaults to 18050.
-fn get_arti_socks_port() -> u16 {
-    env::var("APP_ARTI_SOCKS_PORT")
-        .unwrap_or_else(|_| "18050".to_string())
-        .parse::<u16>()
-        .unwrap_or(18050)
-}
-
-/// Arti process handle for tracking the running Arti instance
-#[derive(Debug)]
-pub struct ArtiProcessHandle {
-    pid: u32,
-    mode: String, // Store mode for logging purposes
-}
-
-impl ArtiProcessHandle {
-    pub fn new(pid: u32, mode: String) -> Self {
-        Self { pid, mode }
-    }
-
-    pub fn pid(&self) -> u32 {
-        self.pid
-    }
-
-    /// Stop the Arti process
-    pub async fn stop(&self) -> Result<(), String> {
-        info!("🛑 Stopping Arti process (PID: {})", self.pid);
-
-        // First try graceful shutdown by killing the process
-        if let Err(e) = crate::ports::kill_process(self.pid) {
-            warn!("⚠️ Failed to kill Arti process {}: {}", self.pid, e);
-            return Err(format!("Failed to kill Arti process: {}", e));
-        }
-
-        // Wait a moment for the process to exit
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-
-        info!("✅ Arti process stopped (PID: {})", self.pid);
-        Ok(())
-    }
-}
-
-/// Global Arti process handle storage
-static ARTI_PROCESS: tokio::sync::RwLock<Option<ArtiProcessHandle>> = tokio::sync::RwLock::const_new(None);
+/// Name Arti is registered under with the process registry and supervisor
+const ARTI_SERVICE_NAME: &str = "arti";
 
 /// Start Arti process when eltord starts
 pub async fn start_arti_with_eltord(mode: &str, path_config: &PathConfig) -> Result<(), String> {
     // Check if Arti is already running
-    {
-        let arti_guard = ARTI_PROCESS.read().await;
-        if arti_guard.is_some() {
-            info!("ℹ️  Arti is already running, skipping startup");
-            return Ok(());
-        }
+    if crate::processes::is_running(ARTI_SERVICE_NAME) {
+        info!("ℹ️  Arti is already running, skipping startup");
+        return Ok(());
     }
 
-    let socks_port = get_arti_socks_port();
-    info!("🚀 Starting Arti for eltord mode: {} (SOCKS port: {})", mode, socks_port);
+    // Pick up settings saved by another process (e.g. the Tauri app) before rendering arti.toml
+    if let Err(e) = arti_config::load_arti_settings(path_config) {
+        warn!("⚠️ {}, keeping current Arti settings", e);
+    }
+    let socks_listen = arti_config::get_arti_settings().socks_listen;
+    info!("🚀 Starting Arti for eltord mode: {} (SOCKS: {})", mode, socks_listen);
 
     let arti_binary = path_config.get_executable_path("arti");
     
@@ -75,15 +34,17 @@ pub async fn start_arti_with_eltord(mode: &str, path_config: &PathConfig) -> Res
         return Err(error_msg);
     }
 
+    // Render the managed arti.toml so state, cache and logs stay in our app data dir
+    let config_path = arti_config::write_arti_toml(path_config)?;
+
     // Start Arti process
-    let socks_config = format!("proxy.socks_port={}", socks_port);
     let mut cmd = StdCommand::new(&arti_binary);
     cmd.arg("proxy")
-        .arg("-o")
-        .arg(&socks_config)
+        .arg("-c")
+        .arg(&config_path)
         .current_dir(&path_config.bin_dir)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .stdin(Stdio::null());
 
     // On Unix, create a new session so Arti can be properly killed as a process group
@@ -98,36 +59,65 @@ pub async fn start_arti_with_eltord(mode: &str, path_config: &PathConfig) -> Res
         }
     }
 
-    info!("🚀 Starting Arti with SOCKS port {}", socks_port);
+    info!("🚀 Starting Arti with SOCKS listener {}", socks_listen);
     info!("   Binary: {:?}", arti_binary);
+    info!("   Config: {:?}", config_path);
     info!("   Working dir: {:?}", path_config.bin_dir);
 
     match cmd.spawn() {
-        Ok(child) => {
+        Ok(mut child) => {
             let pid = child.id();
             info!("✅ Arti started with PID: {} for mode: {}", pid, mode);
-            info!("   SOCKS proxy available on port {}", socks_port);
+
+            // Capture output right away so a failed start leaves something to read
+            let log_file = arti_config::get_arti_settings()
+                .log_file
+                .then(|| Arc::new(Mutex::new(RotatingLog::new(arti_config::arti_log_path(path_config)))));
+            if let Some(stdout) = child.stdout.take() {
+                arti_logs::capture(stdout, "INFO", log_file.clone());
+            }
+            if let Some(stderr) = child.stderr.take() {
+                arti_logs::capture(stderr, "WARN", log_file);
+            }
+            info!("   SOCKS proxy available on {}", socks_listen);
 
             // Wait a moment to see if the process stays alive
             tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
             
             // Check if process is still running
             if !is_process_running(pid) {
+                // Reap it so it doesn't linger as a zombie
+                let _ = child.try_wait();
                 let error_msg = format!("❌ Arti process {} died immediately after start", pid);
                 error!("{}", error_msg);
                 return Err(error_msg);
             }
 
-            // Store the process handle
-            {
-                let mut arti_guard = ARTI_PROCESS.write().await;
-                *arti_guard = Some(ArtiProcessHandle::new(pid, mode.to_string()));
+            // Record PID, start time and executable so stop_arti never hits a recycled PID
+            if let Err(e) = crate::processes::register(ARTI_SERVICE_NAME, pid, Some(mode)) {
+                let _ = child.try_wait();
+                error!("❌ {}", e);
+                return Err(e);
             }
 
-            // Let the process run independently
-            std::mem::forget(child);
+            // Reap Arti when it exits and let the supervisor know
+            std::thread::spawn(move || {
+                if let Ok(status) = child.wait() {
+                    info!("🧹 Arti process exited with status: {}", status);
+                    crate::processes::unregister_pid(ARTI_SERVICE_NAME, pid);
+                    crate::supervisor::record_exit(ARTI_SERVICE_NAME, pid, status.code());
+                }
+            });
+
+            let restart_mode = mode.to_string();
+            let restart_path_config = path_config.clone();
+            crate::supervisor::watch(
+                ARTI_SERVICE_NAME,
+                pid,
+                Arc::new(move || restart_arti(restart_mode.clone(), restart_path_config.clone())),
+            );
             
-            info!("🎯 Arti startup completed successfully (PID: {}, SOCKS port: {})", pid, socks_port);
+            info!("🎯 Arti startup completed successfully (PID: {}, SOCKS: {})", pid, socks_listen);
             Ok(())
         }
         Err(e) => {
@@ -139,45 +129,69 @@ pub async fn start_arti_with_eltord(mode: &str, path_config: &PathConfig) -> Res
     }
 }
 
+/// Restart Arti after a crash (used by the supervisor)
+fn restart_arti(mode: String, path_config: PathConfig) -> BoxFuture<'static, Result<(), String>> {
+    Box::pin(async move { start_arti_with_eltord(&mode, &path_config).await })
+}
+
 /// Stop Arti process
 pub async fn stop_arti() -> Result<(), String> {
-    let mut arti_guard = ARTI_PROCESS.write().await;
-    
-    if let Some(handle) = arti_guard.take() {
-        handle.stop().await?;
-        info!("🛑 Arti process stopped and handle removed");
-        Ok(())
-    } else {
-        info!("ℹ️  No Arti process to stop");
-        Ok(())
+    crate::supervisor::unwatch(ARTI_SERVICE_NAME);
+
+    match crate::processes::terminate(ARTI_SERVICE_NAME) {
+        Ok(Some(record)) => {
+            // Wait a moment for the process to exit
+            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+            info!("🛑 Arti process stopped (PID: {})", record.pid);
+            Ok(())
+        }
+        Ok(None) => {
+            info!("ℹ️  No Arti process to stop");
+            Ok(())
+        }
+        Err(e) => {
+            warn!("⚠️ Failed to kill Arti process: {}", e);
+            Err(format!("Failed to kill Arti process: {}", e))
+        }
+    }
+}
+
+/// Save new Arti settings and restart Arti (if it is running) to apply them
+///
+/// The SOCKS router follows a changed SOCKS port.
+pub async fn apply_arti_settings(path_config: &PathConfig, settings: ArtiSettings) -> Result<ArtiConfigInfo, String> {
+    let settings = arti_config::save_arti_settings(path_config, settings)?;
+    if let Some((_, mode)) = get_arti_status().await {
+        info!("🔄 Restarting Arti to apply new settings");
+        stop_arti().await?;
+        start_arti_with_eltord(&mode, path_config).await?;
     }
+    crate::socks::set_arti_socks_port(settings.socks_listen.port()).await?;
+    Ok(arti_config::get_arti_config(path_config))
+}
+
+/// Wipe Arti's state and cache, restarting Arti around it if it is running
+pub async fn reset_arti_storage(path_config: &PathConfig) -> Result<ArtiConfigInfo, String> {
+    let running = get_arti_status().await;
+    if running.is_some() {
+        stop_arti().await?;
+    }
+    arti_config::clear_arti_storage(path_config)?;
+    if let Some((_, mode)) = running {
+        start_arti_with_eltord(&mode, path_config).await?;
+    }
+    Ok(arti_config::get_arti_config(path_config))
 }
 
 /// Check if Arti is currently running
 pub async fn is_arti_running() -> bool {
-    let arti_guard = ARTI_PROCESS.read().await;
-    
-    if let Some(handle) = arti_guard.as_ref() {
-        // Verify the process is actually still running
-        is_process_running(handle.pid())
-    } else {
-        false
-    }
+    crate::processes::is_running(ARTI_SERVICE_NAME)
 }
 
 /// Get Arti process status
 pub async fn get_arti_status() -> Option<(u32, String)> {
-    let arti_guard = ARTI_PROCESS.read().await;
-    
-    if let Some(handle) = arti_guard.as_ref() {
-        if is_process_running(handle.pid()) {
-            Some((handle.pid(), handle.mode.clone()))
-        } else {
-            None
-        }
-    } else {
-        None
-    }
+    crate::processes::get_running(ARTI_SERVICE_NAME)
+        .map(|record| (record.pid, record.mode.unwrap_or_default()))
 }
 
 /// Helper function to check if a process is running
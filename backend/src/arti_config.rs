@@ -0,0 +1,291 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+use crate::paths::PathConfig;
+use crate::socks::{SocksCredential, TargetAddress};
+
+/// Directory under the app data dir holding everything Arti owns
+const ARTI_DIR: &str = "arti";
+const SETTINGS_FILE: &str = "settings.json";
+const CONFIG_FILE: &str = "arti.toml";
+
+/// Default Arti SOCKS port from APP_ARTI_SOCKS_PORT ("PORT" or "IP:PORT"), else 18050
+fn env_socks_port() -> u16 {
+    std::env::var("APP_ARTI_SOCKS_PORT")
+        .ok()
+        .and_then(|value| value.rsplit(':').next()?.parse().ok())
+        .unwrap_or(18050)
+}
+
+/// How streams the SOCKS router sends to Arti are kept on separate circuits
+///
+/// Arti isolates streams by SOCKS username/password; with no credentials from
+/// the client the router can supply its own per client or per destination.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamIsolation {
+    /// Only the credentials clients send themselves (Arti's default)
+    #[default]
+    Credentials,
+    /// Each client address gets its own circuits
+    Client,
+    /// Each destination host gets its own circuits
+    Destination,
+}
+
+/// Settings rendered into the managed arti.toml
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArtiSettings {
+    /// Address of Arti's SOCKS proxy; the router reaches it on loopback, so
+    /// the IP must be a loopback or unspecified address
+    pub socks_listen: SocketAddr,
+    /// Log filter for Arti's output ("info", "debug", "info,tor_proto=debug", ...)
+    pub log_level: String,
+    /// Also keep Arti's output in arti/logs/arti.log, rotated by size
+    pub log_file: bool,
+    pub stream_isolation: StreamIsolation,
+}
+
+impl Default for ArtiSettings {
+    fn default() -> Self {
+        Self {
+            socks_listen: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), env_socks_port()),
+            log_level: "info".to_string(),
+            log_file: true,
+            stream_isolation: StreamIsolation::default(),
+        }
+    }
+}
+
+impl ArtiSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        let ip = self.socks_listen.ip();
+        if !ip.is_loopback() && !ip.is_unspecified() {
+            return Err(format!(
+                "Arti SOCKS address {} must be loopback or unspecified so the router can reach it",
+                ip
+            ));
+        }
+        if self.socks_listen.port() == 0 {
+            return Err("Arti SOCKS port can't be 0".to_string());
+        }
+        let valid_filter = self
+            .log_level
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-:=,.".contains(c));
+        if self.log_level.trim().is_empty() || !valid_filter {
+            return Err(format!("Invalid Arti log level: {:?}", self.log_level));
+        }
+        Ok(())
+    }
+}
+
+/// Managed settings plus where Arti keeps its files
+#[derive(Debug, Clone, Serialize)]
+pub struct ArtiConfigInfo {
+    pub settings: ArtiSettings,
+    pub config_path: PathBuf,
+    pub state_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub log_path: Option<PathBuf>,
+}
+
+/// Settings Arti was (or will be) started with
+static SETTINGS: OnceLock<RwLock<ArtiSettings>> = OnceLock::new();
+
+fn settings() -> &'static RwLock<ArtiSettings> {
+    SETTINGS.get_or_init(|| RwLock::new(ArtiSettings::default()))
+}
+
+/// Arti's directory: app_data_dir/arti, or data_dir/arti when there is no app data dir
+pub fn arti_dir(path_config: &PathConfig) -> PathBuf {
+    path_config
+        .app_data_dir
+        .as_ref()
+        .unwrap_or(&path_config.data_dir)
+        .join(ARTI_DIR)
+}
+
+pub fn arti_config_path(path_config: &PathConfig) -> PathBuf {
+    arti_dir(path_config).join(CONFIG_FILE)
+}
+
+pub fn arti_state_dir(path_config: &PathConfig) -> PathBuf {
+    arti_dir(path_config).join("state")
+}
+
+pub fn arti_cache_dir(path_config: &PathConfig) -> PathBuf {
+    arti_dir(path_config).join("cache")
+}
+
+pub fn arti_log_path(path_config: &PathConfig) -> PathBuf {
+    arti_dir(path_config).join("logs").join("arti.log")
+}
+
+fn settings_path(path_config: &PathConfig) -> PathBuf {
+    arti_dir(path_config).join(SETTINGS_FILE)
+}
+
+/// TOML basic string (JSON escapes are a subset of TOML's)
+fn toml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn toml_path(path: &Path) -> String {
+    toml_string(&path.to_string_lossy())
+}
+
+/// Render arti.toml for the settings
+pub fn render_arti_toml(settings: &ArtiSettings, path_config: &PathConfig) -> String {
+    [
+        "# Managed by El Tor: change these settings through /api/arti/config.".to_string(),
+        "# This file is rewritten every time Arti starts.".to_string(),
+        String::new(),
+        "[proxy]".to_string(),
+        format!("socks_listen = {}", toml_string(&settings.socks_listen.to_string())),
+        String::new(),
+        "[storage]".to_string(),
+        format!("cache_dir = {}", toml_path(&arti_cache_dir(path_config))),
+        format!("state_dir = {}", toml_path(&arti_state_dir(path_config))),
+        String::new(),
+        // The backend captures console output into the log stream and arti.log
+        "[logging]".to_string(),
+        format!("console = {}", toml_string(&settings.log_level)),
+        "files = []".to_string(),
+    ]
+    .join("\n")
+        + "\n"
+}
+
+/// Write arti.toml from the current settings, creating Arti's directories
+pub fn write_arti_toml(path_config: &PathConfig) -> Result<PathBuf, String> {
+    let settings = get_arti_settings();
+    for dir in [arti_state_dir(path_config), arti_cache_dir(path_config), arti_dir(path_config).join("logs")] {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create Arti directory {:?}: {}", dir, e))?;
+    }
+    let path = arti_config_path(path_config);
+    fs::write(&path, render_arti_toml(&settings, path_config))
+        .map_err(|e| format!("Failed to write Arti config {:?}: {}", path, e))?;
+    Ok(path)
+}
+
+/// Load the saved settings; a missing file means defaults
+pub fn load_arti_settings(path_config: &PathConfig) -> Result<ArtiSettings, String> {
+    let path = settings_path(path_config);
+    let loaded = match fs::read_to_string(&path) {
+        Ok(content) => {
+            let loaded: ArtiSettings = serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid Arti settings {:?}: {}", path, e))?;
+            loaded.validate().map_err(|e| format!("Invalid Arti settings {:?}: {}", path, e))?;
+            loaded
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => ArtiSettings::default(),
+        Err(e) => return Err(format!("Failed to read Arti settings {:?}: {}", path, e)),
+    };
+    *settings().write().unwrap() = loaded.clone();
+    Ok(loaded)
+}
+
+/// Validate and persist new settings; Arti picks them up when it next starts
+pub fn save_arti_settings(path_config: &PathConfig, new_settings: ArtiSettings) -> Result<ArtiSettings, String> {
+    new_settings.validate()?;
+    let dir = arti_dir(path_config);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create Arti directory {:?}: {}", dir, e))?;
+    let path = settings_path(path_config);
+    let json = serde_json::to_string_pretty(&new_settings).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write Arti settings {:?}: {}", path, e))?;
+
+    info!("🧅 Saved Arti settings to {:?}", path);
+    *settings().write().unwrap() = new_settings.clone();
+    Ok(new_settings)
+}
+
+pub fn get_arti_settings() -> ArtiSettings {
+    settings().read().unwrap().clone()
+}
+
+pub fn get_arti_config(path_config: &PathConfig) -> ArtiConfigInfo {
+    let settings = get_arti_settings();
+    ArtiConfigInfo {
+        log_path: settings.log_file.then(|| arti_log_path(path_config)),
+        settings,
+        config_path: arti_config_path(path_config),
+        state_dir: arti_state_dir(path_config),
+        cache_dir: arti_cache_dir(path_config),
+    }
+}
+
+/// Delete Arti's state and cache (guard state, directory cache); Arti must be stopped
+pub fn clear_arti_storage(path_config: &PathConfig) -> Result<(), String> {
+    for dir in [arti_state_dir(path_config), arti_cache_dir(path_config)] {
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => info!("🧹 Removed {:?}", dir),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to remove {:?}: {}", dir, e)),
+        }
+    }
+    Ok(())
+}
+
+/// SOCKS port the router should use to reach Arti
+pub fn arti_socks_port() -> u16 {
+    get_arti_settings().socks_listen.port()
+}
+
+/// Credentials that put a stream sent to Arti on its own circuits
+pub(crate) fn isolation_credential(target: &TargetAddress, client_addr: SocketAddr) -> Option<SocksCredential> {
+    let (username, password) = match get_arti_settings().stream_isolation {
+        StreamIsolation::Credentials => return None,
+        StreamIsolation::Client => ("eltor-client", client_addr.ip().to_string()),
+        StreamIsolation::Destination => match target {
+            TargetAddress::Domain(domain, _) => ("eltor-destination", domain.to_ascii_lowercase()),
+            TargetAddress::IPv4(ip, _) => ("eltor-destination", ip.to_string()),
+            TargetAddress::IPv6(ip, _) => ("eltor-destination", ip.to_string()),
+        },
+    };
+    Some(SocksCredential {
+        username: username.to_string(),
+        password,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_arti_toml() {
+        let path_config = PathConfig {
+            bin_dir: PathBuf::from("/opt/eltor/bin"),
+            data_dir: PathBuf::from("/var/lib/eltor"),
+            app_data_dir: Some(PathBuf::from("/home/user/.eltor \"data\"")),
+        };
+        let settings = ArtiSettings {
+            socks_listen: "127.0.0.1:19050".parse().unwrap(),
+            log_level: "info,tor_proto=debug".to_string(),
+            ..ArtiSettings::default()
+        };
+        let toml = render_arti_toml(&settings, &path_config);
+        assert!(toml.contains("[proxy]\nsocks_listen = \"127.0.0.1:19050\"\n"));
+        assert!(toml.contains(r#"state_dir = "/home/user/.eltor \"data\"/arti/state""#));
+        assert!(toml.contains(r#"console = "info,tor_proto=debug""#));
+        assert!(toml.contains("files = []"));
+
+        assert!(settings.validate().is_ok());
+        let public = ArtiSettings {
+            socks_listen: "192.168.1.5:19050".parse().unwrap(),
+            ..settings.clone()
+        };
+        assert!(public.validate().is_err());
+        let injected = ArtiSettings {
+            log_level: "info\"\n[proxy]".to_string(),
+            ..settings
+        };
+        assert!(injected.validate().is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use chrono::Utc;
+use log::{debug, warn};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::state::{AppState, LogEntry};
+
+/// LogEntry source for everything Arti prints
+pub const ARTI_LOG_SOURCE: &str = "arti";
+/// arti.log is rotated once it grows past this
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated files kept next to arti.log (arti.log.1 is the newest)
+const KEEP_ROTATED: usize = 3;
+
+/// App state Arti output is pushed to, set once the backend has one
+fn app_state() -> &'static Mutex<Option<AppState>> {
+    static APP_STATE: OnceLock<Mutex<Option<AppState>>> = OnceLock::new();
+    APP_STATE.get_or_init(|| Mutex::new(None))
+}
+
+/// Send captured Arti output to this state's log stream
+pub fn attach_app_state(state: AppState) {
+    *app_state().lock().unwrap() = Some(state);
+}
+
+/// Append-only log file that rotates by size
+pub struct RotatingLog {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+}
+
+impl RotatingLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            file: None,
+            size: 0,
+        }
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    /// Shift arti.log -> arti.log.1 -> ... dropping the oldest
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        let _ = fs::remove_file(self.rotated_path(KEEP_ROTATED));
+        for index in (1..KEEP_ROTATED).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))
+    }
+
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        if self.file.is_none() {
+            let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        if self.size >= MAX_LOG_BYTES {
+            self.rotate()?;
+            return self.write_line(line);
+        }
+        let file = self.file.as_mut().expect("log file is open");
+        writeln!(file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+/// Drop ANSI color sequences in case Arti colors its output anyway
+fn strip_ansi(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequences end with a letter
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Level and message of an Arti log line
+///
+/// Arti logs as `2024-05-01T12:00:00.123Z  INFO target: message`. Lines
+/// that don't look like that (panics, argument errors) get `default_level`.
+pub fn parse_arti_line(line: &str, default_level: &str) -> (String, String) {
+    let mut parts = line.split_whitespace();
+    if let (Some(timestamp), Some(level)) = (parts.next(), parts.next()) {
+        let level = level.trim_end_matches(':').to_ascii_uppercase();
+        let is_timestamp = timestamp.starts_with(|c: char| c.is_ascii_digit()) && timestamp.contains(':');
+        if is_timestamp && ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"].contains(&level.as_str()) {
+            let message = parts.collect::<Vec<_>>().join(" ");
+            return (level, message);
+        }
+    }
+    let level = match line.to_ascii_lowercase() {
+        lower if lower.starts_with("error") || lower.contains("panicked") => "ERROR",
+        _ => default_level,
+    };
+    (level.to_string(), line.trim().to_string())
+}
+
+/// Read one of Arti's output streams on its own thread until Arti closes it
+///
+/// Lines go to the app log stream with source "arti" and, when given, to the
+/// rotating arti.log.
+pub fn capture<R: Read + Send + 'static>(reader: R, default_level: &'static str, file: Option<Arc<Mutex<RotatingLog>>>) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(line) => strip_ansi(&line),
+                Err(e) => {
+                    warn!("⚠️ Stopped reading Arti output: {}", e);
+                    break;
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            debug!("[arti] {}", line);
+            if let Some(file) = &file {
+                if let Err(e) = file.lock().unwrap().write_line(&line) {
+                    warn!("⚠️ Failed to write arti.log: {}", e);
+                }
+            }
+
+            let (level, message) = parse_arti_line(&line, default_level);
+            if let Some(state) = app_state().lock().unwrap().as_ref() {
+                state.add_log(LogEntry {
+                    timestamp: Utc::now(),
+                    level,
+                    message,
+                    source: ARTI_LOG_SOURCE.to_string(),
+                    mode: None, // Arti is shared by every eltord mode
+                });
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arti_line() {
+        let line = "2024-05-01T12:00:00.123456Z  WARN tor_guardmgr::guard: Guard unreachable";
+        assert_eq!(
+            parse_arti_line(line, "INFO"),
+            ("WARN".to_string(), "tor_guardmgr::guard: Guard unreachable".to_string())
+        );
+        assert_eq!(
+            parse_arti_line(&strip_ansi("2024-05-01T12:00:00Z \x1b[32m INFO\x1b[0m arti: Listening"), "WARN").0,
+            "INFO"
+        );
+        assert_eq!(parse_arti_line("error: unexpected argument '-x'", "INFO").0, "ERROR");
+        assert_eq!(parse_arti_line("Bootstrapped", "WARN"), ("WARN".to_string(), "Bootstrapped".to_string()));
+    }
+
+    #[test]
+    fn test_rotating_log() {
+        let dir = std::env::temp_dir().join(format!("eltor-arti-log-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("arti.log");
+        let mut log = RotatingLog::new(path.clone());
+
+        let line = "x".repeat(1024 * 1024 - 1);
+        for _ in 0..(MAX_LOG_BYTES as usize / line.len() + 1) * 2 {
+            log.write_line(&line).unwrap();
+        }
+        log.write_line("latest").unwrap();
+
+        assert!(fs::read_to_string(&path).unwrap().ends_with("latest\n"));
+        assert!(log.rotated_path(1).exists());
+        assert!(fs::metadata(&path).unwrap().len() <= MAX_LOG_BYTES);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
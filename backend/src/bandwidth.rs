@@ -0,0 +1,315 @@
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use log::debug;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::broadcast;
+
+use crate::eltor::EltorMode;
+
+/// Per-second samples kept per mode (5 minutes)
+const SECOND_HISTORY: usize = 300;
+/// Per-minute samples kept per mode (24 hours)
+const MINUTE_HISTORY: usize = 1440;
+
+/// Traffic over one interval (a second or a minute)
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct BandwidthSample {
+    /// Start of the interval
+    pub timestamp: DateTime<Utc>,
+    /// Bytes Tor read during the interval (BW event)
+    pub read_bytes: u64,
+    /// Bytes Tor wrote during the interval (BW event)
+    pub written_bytes: u64,
+    /// Bytes read on application streams (STREAM_BW, client side only)
+    pub stream_read_bytes: u64,
+    /// Bytes written on application streams (STREAM_BW, client side only)
+    pub stream_written_bytes: u64,
+}
+
+impl BandwidthSample {
+    fn add(&mut self, other: &BandwidthSample) {
+        self.read_bytes += other.read_bytes;
+        self.written_bytes += other.written_bytes;
+        self.stream_read_bytes += other.stream_read_bytes;
+        self.stream_written_bytes += other.stream_written_bytes;
+    }
+}
+
+/// Current rates and totals for one eltord mode
+#[derive(Debug, Clone, Serialize)]
+pub struct BandwidthStatus {
+    pub mode: String,
+    /// Bytes per second over the last second
+    pub read_rate: u64,
+    pub write_rate: u64,
+    /// Average bytes per second over the last minute
+    pub read_rate_1m: u64,
+    pub write_rate_1m: u64,
+    /// Totals since the control connection was established
+    pub total_read: u64,
+    pub total_written: u64,
+    pub total_stream_read: u64,
+    pub total_stream_written: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Rolling traffic history for one eltord mode, oldest first
+#[derive(Debug, Clone, Serialize)]
+pub struct BandwidthHistory {
+    pub mode: String,
+    pub seconds: Vec<BandwidthSample>,
+    /// Completed minutes; the one in progress is in `current_minute`
+    pub minutes: Vec<BandwidthSample>,
+    pub current_minute: Option<BandwidthSample>,
+}
+
+#[derive(Default)]
+struct ModeBandwidth {
+    seconds: VecDeque<BandwidthSample>,
+    minutes: VecDeque<BandwidthSample>,
+    current_minute: Option<BandwidthSample>,
+    /// STREAM_BW bytes seen since the last BW event
+    pending_stream_read: u64,
+    pending_stream_written: u64,
+    total: BandwidthSample,
+}
+
+impl ModeBandwidth {
+    fn record_second(&mut self, mut sample: BandwidthSample) {
+        sample.stream_read_bytes = std::mem::take(&mut self.pending_stream_read);
+        sample.stream_written_bytes = std::mem::take(&mut self.pending_stream_written);
+        self.total.add(&sample);
+
+        let minute = sample
+            .timestamp
+            .duration_trunc(TimeDelta::minutes(1))
+            .unwrap_or(sample.timestamp);
+        match &mut self.current_minute {
+            Some(current) if current.timestamp == minute => current.add(&sample),
+            current => {
+                if let Some(finished) = current.take() {
+                    push_capped(&mut self.minutes, finished, MINUTE_HISTORY);
+                }
+                let mut started = sample.clone();
+                started.timestamp = minute;
+                *current = Some(started);
+            }
+        }
+
+        push_capped(&mut self.seconds, sample, SECOND_HISTORY);
+    }
+
+    fn status(&self, mode: &str) -> BandwidthStatus {
+        let latest = self.seconds.back().cloned().unwrap_or_default();
+        let last_minute = self.seconds.iter().rev().take(60);
+        let count = last_minute.len().max(1) as u64;
+        let (read, written) = last_minute.fold((0, 0), |(r, w), s| (r + s.read_bytes, w + s.written_bytes));
+
+        BandwidthStatus {
+            mode: mode.to_string(),
+            read_rate: latest.read_bytes,
+            write_rate: latest.written_bytes,
+            read_rate_1m: read / count,
+            write_rate_1m: written / count,
+            total_read: self.total.read_bytes,
+            total_written: self.total.written_bytes,
+            total_stream_read: self.total.stream_read_bytes,
+            total_stream_written: self.total.stream_written_bytes,
+            updated_at: latest.timestamp,
+        }
+    }
+}
+
+fn push_capped(queue: &mut VecDeque<BandwidthSample>, sample: BandwidthSample, cap: usize) {
+    if queue.len() >= cap {
+        queue.pop_front();
+    }
+    queue.push_back(sample);
+}
+
+/// Parse a BW event body: `BytesRead BytesWritten [extra keywords]`
+pub fn parse_bw_event(body: &str) -> Option<(u64, u64)> {
+    let mut parts = body.split_whitespace();
+    let read = parts.next()?.parse().ok()?;
+    let written = parts.next()?.parse().ok()?;
+    Some((read, written))
+}
+
+/// Parse a STREAM_BW event body: `StreamID BytesWritten BytesRead [Time]`
+///
+/// Returns (read, written) in the same order as `parse_bw_event`.
+pub fn parse_stream_bw_event(body: &str) -> Option<(u64, u64)> {
+    let mut parts = body.split_whitespace();
+    parts.next()?;
+    let written = parts.next()?.parse().ok()?;
+    let read = parts.next()?.parse().ok()?;
+    Some((read, written))
+}
+
+struct BandwidthTracker {
+    modes: Mutex<HashMap<String, ModeBandwidth>>,
+    sender: broadcast::Sender<BandwidthStatus>,
+}
+
+static TRACKER: OnceLock<BandwidthTracker> = OnceLock::new();
+
+fn tracker() -> &'static BandwidthTracker {
+    TRACKER.get_or_init(|| {
+        let (sender, _) = broadcast::channel(100);
+        BandwidthTracker {
+            modes: Mutex::new(HashMap::new()),
+            sender,
+        }
+    })
+}
+
+/// Start a fresh history for a mode (new control connection)
+pub(crate) fn start_tracking(mode: &EltorMode) {
+    tracker()
+        .modes
+        .lock()
+        .unwrap()
+        .insert(mode.to_string().to_string(), ModeBandwidth::default());
+}
+
+/// Forget the history for a mode (eltord stopped)
+pub(crate) fn stop_tracking(mode: &EltorMode) {
+    tracker().modes.lock().unwrap().remove(mode.to_string());
+}
+
+/// Record a BW event, which Tor sends once per second
+pub(crate) fn apply_bw_event(mode: &EltorMode, body: &str) {
+    let Some((read, written)) = parse_bw_event(body) else {
+        debug!("Ignoring unparseable BW event: {}", body);
+        return;
+    };
+
+    let status = {
+        let mut modes = tracker().modes.lock().unwrap();
+        let Some(bandwidth) = modes.get_mut(mode.to_string()) else {
+            return;
+        };
+        bandwidth.record_second(BandwidthSample {
+            timestamp: Utc::now().duration_trunc(TimeDelta::seconds(1)).unwrap_or_else(|_| Utc::now()),
+            read_bytes: read,
+            written_bytes: written,
+            ..Default::default()
+        });
+        bandwidth.status(mode.to_string())
+    };
+    let _ = tracker().sender.send(status);
+}
+
+/// Add a STREAM_BW event to the second in progress
+pub(crate) fn apply_stream_bw_event(mode: &EltorMode, body: &str) {
+    let Some((read, written)) = parse_stream_bw_event(body) else {
+        debug!("Ignoring unparseable STREAM_BW event: {}", body);
+        return;
+    };
+
+    let mut modes = tracker().modes.lock().unwrap();
+    if let Some(bandwidth) = modes.get_mut(mode.to_string()) {
+        bandwidth.pending_stream_read += read;
+        bandwidth.pending_stream_written += written;
+    }
+}
+
+/// Current rates and totals for a mode ("client", "relay" or "both")
+pub fn get_bandwidth(mode: &str) -> Option<BandwidthStatus> {
+    tracker().modes.lock().unwrap().get(mode).map(|b| b.status(mode))
+}
+
+/// Current rates and totals for every mode we are tracking
+pub fn get_all_bandwidth() -> Vec<BandwidthStatus> {
+    let mut statuses: Vec<_> = tracker()
+        .modes
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(mode, b)| b.status(mode))
+        .collect();
+    statuses.sort_by(|a, b| a.mode.cmp(&b.mode));
+    statuses
+}
+
+/// Per-second and per-minute history for a mode
+pub fn get_bandwidth_history(mode: &str) -> Option<BandwidthHistory> {
+    tracker().modes.lock().unwrap().get(mode).map(|b| BandwidthHistory {
+        mode: mode.to_string(),
+        seconds: b.seconds.iter().cloned().collect(),
+        minutes: b.minutes.iter().cloned().collect(),
+        current_minute: b.current_minute.clone(),
+    })
+}
+
+/// Subscribe to per-second bandwidth updates for all modes
+pub fn subscribe_bandwidth() -> broadcast::Receiver<BandwidthStatus> {
+    tracker().sender.subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(timestamp: &str, read: u64, written: u64) -> BandwidthSample {
+        BandwidthSample {
+            timestamp: timestamp.parse().unwrap(),
+            read_bytes: read,
+            written_bytes: written,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_bw_events() {
+        assert_eq!(parse_bw_event("1024 2048"), Some((1024, 2048)));
+        assert_eq!(parse_bw_event("10 20 OR=1:2 DIR=3:4"), Some((10, 20)));
+        assert_eq!(parse_bw_event("garbage"), None);
+
+        // STREAM_BW puts written before read
+        assert_eq!(parse_stream_bw_event("42 100 5000 2024-01-01T00:00:00.000000"), Some((5000, 100)));
+        assert_eq!(parse_stream_bw_event("42"), None);
+    }
+
+    #[test]
+    fn test_minute_rollup() {
+        let mut bandwidth = ModeBandwidth {
+            pending_stream_read: 7,
+            ..Default::default()
+        };
+        bandwidth.record_second(sample("2024-01-01T00:00:58Z", 100, 10));
+        bandwidth.record_second(sample("2024-01-01T00:00:59Z", 200, 20));
+        bandwidth.record_second(sample("2024-01-01T00:01:00Z", 50, 5));
+
+        assert_eq!(bandwidth.seconds.len(), 3);
+        assert_eq!(bandwidth.seconds[0].stream_read_bytes, 7);
+        assert_eq!(bandwidth.seconds[1].stream_read_bytes, 0);
+
+        assert_eq!(bandwidth.minutes.len(), 1);
+        assert_eq!(bandwidth.minutes[0].read_bytes, 300);
+        assert_eq!(bandwidth.minutes[0].written_bytes, 30);
+        assert_eq!(bandwidth.minutes[0].timestamp, "2024-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap());
+
+        let status = bandwidth.status("client");
+        assert_eq!(status.read_rate, 50);
+        assert_eq!(status.read_rate_1m, 350 / 3);
+        assert_eq!(status.total_read, 350);
+        assert_eq!(status.total_stream_read, 7);
+    }
+
+    #[test]
+    fn test_second_history_is_capped() {
+        let mut bandwidth = ModeBandwidth::default();
+        let start: DateTime<Utc> = "2024-01-01T00:00:00Z".parse().unwrap();
+        for i in 0..(SECOND_HISTORY + 10) {
+            bandwidth.record_second(BandwidthSample {
+                timestamp: start + TimeDelta::seconds(i as i64),
+                read_bytes: 1,
+                ..Default::default()
+            });
+        }
+        assert_eq!(bandwidth.seconds.len(), SECOND_HISTORY);
+        assert_eq!(bandwidth.total.read_bytes, (SECOND_HISTORY + 10) as u64);
+    }
+}
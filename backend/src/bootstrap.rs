@@ -0,0 +1,346 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use crate::control::{self, ControlClient};
+use crate::eltor::EltorMode;
+use crate::paths::PathConfig;
+
+/// How often to poll `status/bootstrap-phase` until bootstrap is done
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long to wait between attempts to reach the control port
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bootstrap progress for one eltord mode
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BootstrapStatus {
+    pub mode: String,
+    /// 0-100, as reported by Tor
+    pub progress: u8,
+    /// Machine-readable phase, e.g. "conn_done", "loading_descriptors", "done"
+    pub tag: String,
+    /// Human-readable phase description
+    pub summary: String,
+    /// Set when Tor reports a bootstrap problem (WARN BOOTSTRAP ...)
+    pub warning: Option<String>,
+    /// Whether we currently hold a control-port connection to this eltord
+    pub control_connected: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BootstrapStatus {
+    fn waiting(mode: &EltorMode) -> Self {
+        Self {
+            mode: mode.to_string().to_string(),
+            progress: 0,
+            tag: "starting".to_string(),
+            summary: "Waiting for Tor control port".to_string(),
+            warning: None,
+            control_connected: false,
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.progress >= 100
+    }
+}
+
+/// Parsed `BOOTSTRAP` status line, from either GETINFO or a STATUS_CLIENT event
+#[derive(Debug, Clone, PartialEq)]
+pub struct BootstrapPhase {
+    pub progress: u8,
+    pub tag: String,
+    pub summary: String,
+    pub warning: Option<String>,
+}
+
+/// Parse a bootstrap phase line such as
+/// `NOTICE BOOTSTRAP PROGRESS=45 TAG=requesting_descriptors SUMMARY="Asking for relay descriptors"`
+pub fn parse_bootstrap_phase(line: &str) -> Option<BootstrapPhase> {
+    let args = control::split_arguments(line);
+    let severity = args.first()?;
+    if args.get(1).map(String::as_str) != Some("BOOTSTRAP") {
+        return None;
+    }
+
+    let keywords = control::parse_keywords(line);
+    let progress = keywords.get("PROGRESS")?.parse::<u8>().ok()?.min(100);
+
+    // Tor only includes WARNING on problem reports, which are sent at WARN severity
+    let warning = if severity == "WARN" {
+        let mut warning = keywords
+            .get("WARNING")
+            .cloned()
+            .unwrap_or_else(|| "Bootstrap problem".to_string());
+        if let Some(reason) = keywords.get("REASON") {
+            warning = format!("{} ({})", warning, reason);
+        }
+        Some(warning)
+    } else {
+        None
+    };
+
+    Some(BootstrapPhase {
+        progress,
+        tag: keywords.get("TAG").cloned().unwrap_or_default(),
+        summary: keywords.get("SUMMARY").cloned().unwrap_or_default(),
+        warning,
+    })
+}
+
+struct BootstrapTracker {
+    statuses: Mutex<HashMap<String, BootstrapStatus>>,
+    monitors: Mutex<HashMap<String, CancellationToken>>,
+    sender: broadcast::Sender<BootstrapStatus>,
+}
+
+static TRACKER: OnceLock<BootstrapTracker> = OnceLock::new();
+
+fn tracker() -> &'static BootstrapTracker {
+    TRACKER.get_or_init(|| {
+        let (sender, _) = broadcast::channel(100);
+        BootstrapTracker {
+            statuses: Mutex::new(HashMap::new()),
+            monitors: Mutex::new(HashMap::new()),
+            sender,
+        }
+    })
+}
+
+fn publish(status: BootstrapStatus) {
+    let tracker = tracker();
+    {
+        let mut statuses = tracker.statuses.lock().unwrap();
+        if let Some(previous) = statuses.get(&status.mode) {
+            // Only broadcast real changes - polling repeats the same phase a lot
+            let unchanged = previous.progress == status.progress
+                && previous.tag == status.tag
+                && previous.warning == status.warning
+                && previous.control_connected == status.control_connected;
+            if unchanged {
+                return;
+            }
+        }
+        statuses.insert(status.mode.clone(), status.clone());
+    }
+
+    if let Some(warning) = &status.warning {
+        warn!("⚠️ [{}] Bootstrap problem at {}%: {}", status.mode, status.progress, warning);
+    } else if status.control_connected {
+        info!("⏳ [{}] Bootstrapped {}% ({}): {}", status.mode, status.progress, status.tag, status.summary);
+    }
+
+    let _ = tracker.sender.send(status);
+}
+
+fn apply_phase(mode: &EltorMode, phase: BootstrapPhase) {
+    // Problem reports repeat the current progress; keep the warning until progress moves on
+    let warning = phase.warning.or_else(|| {
+        get_bootstrap_status(mode.to_string())
+            .filter(|s| s.progress == phase.progress)
+            .and_then(|s| s.warning)
+    });
+
+    publish(BootstrapStatus {
+        mode: mode.to_string().to_string(),
+        progress: phase.progress,
+        tag: phase.tag,
+        summary: phase.summary,
+        warning,
+        control_connected: true,
+        updated_at: Utc::now(),
+    });
+}
+
+/// Current bootstrap status for a mode ("client", "relay" or "both")
+pub fn get_bootstrap_status(mode: &str) -> Option<BootstrapStatus> {
+    tracker().statuses.lock().unwrap().get(mode).cloned()
+}
+
+/// Current bootstrap status for every mode we are tracking
+pub fn get_all_bootstrap_status() -> Vec<BootstrapStatus> {
+    let mut statuses: Vec<_> = tracker().statuses.lock().unwrap().values().cloned().collect();
+    statuses.sort_by(|a, b| a.mode.cmp(&b.mode));
+    statuses
+}
+
+/// Subscribe to bootstrap status changes for all modes
+pub fn subscribe_bootstrap() -> broadcast::Receiver<BootstrapStatus> {
+    tracker().sender.subscribe()
+}
+
+/// Start watching bootstrap progress for an eltord mode
+///
+/// Safe to call from sync code: like the Arti startup helper, the monitor
+/// gets its own thread and runtime so it outlives the caller. Any previous
+/// monitor for the same mode is cancelled first.
+pub fn start_bootstrap_monitor(mode: EltorMode, path_config: PathConfig) {
+    let key = mode.to_string().to_string();
+    let token = CancellationToken::new();
+
+    {
+        let mut monitors = tracker().monitors.lock().unwrap();
+        if let Some(previous) = monitors.insert(key.clone(), token.clone()) {
+            previous.cancel();
+        }
+    }
+    tracker().statuses.lock().unwrap().remove(&key);
+    publish(BootstrapStatus::waiting(&mode));
+
+    info!("📡 Starting bootstrap monitor for {} mode", mode);
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Runtime::new() {
+            Ok(rt) => rt,
+            Err(e) => {
+                warn!("⚠️ Failed to create runtime for bootstrap monitor: {}", e);
+                return;
+            }
+        };
+        rt.block_on(async {
+            tokio::select! {
+                _ = token.cancelled() => {}
+                _ = run_monitor(&mode, &path_config) => {}
+            }
+        });
+        info!("📡 Bootstrap monitor for {} mode stopped", mode);
+    });
+}
+
+/// Stop watching bootstrap progress for a mode and forget its status
+pub fn stop_bootstrap_monitor(mode: &EltorMode) {
+    let key = mode.to_string();
+    if let Some(token) = tracker().monitors.lock().unwrap().remove(key) {
+        token.cancel();
+    }
+    tracker().statuses.lock().unwrap().remove(key);
+    crate::circuits::stop_tracking(mode);
+    crate::bandwidth::stop_tracking(mode);
+}
+
+async fn run_monitor(mode: &EltorMode, path_config: &PathConfig) {
+    loop {
+        match control::connect_for_mode(mode, path_config).await {
+            Ok(client) => {
+                if let Err(e) = watch_connection(mode, &client).await {
+                    warn!("⚠️ [{}] Lost bootstrap control connection: {}", mode, e);
+                }
+                crate::circuits::stop_tracking(mode);
+                publish(BootstrapStatus::waiting(mode));
+            }
+            Err(control::ControlError::Authentication(e)) => {
+                // Retrying with the same password won't help
+                warn!("⚠️ [{}] Bootstrap monitor could not authenticate: {}", mode, e);
+                let mut status = BootstrapStatus::waiting(mode);
+                status.warning = Some(format!("Control port authentication failed: {}", e));
+                publish(status);
+                return;
+            }
+            Err(_) => {
+                // eltord is still starting up - the control port isn't open yet
+            }
+        }
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+async fn watch_connection(mode: &EltorMode, client: &ControlClient) -> Result<(), control::ControlError> {
+    let mut events = client.subscribe_events();
+    // CIRC and BW ride along on the same connection to keep the circuit
+    // table and bandwidth history live
+    let events_with_streams = ["STATUS_CLIENT", "CIRC", "BW", "STREAM_BW"];
+    match client.set_events(&events_with_streams).await {
+        // Builds without STREAM_BW still report overall bandwidth
+        Err(control::ControlError::Reply { .. }) => client.set_events(&events_with_streams[..3]).await?,
+        result => result?,
+    }
+    crate::circuits::start_tracking(mode, client).await?;
+    crate::bandwidth::start_tracking(mode);
+
+    // Once bootstrap is done STATUS_CLIENT events are enough; a reconnect
+    // lands back here and polls again
+    let mut polling = true;
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = poll.tick(), if polling => {
+                let line = client.get_info_value("status/bootstrap-phase").await?;
+                if let Some(phase) = parse_bootstrap_phase(&line) {
+                    if phase.tag == "done" {
+                        info!("📡 [{}] Bootstrap done, following STATUS_CLIENT events only", mode);
+                        polling = false;
+                    }
+                    apply_phase(mode, phase);
+                }
+            }
+            event = events.recv() => {
+                match event {
+                    Ok(event) if event.kind == "CIRC" => {
+                        crate::circuits::apply_circ_event(mode, &event.body);
+                    }
+                    Ok(event) if event.kind == "BW" => {
+                        crate::bandwidth::apply_bw_event(mode, &event.body);
+                    }
+                    Ok(event) if event.kind == "STREAM_BW" => {
+                        crate::bandwidth::apply_stream_bw_event(mode, &event.body);
+                    }
+                    Ok(event) => {
+                        if let Some(phase) = parse_bootstrap_phase(&event.body) {
+                            apply_phase(mode, phase);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => {
+                        return Err(control::ControlError::Closed);
+                    }
+                }
+            }
+        }
+
+        if !client.is_connected() {
+            return Err(control::ControlError::Closed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bootstrap_phase() {
+        let phase = parse_bootstrap_phase(
+            "NOTICE BOOTSTRAP PROGRESS=45 TAG=requesting_descriptors SUMMARY=\"Asking for relay descriptors\"",
+        )
+        .unwrap();
+        assert_eq!(phase.progress, 45);
+        assert_eq!(phase.tag, "requesting_descriptors");
+        assert_eq!(phase.summary, "Asking for relay descriptors");
+        assert_eq!(phase.warning, None);
+
+        let done = parse_bootstrap_phase("NOTICE BOOTSTRAP PROGRESS=100 TAG=done SUMMARY=\"Done\"").unwrap();
+        assert_eq!(done.progress, 100);
+    }
+
+    #[test]
+    fn test_parse_bootstrap_warning() {
+        let phase = parse_bootstrap_phase(
+            "WARN BOOTSTRAP PROGRESS=10 TAG=conn_done SUMMARY=\"Connected to a relay\" WARNING=\"Connection refused\" REASON=CONNECTREFUSED COUNT=3 RECOMMENDATION=ignore",
+        )
+        .unwrap();
+        assert_eq!(phase.progress, 10);
+        assert_eq!(phase.warning.as_deref(), Some("Connection refused (CONNECTREFUSED)"));
+    }
+
+    #[test]
+    fn test_parse_ignores_other_status_events() {
+        assert!(parse_bootstrap_phase("NOTICE CIRCUIT_ESTABLISHED").is_none());
+        assert!(parse_bootstrap_phase("NOTICE ENOUGH_DIR_INFO").is_none());
+        assert!(parse_bootstrap_phase("").is_none());
+    }
+}
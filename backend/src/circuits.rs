@@ -0,0 +1,286 @@
+use log::{debug, warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, OnceLock};
+
+use crate::control::{self, ControlClient};
+use crate::eltor::EltorMode;
+use crate::paths::PathConfig;
+use crate::routes::ip::{is_ip_database_loaded, lookup_ip_location, IpLocationResponse};
+
+/// One relay in a circuit path
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitHop {
+    /// 1 = guard, last = exit
+    pub hop: usize,
+    pub fingerprint: String,
+    pub nickname: Option<String>,
+    pub ip: Option<String>,
+    pub location: Option<IpLocationResponse>,
+}
+
+/// A circuit as reported by `GETINFO circuit-status` or a CIRC event
+#[derive(Debug, Clone, Serialize)]
+pub struct Circuit {
+    pub id: u32,
+    /// LAUNCHED, BUILT, EXTENDED, FAILED or CLOSED
+    pub status: String,
+    pub purpose: Option<String>,
+    pub build_flags: Vec<String>,
+    pub time_created: Option<String>,
+    pub relays: Vec<CircuitHop>,
+}
+
+impl Circuit {
+    pub fn is_built(&self) -> bool {
+        self.status == "BUILT"
+    }
+
+    /// Short human readable path, e.g. "guard (US) → middle (DE) → exit (NL)"
+    pub fn describe(&self) -> String {
+        self.relays
+            .iter()
+            .map(|hop| {
+                let name = hop
+                    .nickname
+                    .clone()
+                    .unwrap_or_else(|| hop.fingerprint.chars().take(8).collect());
+                match &hop.location {
+                    Some(location) => format!("{} ({})", name, location.country_code),
+                    None => name,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+}
+
+/// Parse a circuit-status line or CIRC event body:
+/// `7 BUILT $FP~nick,$FP~nick BUILD_FLAGS=NEED_CAPACITY PURPOSE=GENERAL TIME_CREATED=...`
+pub fn parse_circuit_line(line: &str) -> Option<Circuit> {
+    let args = control::split_arguments(line);
+    let id = args.first()?.parse::<u32>().ok()?;
+    let status = args.get(1)?.clone();
+
+    // The path is optional (e.g. LAUNCHED circuits have none yet)
+    let relays = match args.get(2) {
+        Some(path) if !path.contains('=') => path
+            .split(',')
+            .enumerate()
+            .map(|(i, hop)| parse_hop(i + 1, hop))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let keywords = control::parse_keywords(line);
+    let build_flags = keywords
+        .get("BUILD_FLAGS")
+        .map(|flags| flags.split(',').map(|f| f.to_string()).collect())
+        .unwrap_or_default();
+
+    Some(Circuit {
+        id,
+        status,
+        purpose: keywords.get("PURPOSE").cloned(),
+        build_flags,
+        time_created: keywords.get("TIME_CREATED").cloned(),
+        relays,
+    })
+}
+
+/// Parse `$FINGERPRINT~nickname` (or `$FINGERPRINT=nickname`, or a bare fingerprint)
+fn parse_hop(hop: usize, text: &str) -> CircuitHop {
+    let text = text.trim_start_matches('$');
+    let (fingerprint, nickname) = match text.split_once(['~', '=']) {
+        Some((fingerprint, nickname)) => (fingerprint, Some(nickname.to_string())),
+        None => (text, None),
+    };
+
+    CircuitHop {
+        hop,
+        fingerprint: fingerprint.to_uppercase(),
+        nickname,
+        ip: None,
+        location: None,
+    }
+}
+
+/// Pull the relay address out of a `GETINFO ns/id/<fp>` router status entry
+///
+/// The `r` line looks like `r nickname identity digest date time IP ORPort DirPort`.
+pub fn parse_router_status_ip(entry: &str) -> Option<String> {
+    entry
+        .lines()
+        .find(|line| line.starts_with("r "))
+        .and_then(|line| line.split_whitespace().nth(6))
+        .map(|ip| ip.to_string())
+}
+
+struct CircuitTracker {
+    /// Live circuits per mode, maintained from CIRC events by the control monitor
+    circuits: Mutex<HashMap<String, BTreeMap<u32, Circuit>>>,
+    /// Relay fingerprint -> IP, relays don't move often enough to matter
+    relay_ips: Mutex<HashMap<String, String>>,
+}
+
+static TRACKER: OnceLock<CircuitTracker> = OnceLock::new();
+
+fn tracker() -> &'static CircuitTracker {
+    TRACKER.get_or_init(|| CircuitTracker {
+        circuits: Mutex::new(HashMap::new()),
+        relay_ips: Mutex::new(HashMap::new()),
+    })
+}
+
+/// Seed the live circuit table for a mode from `GETINFO circuit-status`
+pub(crate) async fn start_tracking(mode: &EltorMode, client: &ControlClient) -> Result<(), control::ControlError> {
+    let circuits = fetch_circuit_status(client).await?;
+    let table = circuits.into_iter().map(|c| (c.id, c)).collect();
+    tracker()
+        .circuits
+        .lock()
+        .unwrap()
+        .insert(mode.to_string().to_string(), table);
+    Ok(())
+}
+
+/// Forget the live circuit table for a mode (control connection went away)
+pub(crate) fn stop_tracking(mode: &EltorMode) {
+    tracker().circuits.lock().unwrap().remove(mode.to_string());
+}
+
+/// Apply a CIRC event to the live circuit table
+pub(crate) fn apply_circ_event(mode: &EltorMode, body: &str) {
+    let Some(circuit) = parse_circuit_line(body) else {
+        debug!("Ignoring unparseable CIRC event: {}", body);
+        return;
+    };
+
+    let mut circuits = tracker().circuits.lock().unwrap();
+    let Some(table) = circuits.get_mut(mode.to_string()) else {
+        return;
+    };
+    match circuit.status.as_str() {
+        "CLOSED" | "FAILED" => {
+            table.remove(&circuit.id);
+        }
+        _ => {
+            table.insert(circuit.id, circuit);
+        }
+    }
+}
+
+async fn fetch_circuit_status(client: &ControlClient) -> Result<Vec<Circuit>, control::ControlError> {
+    let status = client.get_info_value("circuit-status").await?;
+    Ok(status.lines().filter_map(parse_circuit_line).collect())
+}
+
+/// List built circuits for a mode with per-hop IP and geolocation
+///
+/// Uses the live table kept by the control monitor when there is one,
+/// otherwise asks Tor directly.
+pub async fn get_circuits(mode: &EltorMode, path_config: &PathConfig) -> Result<Vec<Circuit>, String> {
+    let tracked: Option<Vec<Circuit>> = tracker()
+        .circuits
+        .lock()
+        .unwrap()
+        .get(mode.to_string())
+        .map(|table| table.values().cloned().collect());
+
+    let mut client = None;
+    let circuits = match tracked {
+        Some(circuits) => circuits,
+        None => {
+            let c = control::connect_for_mode(mode, path_config).await?;
+            let circuits = fetch_circuit_status(&c).await?;
+            client = Some(c);
+            circuits
+        }
+    };
+    let mut circuits: Vec<Circuit> = circuits.into_iter().filter(|c| c.is_built()).collect();
+
+    // Look up relay IPs we haven't seen before
+    let mut unknown: Vec<String> = {
+        let relay_ips = tracker().relay_ips.lock().unwrap();
+        circuits
+            .iter()
+            .flat_map(|c| c.relays.iter())
+            .filter(|hop| !relay_ips.contains_key(&hop.fingerprint))
+            .map(|hop| hop.fingerprint.clone())
+            .collect()
+    };
+    // Circuits usually share a guard
+    unknown.sort();
+    unknown.dedup();
+    if !unknown.is_empty() {
+        let client = match client {
+            Some(client) => client,
+            None => control::connect_for_mode(mode, path_config).await?,
+        };
+        for fingerprint in unknown {
+            match client.get_info_value(&format!("ns/id/{}", fingerprint)).await {
+                Ok(entry) => {
+                    if let Some(ip) = parse_router_status_ip(&entry) {
+                        tracker().relay_ips.lock().unwrap().insert(fingerprint, ip);
+                    }
+                }
+                Err(e) => debug!("No router status for {}: {}", fingerprint, e),
+            }
+        }
+    }
+
+    // Without the database only private addresses resolve, so a failed
+    // lookup is expected and not worth a warning per hop on every refresh
+    let database_loaded = is_ip_database_loaded();
+    let relay_ips = tracker().relay_ips.lock().unwrap().clone();
+    for hop in circuits.iter_mut().flat_map(|c| c.relays.iter_mut()) {
+        hop.ip = relay_ips.get(&hop.fingerprint).cloned();
+        if let Some(ip) = &hop.ip {
+            match lookup_ip_location(ip) {
+                Ok(location) => hop.location = Some(location),
+                Err(e) if database_loaded => warn!("⚠️ Could not geolocate relay {} ({}): {}", hop.fingerprint, ip, e),
+                Err(e) => debug!("Skipping geolocation of relay {} ({}): {}", hop.fingerprint, ip, e),
+            }
+        }
+    }
+
+    Ok(circuits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_circuit_line() {
+        let circuit = parse_circuit_line(
+            "7 BUILT $AAAA1111~guardrelay,$bbbb2222~middle,$CCCC3333~exitrelay BUILD_FLAGS=NEED_CAPACITY,NEED_UPTIME PURPOSE=GENERAL TIME_CREATED=2024-01-01T00:00:00.000000",
+        )
+        .unwrap();
+
+        assert_eq!(circuit.id, 7);
+        assert!(circuit.is_built());
+        assert_eq!(circuit.purpose.as_deref(), Some("GENERAL"));
+        assert_eq!(circuit.build_flags, vec!["NEED_CAPACITY", "NEED_UPTIME"]);
+        assert_eq!(circuit.relays.len(), 3);
+        assert_eq!(circuit.relays[1].fingerprint, "BBBB2222");
+        assert_eq!(circuit.relays[1].nickname.as_deref(), Some("middle"));
+        assert_eq!(circuit.relays[2].hop, 3);
+        assert_eq!(circuit.describe(), "guardrelay → middle → exitrelay");
+    }
+
+    #[test]
+    fn test_parse_circuit_without_path() {
+        let circuit = parse_circuit_line("12 LAUNCHED BUILD_FLAGS=NEED_CAPACITY PURPOSE=GENERAL").unwrap();
+        assert_eq!(circuit.status, "LAUNCHED");
+        assert!(circuit.relays.is_empty());
+        assert!(parse_circuit_line("").is_none());
+    }
+
+    #[test]
+    fn test_parse_router_status_ip() {
+        let entry = "r exitrelay AAAAAAAAAAAAAAAAAAAAAAAAAAA BBBBBBBBBBBBBBBBBBBBBBBBBBB 2024-01-01 00:00:00 203.0.113.5 9001 0\ns Exit Fast Running Valid\nw Bandwidth=1000";
+        assert_eq!(parse_router_status_ip(entry).as_deref(), Some("203.0.113.5"));
+        assert_eq!(parse_router_status_ip("s Running"), None);
+    }
+}
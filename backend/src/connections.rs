@@ -0,0 +1,304 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::socks::TargetAddress;
+
+/// Closed connections kept for the connection table
+const MAX_RECENT: usize = 200;
+/// Destinations kept in the traffic aggregate before the quietest is dropped
+const MAX_DESTINATIONS: usize = 1000;
+
+/// One proxied connection, live or recently closed
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub id: u64,
+    /// "socks", "http" or "transparent"
+    pub listener: &'static str,
+    pub client_addr: SocketAddr,
+    /// "host:port", unset until the handshake names a target
+    pub target: Option<String>,
+    /// Host part of the target, used for the per-destination totals
+    pub destination: Option<String>,
+    /// "socks5://host:port" that carried the connection, "direct" or "blocked"
+    pub upstream: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    /// Client to target
+    pub bytes_sent: u64,
+    /// Target to client
+    pub bytes_received: u64,
+    pub close_reason: Option<String>,
+}
+
+/// Byte and connection totals for a group of connections
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TrafficStats {
+    pub connections: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl TrafficStats {
+    fn add(&mut self, connection: &ConnectionInfo) {
+        self.connections += 1;
+        self.bytes_sent += connection.bytes_sent;
+        self.bytes_received += connection.bytes_received;
+    }
+
+    fn total_bytes(&self) -> u64 {
+        self.bytes_sent + self.bytes_received
+    }
+}
+
+/// Traffic totals for one destination host or upstream
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupTraffic {
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: TrafficStats,
+}
+
+/// Everything `/api/socks/connections` reports
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionsSnapshot {
+    pub active: Vec<ConnectionInfo>,
+    /// Most recently closed first
+    pub recent: Vec<ConnectionInfo>,
+    /// Every connection since startup, live ones included
+    pub totals: TrafficStats,
+    /// Busiest destination hosts first
+    pub destinations: Vec<GroupTraffic>,
+    /// Busiest upstreams first
+    pub upstreams: Vec<GroupTraffic>,
+}
+
+#[derive(Debug, Default)]
+struct EntryState {
+    target: Option<TargetAddress>,
+    upstream: Option<String>,
+    close_reason: Option<String>,
+}
+
+#[derive(Debug)]
+struct ConnectionEntry {
+    id: u64,
+    listener: &'static str,
+    client_addr: SocketAddr,
+    started_at: DateTime<Utc>,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    state: Mutex<EntryState>,
+}
+
+impl ConnectionEntry {
+    fn info(&self, closed_at: Option<DateTime<Utc>>) -> ConnectionInfo {
+        let state = self.state.lock().unwrap();
+        ConnectionInfo {
+            id: self.id,
+            listener: self.listener,
+            client_addr: self.client_addr,
+            target: state.target.as_ref().map(|target| target.to_string()),
+            destination: state.target.as_ref().map(destination_host),
+            upstream: state.upstream.clone(),
+            started_at: self.started_at,
+            closed_at,
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            close_reason: state.close_reason.clone(),
+        }
+    }
+}
+
+/// Host part of a target, without the port
+fn destination_host(target: &TargetAddress) -> String {
+    match target {
+        TargetAddress::IPv4(ip, _) => ip.to_string(),
+        TargetAddress::Domain(domain, _) => domain.to_ascii_lowercase(),
+        TargetAddress::IPv6(ip, _) => ip.to_string(),
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    active: BTreeMap<u64, Arc<ConnectionEntry>>,
+    recent: VecDeque<ConnectionInfo>,
+    /// Totals of closed connections; live ones are added when reporting
+    totals: TrafficStats,
+    destinations: HashMap<String, TrafficStats>,
+    upstreams: HashMap<String, TrafficStats>,
+}
+
+impl Registry {
+    fn record_closed(&mut self, connection: ConnectionInfo) {
+        self.totals.add(&connection);
+        if let Some(destination) = &connection.destination {
+            if !self.destinations.contains_key(destination) && self.destinations.len() >= MAX_DESTINATIONS {
+                let quietest = self
+                    .destinations
+                    .iter()
+                    .min_by_key(|(_, stats)| stats.total_bytes())
+                    .map(|(name, _)| name.clone());
+                if let Some(quietest) = quietest {
+                    self.destinations.remove(&quietest);
+                }
+            }
+            self.destinations.entry(destination.clone()).or_default().add(&connection);
+        }
+        if let Some(upstream) = &connection.upstream {
+            self.upstreams.entry(upstream.clone()).or_default().add(&connection);
+        }
+        self.recent.push_front(connection);
+        self.recent.truncate(MAX_RECENT);
+    }
+}
+
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(Registry::default()))
+}
+
+/// Handle for a connection in the table; moves it to the recent list when dropped
+pub struct TrackedConnection(Arc<ConnectionEntry>);
+
+impl TrackedConnection {
+    pub fn set_target(&self, target: &TargetAddress) {
+        self.0.state.lock().unwrap().target = Some(target.clone());
+    }
+
+    pub fn set_upstream(&self, upstream: impl ToString) {
+        self.0.state.lock().unwrap().upstream = Some(upstream.to_string());
+    }
+
+    pub fn add_sent(&self, bytes: usize) {
+        self.0.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn add_received(&self, bytes: usize) {
+        self.0.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Record why the connection ended; the first reason given wins
+    pub fn close(&self, reason: impl ToString) {
+        self.0.state.lock().unwrap().close_reason.get_or_insert_with(|| reason.to_string());
+    }
+}
+
+impl Drop for TrackedConnection {
+    fn drop(&mut self) {
+        // No reason yet means the task was cut off, e.g. by a router stop
+        self.close("aborted");
+        let mut registry = registry().lock().unwrap();
+        registry.active.remove(&self.0.id);
+        registry.record_closed(self.0.info(Some(Utc::now())));
+    }
+}
+
+/// Add a freshly accepted connection to the table
+pub fn track(listener: &'static str, client_addr: SocketAddr) -> TrackedConnection {
+    let mut registry = registry().lock().unwrap();
+    registry.next_id += 1;
+    let entry = Arc::new(ConnectionEntry {
+        id: registry.next_id,
+        listener,
+        client_addr,
+        started_at: Utc::now(),
+        bytes_sent: AtomicU64::new(0),
+        bytes_received: AtomicU64::new(0),
+        state: Mutex::new(EntryState::default()),
+    });
+    registry.active.insert(entry.id, entry.clone());
+    TrackedConnection(entry)
+}
+
+fn sorted_groups(groups: HashMap<String, TrafficStats>) -> Vec<GroupTraffic> {
+    let mut groups: Vec<GroupTraffic> = groups
+        .into_iter()
+        .map(|(name, stats)| GroupTraffic { name, stats })
+        .collect();
+    groups.sort_by(|a, b| b.stats.total_bytes().cmp(&a.stats.total_bytes()).then_with(|| a.name.cmp(&b.name)));
+    groups
+}
+
+/// Live and recent connections with traffic totals
+pub fn get_connections() -> ConnectionsSnapshot {
+    let registry = registry().lock().unwrap();
+    let active: Vec<ConnectionInfo> = registry.active.values().map(|entry| entry.info(None)).collect();
+
+    let mut totals = registry.totals.clone();
+    let mut destinations = registry.destinations.clone();
+    let mut upstreams = registry.upstreams.clone();
+    for connection in &active {
+        totals.add(connection);
+        if let Some(destination) = &connection.destination {
+            destinations.entry(destination.clone()).or_default().add(connection);
+        }
+        if let Some(upstream) = &connection.upstream {
+            upstreams.entry(upstream.clone()).or_default().add(connection);
+        }
+    }
+
+    ConnectionsSnapshot {
+        active,
+        recent: registry.recent.iter().cloned().collect(),
+        totals,
+        destinations: sorted_groups(destinations),
+        upstreams: sorted_groups(upstreams),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group<'a>(groups: &'a [GroupTraffic], name: &str) -> Option<&'a TrafficStats> {
+        groups.iter().find(|group| group.name == name).map(|group| &group.stats)
+    }
+
+    #[test]
+    fn test_connection_tracking() {
+        let client_addr: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let target = TargetAddress::Domain("Tracking-Test.example".to_string(), 443);
+
+        let connection = track("socks", client_addr);
+        connection.set_target(&target);
+        connection.set_upstream("socks5://127.0.0.1:18058");
+        connection.add_sent(100);
+        connection.add_received(2000);
+
+        // Live connections count towards the totals
+        let snapshot = get_connections();
+        let live = snapshot.active.iter().find(|c| c.id == connection.0.id).unwrap();
+        assert_eq!(live.target.as_deref(), Some("Tracking-Test.example:443"));
+        assert_eq!(live.close_reason, None);
+        let destination = group(&snapshot.destinations, "tracking-test.example").unwrap();
+        assert_eq!((destination.connections, destination.bytes_received), (1, 2000));
+
+        let id = connection.0.id;
+        connection.close("client closed");
+        connection.close("closed");
+        let second = track("http", client_addr);
+        let second_id = second.0.id;
+        second.set_target(&target);
+        second.add_sent(5);
+        drop(connection);
+        drop(second);
+
+        let snapshot = get_connections();
+        assert!(snapshot.active.iter().all(|c| c.id != id));
+        let closed = snapshot.recent.iter().find(|c| c.id == id).unwrap();
+        assert_eq!(closed.close_reason.as_deref(), Some("client closed"));
+        assert!(closed.closed_at.is_some());
+        let aborted = snapshot.recent.iter().find(|c| c.id == second_id).unwrap();
+        assert_eq!(aborted.close_reason.as_deref(), Some("aborted"));
+
+        let destination = group(&snapshot.destinations, "tracking-test.example").unwrap();
+        assert_eq!(destination.connections, 2);
+        assert_eq!(destination.bytes_sent, 105);
+        assert!(group(&snapshot.upstreams, "socks5://127.0.0.1:18058").is_some());
+    }
+}
@@ -0,0 +1,739 @@
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, oneshot, Mutex};
+
+use crate::eltor::EltorMode;
+use crate::paths::PathConfig;
+
+/// Default timeout for connecting to a control port and waiting for a reply
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors returned by the Tor control-protocol client
+#[derive(Debug)]
+pub enum ControlError {
+    /// Socket level failure while talking to the control port
+    Io(std::io::Error),
+    /// Timed out connecting or waiting for a reply
+    Timeout,
+    /// The control connection was closed by Tor (or our reader task exited)
+    Closed,
+    /// Tor sent something that does not follow the control-spec reply grammar
+    Protocol(String),
+    /// AUTHENTICATE was rejected
+    Authentication(String),
+    /// Tor answered a command with a non-2xx status
+    Reply { code: u16, message: String },
+}
+
+impl std::fmt::Display for ControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ControlError::Io(e) => write!(f, "Control port I/O error: {}", e),
+            ControlError::Timeout => write!(f, "Timed out waiting for Tor control port"),
+            ControlError::Closed => write!(f, "Tor control connection closed"),
+            ControlError::Protocol(msg) => write!(f, "Malformed control reply: {}", msg),
+            ControlError::Authentication(msg) => write!(f, "Control port authentication failed: {}", msg),
+            ControlError::Reply { code, message } => write!(f, "Tor replied {} {}", code, message),
+        }
+    }
+}
+
+impl std::error::Error for ControlError {}
+
+impl From<std::io::Error> for ControlError {
+    fn from(e: std::io::Error) -> Self {
+        ControlError::Io(e)
+    }
+}
+
+impl From<ControlError> for String {
+    fn from(e: ControlError) -> Self {
+        e.to_string()
+    }
+}
+
+/// A single line of a control reply
+///
+/// `data` is only set for data lines (`XYZ+...`), and holds the dot-decoded
+/// body that followed the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplyLine {
+    pub code: u16,
+    pub text: String,
+    pub data: Option<String>,
+}
+
+/// A complete (possibly multi-line) reply from the control port
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub code: u16,
+    pub lines: Vec<ReplyLine>,
+}
+
+impl Reply {
+    pub fn is_ok(&self) -> bool {
+        (200..300).contains(&self.code)
+    }
+
+    pub fn is_async(&self) -> bool {
+        (600..700).contains(&self.code)
+    }
+
+    /// Text of the final line (e.g. "OK")
+    pub fn message(&self) -> &str {
+        self.lines.last().map(|l| l.text.as_str()).unwrap_or("")
+    }
+
+    fn into_result(self) -> Result<Reply, ControlError> {
+        if self.is_ok() {
+            Ok(self)
+        } else {
+            Err(ControlError::Reply {
+                code: self.code,
+                message: self.message().to_string(),
+            })
+        }
+    }
+}
+
+/// Asynchronous event (650 reply) pushed by Tor after SETEVENTS
+#[derive(Debug, Clone, Serialize)]
+pub struct ControlEvent {
+    /// Event keyword, e.g. "CIRC", "BW", "STATUS_CLIENT"
+    pub kind: String,
+    /// Rest of the first line after the keyword
+    pub body: String,
+    /// Any additional lines (and data blocks) of a multi-line event
+    pub extra: Vec<String>,
+}
+
+impl ControlEvent {
+    fn from_reply(reply: &Reply) -> Option<Self> {
+        let first = reply.lines.first()?;
+        let (kind, body) = match first.text.split_once(' ') {
+            Some((kind, body)) => (kind.to_string(), body.to_string()),
+            None => (first.text.clone(), String::new()),
+        };
+
+        let mut extra = Vec::new();
+        if let Some(data) = &first.data {
+            extra.extend(data.lines().map(|l| l.to_string()));
+        }
+        for line in reply.lines.iter().skip(1) {
+            if line.text != "OK" {
+                extra.push(line.text.clone());
+            }
+            if let Some(data) = &line.data {
+                extra.extend(data.lines().map(|l| l.to_string()));
+            }
+        }
+
+        Some(ControlEvent { kind, body, extra })
+    }
+}
+
+/// Signals accepted by the SIGNAL command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Reload,
+    Shutdown,
+    Dump,
+    Debug,
+    Halt,
+    ClearDnsCache,
+    Newnym,
+    Heartbeat,
+    Active,
+    Dormant,
+}
+
+impl Signal {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Signal::Reload => "RELOAD",
+            Signal::Shutdown => "SHUTDOWN",
+            Signal::Dump => "DUMP",
+            Signal::Debug => "DEBUG",
+            Signal::Halt => "HALT",
+            Signal::ClearDnsCache => "CLEARDNSCACHE",
+            Signal::Newnym => "NEWNYM",
+            Signal::Heartbeat => "HEARTBEAT",
+            Signal::Active => "ACTIVE",
+            Signal::Dormant => "DORMANT",
+        }
+    }
+}
+
+/// Read one complete reply from a control connection
+///
+/// Handles mid lines (`250-`), data lines (`250+` followed by a dot-terminated
+/// body) and end lines (`250 `), per section 2.3 of control-spec.txt.
+pub async fn read_reply<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Reply, ControlError> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(ControlError::Closed);
+        }
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+
+        if trimmed.len() < 4 || !trimmed.is_char_boundary(3) {
+            return Err(ControlError::Protocol(format!("short reply line: {:?}", trimmed)));
+        }
+        let code = trimmed[..3]
+            .parse::<u16>()
+            .map_err(|_| ControlError::Protocol(format!("bad status code: {:?}", trimmed)))?;
+        let separator = trimmed.as_bytes()[3];
+        let text = trimmed[4..].to_string();
+
+        match separator {
+            b'-' => lines.push(ReplyLine { code, text, data: None }),
+            b'+' => {
+                let data = read_data_block(reader).await?;
+                lines.push(ReplyLine { code, text, data: Some(data) });
+            }
+            b' ' => {
+                lines.push(ReplyLine { code, text, data: None });
+                return Ok(Reply { code, lines });
+            }
+            other => {
+                return Err(ControlError::Protocol(format!(
+                    "unknown separator {:?} in {:?}",
+                    other as char, trimmed
+                )));
+            }
+        }
+    }
+}
+
+/// Read a dot-terminated data block, undoing the leading-dot escaping
+async fn read_data_block<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<String, ControlError> {
+    let mut data = Vec::new();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(ControlError::Closed);
+        }
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+        if trimmed == "." {
+            return Ok(data.join("\n"));
+        }
+        data.push(trimmed.strip_prefix('.').unwrap_or(trimmed).to_string());
+    }
+}
+
+/// Quote a value for SETCONF / AUTHENTICATE as a control-spec QuotedString
+pub fn quote_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\r' => quoted.push_str("\\r"),
+            '\n' => quoted.push_str("\\n"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Split a `key=value` reply line, unquoting the value when Tor quoted it
+fn split_key_value(text: &str) -> (String, Option<String>) {
+    match text.split_once('=') {
+        Some((key, value)) => (key.to_string(), Some(unquote(value))),
+        None => (text.to_string(), None),
+    }
+}
+
+fn unquote(value: &str) -> String {
+    let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) else {
+        return value.to_string();
+    };
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => {}
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Split event/reply arguments on spaces, keeping quoted strings together
+pub fn split_arguments(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => {
+                current.push(c);
+                escaped = true;
+            }
+            '"' => {
+                current.push(c);
+                in_quotes = !in_quotes;
+            }
+            ' ' if !in_quotes => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+/// Collect the `KEY=value` arguments of an event or reply line
+///
+/// Positional arguments are skipped and quoted values are unescaped.
+pub fn parse_keywords(text: &str) -> HashMap<String, String> {
+    split_arguments(text)
+        .iter()
+        .filter_map(|arg| match split_key_value(arg) {
+            (key, Some(value)) => Some((key, value)),
+            _ => None,
+        })
+        .collect()
+}
+
+type PendingReplies = Arc<std::sync::Mutex<VecDeque<oneshot::Sender<Result<Reply, ControlError>>>>>;
+
+/// Async client for a single Tor control-port connection
+///
+/// A background task owns the read half of the socket. Synchronous replies are
+/// handed back to the caller that issued the command (Tor answers in order),
+/// and 650 events are broadcast to every subscriber.
+pub struct ControlClient {
+    writer: Mutex<OwnedWriteHalf>,
+    pending: PendingReplies,
+    events: broadcast::Sender<ControlEvent>,
+    reader_task: tokio::task::JoinHandle<()>,
+    timeout: Duration,
+}
+
+impl ControlClient {
+    /// Connect to a control port on localhost
+    pub async fn connect_port(port: u16) -> Result<Self, ControlError> {
+        Self::connect(&format!("127.0.0.1:{}", port)).await
+    }
+
+    /// Connect to a control port at the given address ("host:port")
+    pub async fn connect(addr: &str) -> Result<Self, ControlError> {
+        debug!("🔌 Connecting to Tor control port {}", addr);
+        let stream = tokio::time::timeout(DEFAULT_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .map_err(|_| ControlError::Timeout)??;
+        Ok(Self::from_stream(stream))
+    }
+
+    fn from_stream(stream: TcpStream) -> Self {
+        let (read_half, write_half) = stream.into_split();
+        let pending: PendingReplies = Arc::new(std::sync::Mutex::new(VecDeque::new()));
+        let (events, _) = broadcast::channel(256);
+
+        let reader_task = tokio::spawn(Self::read_loop(
+            BufReader::new(read_half),
+            pending.clone(),
+            events.clone(),
+        ));
+
+        Self {
+            writer: Mutex::new(write_half),
+            pending,
+            events,
+            reader_task,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Override how long to wait for each command reply
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn read_loop<R: AsyncBufRead + Unpin>(
+        mut reader: R,
+        pending: PendingReplies,
+        events: broadcast::Sender<ControlEvent>,
+    ) {
+        loop {
+            match read_reply(&mut reader).await {
+                Ok(reply) if reply.is_async() => {
+                    if let Some(event) = ControlEvent::from_reply(&reply) {
+                        // No subscribers is fine - events are best effort
+                        let _ = events.send(event);
+                    }
+                }
+                Ok(reply) => {
+                    let waiter = pending.lock().unwrap().pop_front();
+                    match waiter {
+                        Some(tx) => {
+                            let _ = tx.send(Ok(reply));
+                        }
+                        None => warn!("⚠️ Unsolicited control reply: {:?}", reply),
+                    }
+                }
+                Err(e) => {
+                    debug!("🔌 Control reader stopped: {}", e);
+                    // Fail everything still waiting so callers don't hang
+                    let waiters: Vec<_> = pending.lock().unwrap().drain(..).collect();
+                    for tx in waiters {
+                        let _ = tx.send(Err(ControlError::Closed));
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Send a raw command line and wait for its reply
+    ///
+    /// Non-2xx replies are returned as `ControlError::Reply`.
+    pub async fn send_command(&self, command: &str) -> Result<Reply, ControlError> {
+        let rx = {
+            let mut writer = self.writer.lock().await;
+            let (tx, rx) = oneshot::channel();
+            self.pending.lock().unwrap().push_back(tx);
+            if let Err(e) = writer.write_all(format!("{}\r\n", command).as_bytes()).await {
+                self.pending.lock().unwrap().pop_back();
+                return Err(e.into());
+            }
+            rx
+        };
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(reply)) => reply?.into_result(),
+            Ok(Err(_)) => Err(ControlError::Closed),
+            Err(_) => Err(ControlError::Timeout),
+        }
+    }
+
+    /// Authenticate with a control password (HashedControlPassword)
+    pub async fn authenticate(&self, password: &str) -> Result<(), ControlError> {
+        match self.send_command(&format!("AUTHENTICATE {}", quote_string(password))).await {
+            Ok(_) => Ok(()),
+            Err(ControlError::Reply { code, message }) => {
+                Err(ControlError::Authentication(format!("{} {}", code, message)))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// GETINFO for one or more keys, returning key -> value
+    pub async fn get_info(&self, keys: &[&str]) -> Result<HashMap<String, String>, ControlError> {
+        let reply = self.send_command(&format!("GETINFO {}", keys.join(" "))).await?;
+        let mut values = HashMap::new();
+
+        for line in &reply.lines {
+            if line.text == "OK" && line.data.is_none() {
+                continue;
+            }
+            let (key, inline_value) = split_key_value(&line.text);
+            let value = match &line.data {
+                Some(data) => data.clone(),
+                None => inline_value.unwrap_or_default(),
+            };
+            values.insert(key, value);
+        }
+
+        Ok(values)
+    }
+
+    /// GETINFO for a single key
+    pub async fn get_info_value(&self, key: &str) -> Result<String, ControlError> {
+        let mut values = self.get_info(&[key]).await?;
+        values
+            .remove(key)
+            .ok_or_else(|| ControlError::Protocol(format!("GETINFO reply missing key {}", key)))
+    }
+
+    /// GETCONF for a single option
+    ///
+    /// Returns every configured value (options like SocksPort can repeat);
+    /// an empty vec means the option is at its default.
+    pub async fn get_conf(&self, key: &str) -> Result<Vec<String>, ControlError> {
+        let reply = self.send_command(&format!("GETCONF {}", key)).await?;
+        Ok(reply
+            .lines
+            .iter()
+            .filter_map(|line| split_key_value(&line.text).1)
+            .collect())
+    }
+
+    /// SETCONF one or more options; `None` resets an option to its default
+    pub async fn set_conf(&self, options: &[(&str, Option<&str>)]) -> Result<(), ControlError> {
+        let args: Vec<String> = options
+            .iter()
+            .map(|(key, value)| match value {
+                Some(value) => format!("{}={}", key, quote_string(value)),
+                None => key.to_string(),
+            })
+            .collect();
+        self.send_command(&format!("SETCONF {}", args.join(" "))).await?;
+        Ok(())
+    }
+
+    /// Send a SIGNAL command
+    pub async fn signal(&self, signal: Signal) -> Result<(), ControlError> {
+        self.send_command(&format!("SIGNAL {}", signal.as_str())).await?;
+        Ok(())
+    }
+
+    /// Replace the set of events this connection receives (empty clears them)
+    pub async fn set_events(&self, events: &[&str]) -> Result<(), ControlError> {
+        let command = if events.is_empty() {
+            "SETEVENTS".to_string()
+        } else {
+            format!("SETEVENTS {}", events.join(" "))
+        };
+        self.send_command(&command).await?;
+        Ok(())
+    }
+
+    /// Subscribe to async events received on this connection
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ControlEvent> {
+        self.events.subscribe()
+    }
+
+    /// Whether the reader task is still attached to a live connection
+    pub fn is_connected(&self) -> bool {
+        !self.reader_task.is_finished()
+    }
+
+    /// Send QUIT and close the connection
+    pub async fn quit(self) {
+        let _ = self.send_command("QUIT").await;
+    }
+}
+
+impl Drop for ControlClient {
+    fn drop(&mut self) {
+        self.reader_task.abort();
+    }
+}
+
+/// Connect and authenticate to the control port used by an eltord mode
+pub async fn connect_for_mode(
+    mode: &EltorMode,
+    path_config: &PathConfig,
+) -> Result<ControlClient, ControlError> {
+    let port = mode.get_control_port(path_config).await;
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| ControlError::Protocol(format!("Invalid control port for {}: {}", mode, port)))?;
+
+    let client = ControlClient::connect_port(port).await?;
+    client
+        .authenticate(&crate::eltor::get_tor_control_password(mode))
+        .await?;
+    info!("🔐 Authenticated to Tor control port {} ({} mode)", port, mode);
+    Ok(client)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    /// Spawn a mock control port that answers each received command line with
+    /// the scripted response, in order. Returns the port to connect to.
+    pub(crate) async fn mock_control_port(script: Vec<(&'static str, &'static str)>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read_half, mut write_half) = stream.into_split();
+            let mut reader = BufReader::new(read_half);
+
+            for (expected, response) in script {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await.unwrap() == 0 {
+                    return;
+                }
+                assert_eq!(line.trim_end(), expected);
+                write_half.write_all(response.as_bytes()).await.unwrap();
+            }
+
+            // Hold the connection open until the client goes away
+            let mut sink = Vec::new();
+            let _ = reader.read_to_end(&mut sink).await;
+        });
+
+        port
+    }
+
+    #[tokio::test]
+    async fn test_read_reply_multiline_and_data() {
+        let raw = "250-version=0.4.8.10\r\n250+config-text=\r\nSocksPort 9050\r\n..hidden\r\n.\r\n250 OK\r\n";
+        let mut reader = BufReader::new(raw.as_bytes());
+
+        let reply = read_reply(&mut reader).await.unwrap();
+        assert_eq!(reply.code, 250);
+        assert_eq!(reply.lines.len(), 3);
+        assert_eq!(reply.lines[0].text, "version=0.4.8.10");
+        assert_eq!(reply.lines[1].data.as_deref(), Some("SocksPort 9050\n.hidden"));
+        assert_eq!(reply.message(), "OK");
+    }
+
+    #[tokio::test]
+    async fn test_read_reply_rejects_garbage() {
+        let mut reader = BufReader::new("hello\r\n".as_bytes());
+        assert!(matches!(read_reply(&mut reader).await, Err(ControlError::Protocol(_))));
+
+        let mut reader = BufReader::new("250-partial\r\n".as_bytes());
+        assert!(matches!(read_reply(&mut reader).await, Err(ControlError::Closed)));
+    }
+
+    #[test]
+    fn test_parse_keywords() {
+        let args = split_arguments("NOTICE BOOTSTRAP PROGRESS=45 SUMMARY=\"Asking for \\\"relay\\\" descriptors\"");
+        assert_eq!(args.len(), 4);
+
+        let keywords = parse_keywords("NOTICE BOOTSTRAP PROGRESS=45 TAG=requesting_descriptors SUMMARY=\"Asking for \\\"relay\\\" descriptors\"");
+        assert_eq!(keywords.get("PROGRESS").map(String::as_str), Some("45"));
+        assert_eq!(keywords.get("TAG").map(String::as_str), Some("requesting_descriptors"));
+        assert_eq!(
+            keywords.get("SUMMARY").map(String::as_str),
+            Some("Asking for \"relay\" descriptors")
+        );
+        assert!(!keywords.contains_key("NOTICE"));
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_and_get_info() {
+        let port = mock_control_port(vec![
+            ("AUTHENTICATE \"pass\\\"word\"", "250 OK\r\n"),
+            (
+                "GETINFO version status/bootstrap-phase",
+                "250-version=0.4.8.10\r\n250-status/bootstrap-phase=NOTICE BOOTSTRAP PROGRESS=100 TAG=done SUMMARY=\"Done\"\r\n250 OK\r\n",
+            ),
+        ])
+        .await;
+
+        let client = ControlClient::connect_port(port).await.unwrap();
+        client.authenticate("pass\"word").await.unwrap();
+
+        let info = client.get_info(&["version", "status/bootstrap-phase"]).await.unwrap();
+        assert_eq!(info.get("version").map(String::as_str), Some("0.4.8.10"));
+        assert!(info["status/bootstrap-phase"].contains("PROGRESS=100"));
+    }
+
+    #[tokio::test]
+    async fn test_authentication_failure_is_typed() {
+        let port = mock_control_port(vec![(
+            "AUTHENTICATE \"wrong\"",
+            "515 Authentication failed: Password did not match\r\n",
+        )])
+        .await;
+
+        let client = ControlClient::connect_port(port).await.unwrap();
+        match client.authenticate("wrong").await {
+            Err(ControlError::Authentication(msg)) => assert!(msg.starts_with("515")),
+            other => panic!("expected authentication error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conf_and_signal_commands() {
+        let port = mock_control_port(vec![
+            ("GETCONF SocksPort", "250-SocksPort=127.0.0.1:18058\r\n250 SocksPort=18057\r\n"),
+            ("SETCONF PaymentRateMsats=\"2000\" ExitRelay", "250 OK\r\n"),
+            ("SIGNAL NEWNYM", "250 OK\r\n"),
+            ("SETCONF Bogus=\"1\"", "552 Unrecognized option: Unknown option 'Bogus'\r\n"),
+        ])
+        .await;
+
+        let client = ControlClient::connect_port(port).await.unwrap();
+        assert_eq!(
+            client.get_conf("SocksPort").await.unwrap(),
+            vec!["127.0.0.1:18058", "18057"]
+        );
+        client
+            .set_conf(&[("PaymentRateMsats", Some("2000")), ("ExitRelay", None)])
+            .await
+            .unwrap();
+        client.signal(Signal::Newnym).await.unwrap();
+
+        match client.set_conf(&[("Bogus", Some("1"))]).await {
+            Err(ControlError::Reply { code, .. }) => assert_eq!(code, 552),
+            other => panic!("expected 552 reply, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_events_interleaved_with_replies() {
+        let port = mock_control_port(vec![
+            ("SETEVENTS CIRC STATUS_CLIENT", "250 OK\r\n"),
+            (
+                "GETINFO version",
+                "650 CIRC 7 BUILT $AAAA~relay1,$BBBB~relay2 PURPOSE=GENERAL\r\n650-STATUS_CLIENT NOTICE BOOTSTRAP PROGRESS=50\r\n650 OK\r\n250-version=0.4.8.10\r\n250 OK\r\n",
+            ),
+        ])
+        .await;
+
+        let client = ControlClient::connect_port(port).await.unwrap();
+        let mut events = client.subscribe_events();
+        client.set_events(&["CIRC", "STATUS_CLIENT"]).await.unwrap();
+
+        // The events arrive before the GETINFO reply and must not be mistaken for it
+        assert_eq!(client.get_info_value("version").await.unwrap(), "0.4.8.10");
+
+        let circ = events.recv().await.unwrap();
+        assert_eq!(circ.kind, "CIRC");
+        assert!(circ.body.starts_with("7 BUILT"));
+
+        let status = events.recv().await.unwrap();
+        assert_eq!(status.kind, "STATUS_CLIENT");
+        assert_eq!(status.body, "NOTICE BOOTSTRAP PROGRESS=50");
+    }
+
+    #[tokio::test]
+    async fn test_closed_connection_fails_pending_command() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            drop(stream);
+        });
+
+        let client = ControlClient::connect_port(port).await.unwrap();
+        let result = client.send_command("GETINFO version").await;
+        assert!(matches!(result, Err(ControlError::Closed) | Err(ControlError::Io(_))));
+    }
+}
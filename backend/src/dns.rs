@@ -0,0 +1,671 @@
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::routing::RouteAction;
+use crate::socks::{self, ReplyCode, SocksCommand, SocksRouterConfig, TargetAddress, Upstream};
+
+/// Tor's RESOLVE carries no TTL, so answers are cached and handed out for at most this long
+const MAX_TTL: Duration = Duration::from_secs(60);
+/// How long a name that doesn't exist stays cached
+const NEGATIVE_TTL: Duration = Duration::from_secs(10);
+const MAX_CACHE_ENTRIES: usize = 4096;
+/// How long one lookup through Tor may take
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long an idle DNS-over-TCP client is kept, and how long a message body may take
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Synthetic .onion addresses come from 127.192.0.0/10, like Tor's VirtualAddrNetworkIPv4
+const SYNTHETIC_BASE: u32 = 0x7FC0_0000;
+const SYNTHETIC_SIZE: u32 = 1 << 22;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const RCODE_NOERROR: u8 = 0;
+const RCODE_FORMERR: u8 = 1;
+const RCODE_SERVFAIL: u8 = 2;
+const RCODE_NXDOMAIN: u8 = 3;
+const RCODE_NOTIMP: u8 = 4;
+
+/// How the resolver answers .onion names, which only Tor can reach
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnionDnsMode {
+    /// NXDOMAIN, so the name never reaches another resolver
+    #[default]
+    Block,
+    /// A fake address from 127.192.0.0/10 that the router maps back to the name
+    Synthetic,
+}
+
+impl OnionDnsMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "block" => Some(OnionDnsMode::Block),
+            "synthetic" => Some(OnionDnsMode::Synthetic),
+            _ => None,
+        }
+    }
+}
+
+/// A single-question DNS query
+#[derive(Debug, Clone, PartialEq)]
+struct DnsQuery {
+    id: u16,
+    /// Recursion desired, echoed back
+    recursion_desired: bool,
+    /// Lowercased, without the trailing dot
+    name: String,
+    qtype: u16,
+    qclass: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Ptr(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Record {
+    ttl: u32,
+    data: RecordData,
+}
+
+/// What a lookup through Tor came back with
+#[derive(Debug, Clone, PartialEq)]
+enum Resolved {
+    Address(IpAddr),
+    Name(String),
+    /// DNS rcode to answer with
+    Failed(u8),
+}
+
+/// Parse a query; Err carries the id and rcode to answer with, None means drop it
+fn parse_query(packet: &[u8]) -> Result<DnsQuery, Option<(u16, u8)>> {
+    if packet.len() < 12 {
+        return Err(None);
+    }
+    let id = u16::from_be_bytes([packet[0], packet[1]]);
+    let flags = u16::from_be_bytes([packet[2], packet[3]]);
+    if flags & 0x8000 != 0 {
+        // A response, not a query
+        return Err(None);
+    }
+    if (flags >> 11) & 0x0F != 0 {
+        return Err(Some((id, RCODE_NOTIMP)));
+    }
+    if u16::from_be_bytes([packet[4], packet[5]]) != 1 {
+        return Err(Some((id, RCODE_FORMERR)));
+    }
+
+    let (name, end) = decode_name(packet, 12).ok_or(Some((id, RCODE_FORMERR)))?;
+    let fixed = packet.get(end..end + 4).ok_or(Some((id, RCODE_FORMERR)))?;
+    Ok(DnsQuery {
+        id,
+        recursion_desired: flags & 0x0100 != 0,
+        name,
+        qtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+        qclass: u16::from_be_bytes([fixed[2], fixed[3]]),
+    })
+}
+
+/// Read an uncompressed name starting at `start`; returns it and the offset after it
+fn decode_name(packet: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut position = start;
+    loop {
+        let len = *packet.get(position)? as usize;
+        position += 1;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers and extended label types have no place in a question
+        if len > 63 {
+            return None;
+        }
+        let label = std::str::from_utf8(packet.get(position..position + len)?).ok()?;
+        labels.push(label.to_ascii_lowercase());
+        position += len;
+    }
+    let name = labels.join(".");
+    (name.len() <= 253).then_some((name, position))
+}
+
+fn encode_name(name: &str, out: &mut Vec<u8>) {
+    for label in name.trim_end_matches('.').split('.').filter(|label| !label.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        out.push(label.len() as u8);
+        out.extend_from_slice(label);
+    }
+    out.push(0);
+}
+
+/// Header for a response; the question section is echoed when there is one
+fn encode_header(id: u16, recursion_desired: bool, rcode: u8, questions: u16, answers: u16) -> Vec<u8> {
+    // QR, recursion available, and RD copied from the query
+    let flags = 0x8080 | if recursion_desired { 0x0100 } else { 0 } | rcode as u16;
+    let mut out = Vec::with_capacity(512);
+    out.extend_from_slice(&id.to_be_bytes());
+    out.extend_from_slice(&flags.to_be_bytes());
+    out.extend_from_slice(&questions.to_be_bytes());
+    out.extend_from_slice(&answers.to_be_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0]);
+    out
+}
+
+fn encode_response(query: &DnsQuery, rcode: u8, records: &[Record]) -> Vec<u8> {
+    let mut out = encode_header(query.id, query.recursion_desired, rcode, 1, records.len() as u16);
+    encode_name(&query.name, &mut out);
+    out.extend_from_slice(&query.qtype.to_be_bytes());
+    out.extend_from_slice(&query.qclass.to_be_bytes());
+
+    for record in records {
+        // Every answer is for the question name, at offset 12
+        out.extend_from_slice(&[0xC0, 0x0C]);
+        let (rtype, rdata) = match &record.data {
+            RecordData::A(ip) => (TYPE_A, ip.octets().to_vec()),
+            RecordData::Aaaa(ip) => (TYPE_AAAA, ip.octets().to_vec()),
+            RecordData::Ptr(name) => {
+                let mut rdata = Vec::new();
+                encode_name(name, &mut rdata);
+                (TYPE_PTR, rdata)
+            }
+        };
+        out.extend_from_slice(&rtype.to_be_bytes());
+        out.extend_from_slice(&CLASS_IN.to_be_bytes());
+        out.extend_from_slice(&record.ttl.to_be_bytes());
+        out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        out.extend_from_slice(&rdata);
+    }
+    out
+}
+
+/// Address named by an in-addr.arpa or ip6.arpa PTR query
+fn parse_ptr_name(name: &str) -> Option<IpAddr> {
+    if let Some(reversed) = name.strip_suffix(".in-addr.arpa") {
+        let mut octets: Vec<u8> = reversed.split('.').map(|octet| octet.parse().ok()).collect::<Option<_>>()?;
+        if octets.len() != 4 {
+            return None;
+        }
+        octets.reverse();
+        return Some(IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])));
+    }
+    let reversed = name.strip_suffix(".ip6.arpa")?;
+    let nibbles: Vec<u8> = reversed
+        .split('.')
+        .map(|nibble| u8::from_str_radix(nibble, 16).ok().filter(|_| nibble.len() == 1))
+        .collect::<Option<_>>()?;
+    if nibbles.len() != 32 {
+        return None;
+    }
+    let mut octets = [0u8; 16];
+    for (index, pair) in nibbles.rchunks(2).enumerate() {
+        // Reversed order: the low nibble of each byte comes first
+        octets[index] = (pair[1] << 4) | pair[0];
+    }
+    Some(IpAddr::V6(Ipv6Addr::from(octets)))
+}
+
+struct CacheEntry {
+    resolved: Resolved,
+    expires: Instant,
+}
+
+fn cache() -> &'static Mutex<HashMap<String, CacheEntry>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, CacheEntry>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Cached answer and its remaining TTL in seconds
+fn cached(key: &str) -> Option<(Resolved, u32)> {
+    let cache = cache().lock().unwrap();
+    let entry = cache.get(key)?;
+    let remaining = entry.expires.checked_duration_since(Instant::now())?;
+    Some((entry.resolved.clone(), remaining.as_secs().max(1) as u32))
+}
+
+fn store(key: &str, resolved: &Resolved) {
+    let ttl = match resolved {
+        Resolved::Address(_) | Resolved::Name(_) => MAX_TTL,
+        Resolved::Failed(RCODE_NXDOMAIN) => NEGATIVE_TTL,
+        // Tor trouble isn't the name's fault; ask again next time
+        Resolved::Failed(_) => return,
+    };
+    let mut cache = cache().lock().unwrap();
+    if cache.len() >= MAX_CACHE_ENTRIES {
+        let now = Instant::now();
+        cache.retain(|_, entry| entry.expires > now);
+        if cache.len() >= MAX_CACHE_ENTRIES {
+            cache.clear();
+        }
+    }
+    cache.insert(
+        key.to_string(),
+        CacheEntry {
+            resolved: resolved.clone(),
+            expires: Instant::now() + ttl,
+        },
+    );
+}
+
+#[derive(Default)]
+struct SyntheticMap {
+    by_name: HashMap<String, Ipv4Addr>,
+    by_address: HashMap<Ipv4Addr, String>,
+    next: u32,
+}
+
+fn synthetic() -> &'static Mutex<SyntheticMap> {
+    static SYNTHETIC: OnceLock<Mutex<SyntheticMap>> = OnceLock::new();
+    SYNTHETIC.get_or_init(|| Mutex::new(SyntheticMap::default()))
+}
+
+/// Stable fake address for a .onion name; the oldest mapping is reused once the range is used up
+fn synthetic_address(name: &str) -> Ipv4Addr {
+    let mut map = synthetic().lock().unwrap();
+    if let Some(address) = map.by_name.get(name) {
+        return *address;
+    }
+    // Skip 127.192.0.0 so no address ends in .0.0
+    let address = Ipv4Addr::from(SYNTHETIC_BASE + 1 + map.next % (SYNTHETIC_SIZE - 1));
+    map.next += 1;
+    if let Some(previous) = map.by_address.insert(address, name.to_string()) {
+        map.by_name.remove(&previous);
+    }
+    map.by_name.insert(name.to_string(), address);
+    address
+}
+
+fn synthetic_name(address: Ipv4Addr) -> Option<String> {
+    synthetic().lock().unwrap().by_address.get(&address).cloned()
+}
+
+/// Swap a synthetic .onion address handed out by the resolver back to its name
+///
+/// Lets apps that resolve before connecting still reach onion services
+/// through the router.
+pub fn unmap_synthetic(target: TargetAddress) -> TargetAddress {
+    if let TargetAddress::IPv4(ip, port) = target {
+        if let Some(name) = synthetic_name(ip) {
+            debug!("🧅 Mapping synthetic address {} back to {}", ip, name);
+            return TargetAddress::Domain(name, port);
+        }
+    }
+    target
+}
+
+fn is_onion(name: &str) -> bool {
+    name == "onion" || name.ends_with(".onion")
+}
+
+/// Ask Tor to resolve a name (RESOLVE) or an address (RESOLVE_PTR)
+async fn resolve_via_tor(command: SocksCommand, target: &TargetAddress, config: &SocksRouterConfig) -> Resolved {
+    // Client first, relay as fallback, the same as eltord traffic
+    let Upstream::Socks(candidates) = socks::resolve_upstream(&RouteAction::Eltord, config) else {
+        return Resolved::Failed(RCODE_SERVFAIL);
+    };
+    let lookup = async {
+        let request = socks::encode_socks5_request(command, target).map_err(|e| e.to_string())?;
+        let (_, reply, proxy_addr) = socks::open_first_upstream(&request, &candidates, None)
+            .await
+            .map_err(|(_, e)| e)?;
+        debug!("🔎 DNS {:?} for {} via {}: status {}", command, target, proxy_addr, reply[1]);
+        Ok::<_, String>(reply)
+    };
+    let reply = match tokio::time::timeout(RESOLVE_TIMEOUT, lookup).await {
+        Ok(Ok(reply)) => reply,
+        Ok(Err(e)) => {
+            warn!("⚠️ DNS lookup for {} failed: {}", target, e);
+            return Resolved::Failed(RCODE_SERVFAIL);
+        }
+        Err(_) => {
+            warn!("⚠️ DNS lookup for {} timed out", target);
+            return Resolved::Failed(RCODE_SERVFAIL);
+        }
+    };
+
+    // Tor reports unknown names as host unreachable (or a general failure)
+    if reply[1] != ReplyCode::Success as u8 {
+        return Resolved::Failed(RCODE_NXDOMAIN);
+    }
+    match socks::parse_target_address(reply.get(3..).unwrap_or_default()) {
+        Ok(TargetAddress::IPv4(ip, _)) => Resolved::Address(IpAddr::V4(ip)),
+        Ok(TargetAddress::IPv6(ip, _)) => Resolved::Address(IpAddr::V6(ip)),
+        Ok(TargetAddress::Domain(name, _)) => Resolved::Name(name),
+        Err(e) => {
+            warn!("⚠️ Unreadable DNS answer from Tor for {}: {}", target, e);
+            Resolved::Failed(RCODE_SERVFAIL)
+        }
+    }
+}
+
+/// Cached lookup, going to Tor on a miss
+async fn lookup(key: &str, command: SocksCommand, target: TargetAddress, config: &SocksRouterConfig) -> (Resolved, u32) {
+    if let Some(hit) = cached(key) {
+        return hit;
+    }
+    let resolved = resolve_via_tor(command, &target, config).await;
+    store(key, &resolved);
+    (resolved, MAX_TTL.as_secs() as u32)
+}
+
+/// Records answering an address lookup, empty when the family doesn't match (NODATA)
+fn address_records(qtype: u16, address: IpAddr, ttl: u32) -> Vec<Record> {
+    let data = match (qtype, address) {
+        (TYPE_A, IpAddr::V4(ip)) => RecordData::A(ip),
+        (TYPE_AAAA, IpAddr::V6(ip)) => RecordData::Aaaa(ip),
+        _ => return Vec::new(),
+    };
+    vec![Record { ttl, data }]
+}
+
+/// Work out the rcode and answers for a query
+async fn answer(query: &DnsQuery, config: &SocksRouterConfig) -> (u8, Vec<Record>) {
+    if query.qclass != CLASS_IN {
+        return (RCODE_NOTIMP, Vec::new());
+    }
+    let name = query.name.as_str();
+    match query.qtype {
+        TYPE_A | TYPE_AAAA if name == "localhost" || name.ends_with(".localhost") => {
+            let address = if query.qtype == TYPE_A {
+                IpAddr::V4(Ipv4Addr::LOCALHOST)
+            } else {
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            };
+            (RCODE_NOERROR, address_records(query.qtype, address, MAX_TTL.as_secs() as u32))
+        }
+        TYPE_A | TYPE_AAAA if is_onion(name) => match config.dns_onion {
+            OnionDnsMode::Block => (RCODE_NXDOMAIN, Vec::new()),
+            OnionDnsMode::Synthetic => {
+                let address = IpAddr::V4(synthetic_address(name));
+                (RCODE_NOERROR, address_records(query.qtype, address, MAX_TTL.as_secs() as u32))
+            }
+        },
+        TYPE_A | TYPE_AAAA => {
+            let target = TargetAddress::Domain(name.to_string(), 0);
+            match lookup(name, SocksCommand::Resolve, target, config).await {
+                (Resolved::Address(address), ttl) => (RCODE_NOERROR, address_records(query.qtype, address, ttl)),
+                (Resolved::Failed(rcode), _) => (rcode, Vec::new()),
+                (Resolved::Name(_), _) => (RCODE_SERVFAIL, Vec::new()),
+            }
+        }
+        TYPE_PTR => {
+            let Some(address) = parse_ptr_name(name) else {
+                return (RCODE_NXDOMAIN, Vec::new());
+            };
+            let synthetic_onion = match address {
+                IpAddr::V4(ip) => synthetic_name(ip),
+                IpAddr::V6(_) => None,
+            };
+            if let Some(onion) = synthetic_onion {
+                let data = RecordData::Ptr(onion);
+                return (RCODE_NOERROR, vec![Record { ttl: MAX_TTL.as_secs() as u32, data }]);
+            }
+            let target = match address {
+                IpAddr::V4(ip) => TargetAddress::IPv4(ip, 0),
+                IpAddr::V6(ip) => TargetAddress::IPv6(ip, 0),
+            };
+            match lookup(name, SocksCommand::ResolvePtr, target, config).await {
+                (Resolved::Name(host), ttl) => (RCODE_NOERROR, vec![Record { ttl, data: RecordData::Ptr(host) }]),
+                (Resolved::Failed(rcode), _) => (rcode, Vec::new()),
+                (Resolved::Address(_), _) => (RCODE_SERVFAIL, Vec::new()),
+            }
+        }
+        // Other types (MX, TXT, HTTPS...) can't be asked through Tor: no data
+        _ => (RCODE_NOERROR, Vec::new()),
+    }
+}
+
+/// Answer one DNS message; None means send nothing back
+async fn handle_packet(packet: &[u8], config: &SocksRouterConfig) -> Option<Vec<u8>> {
+    match parse_query(packet) {
+        Ok(query) => {
+            let (rcode, records) = answer(&query, config).await;
+            debug!("🔎 DNS {} (type {}) -> rcode {}, {} answer(s)", query.name, query.qtype, rcode, records.len());
+            Some(encode_response(&query, rcode, &records))
+        }
+        Err(Some((id, rcode))) => Some(encode_header(id, false, rcode, 0, 0)),
+        Err(None) => None,
+    }
+}
+
+/// DNS-over-TCP: two-byte length prefix, several queries per connection
+async fn handle_tcp_connection(mut stream: TcpStream, config: SocksRouterConfig) -> std::io::Result<()> {
+    loop {
+        let len = match tokio::time::timeout(TCP_IDLE_TIMEOUT, stream.read_u16()).await {
+            Ok(Ok(len)) => len as usize,
+            // EOF or idle: the client is done
+            Ok(Err(_)) | Err(_) => return Ok(()),
+        };
+        let mut packet = vec![0u8; len];
+        tokio::time::timeout(TCP_IDLE_TIMEOUT, stream.read_exact(&mut packet))
+            .await
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "DNS message body stalled"))??;
+        if let Some(response) = handle_packet(&packet, &config).await {
+            let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+            framed.extend(response);
+            stream.write_all(&framed).await?;
+        }
+    }
+}
+
+/// Local DNS listener (UDP and TCP on the same port) that resolves through El Tor
+pub struct DnsServer {
+    config: SocksRouterConfig,
+    udp: Option<Arc<UdpSocket>>,
+    tcp: Option<TcpListener>,
+}
+
+impl DnsServer {
+    pub fn new(config: SocksRouterConfig) -> Self {
+        Self {
+            config,
+            udp: None,
+            tcp: None,
+        }
+    }
+
+    /// Bind the DNS port for UDP and TCP
+    pub async fn start(&mut self) -> Result<(), String> {
+        let port = self.config.dns_port.ok_or("DNS port not configured")?;
+        let ip = self.config.dns_addr.unwrap_or(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+        let bind_addr = SocketAddr::new(ip, port);
+
+        let udp = UdpSocket::bind(bind_addr)
+            .await
+            .map_err(|e| format!("Failed to bind DNS (UDP) to {}: {}", bind_addr, e))?;
+        // Port 0 picks a free UDP port; TCP follows it
+        let bind_addr = udp.local_addr().map_err(|e| e.to_string())?;
+        let tcp = TcpListener::bind(bind_addr)
+            .await
+            .map_err(|e| format!("Failed to bind DNS (TCP) to {}: {}", bind_addr, e))?;
+        info!("🔎 DNS resolver started on {} (.onion: {:?})", bind_addr, self.config.dns_onion);
+        self.udp = Some(Arc::new(udp));
+        self.tcp = Some(tcp);
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.udp.as_ref()?.local_addr().ok()
+    }
+
+    /// Answer queries until `shutdown` fires, then drain
+    pub async fn run(&mut self, mut shutdown: watch::Receiver<bool>) -> Result<(), String> {
+        let udp = self.udp.take().ok_or("DNS resolver not started")?;
+        let tcp = self.tcp.take().ok_or("DNS resolver not started")?;
+        let mut queries = JoinSet::new();
+        let mut buffer = vec![0u8; 4096];
+
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                Some(_) = queries.join_next(), if !queries.is_empty() => {}
+                received = udp.recv_from(&mut buffer) => match received {
+                    Ok((len, client_addr)) => {
+                        let packet = buffer[..len].to_vec();
+                        let udp = udp.clone();
+                        let config = self.config.clone();
+                        queries.spawn(async move {
+                            if let Some(response) = handle_packet(&packet, &config).await {
+                                if let Err(e) = udp.send_to(&response, client_addr).await {
+                                    debug!("🔎 Failed to answer DNS query from {}: {}", client_addr, e);
+                                }
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        // ICMP errors from earlier replies surface here on some platforms
+                        debug!("🔎 DNS receive error: {}", e);
+                    }
+                },
+                accepted = tcp.accept() => match accepted {
+                    Ok((stream, client_addr)) => {
+                        let config = self.config.clone();
+                        queries.spawn(async move {
+                            if let Err(e) = handle_tcp_connection(stream, config).await {
+                                debug!("🔎 DNS-over-TCP error from {}: {}", client_addr, e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("❌ Failed to accept DNS connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                    }
+                },
+            }
+        }
+
+        drop(tcp);
+        info!("🔎 DNS resolver stopped accepting queries");
+        socks::drain_connections(queries, "DNS").await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query_packet(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+        let mut packet = encode_header(id, true, 0, 1, 0);
+        // Clear QR/RA: this is a query
+        packet[2] = 0x01;
+        packet[3] = 0x00;
+        encode_name(name, &mut packet);
+        packet.extend_from_slice(&qtype.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = parse_query(&query_packet(0x1234, "Example.COM.", TYPE_AAAA)).unwrap();
+        assert_eq!(query.id, 0x1234);
+        assert!(query.recursion_desired);
+        assert_eq!(query.name, "example.com");
+        assert_eq!((query.qtype, query.qclass), (TYPE_AAAA, CLASS_IN));
+
+        // Too short to carry an id: dropped
+        assert_eq!(parse_query(&[0x12]), Err(None));
+        // Responses are ignored
+        let mut response = query_packet(1, "example.com", TYPE_A);
+        response[2] |= 0x80;
+        assert_eq!(parse_query(&response), Err(None));
+        // Truncated question
+        let packet = query_packet(7, "example.com", TYPE_A);
+        assert_eq!(parse_query(&packet[..packet.len() - 2]), Err(Some((7, RCODE_FORMERR))));
+        // Compression pointer in the question
+        let mut packet = encode_header(8, true, 0, 1, 0);
+        packet[2] = 0x01;
+        packet.extend_from_slice(&[0xC0, 0x0C, 0, 1, 0, 1]);
+        assert_eq!(parse_query(&packet), Err(Some((8, RCODE_FORMERR))));
+    }
+
+    #[test]
+    fn test_encode_response() {
+        let query = parse_query(&query_packet(42, "example.com", TYPE_A)).unwrap();
+        let records = [Record {
+            ttl: 60,
+            data: RecordData::A(Ipv4Addr::new(93, 184, 216, 34)),
+        }];
+        let response = encode_response(&query, RCODE_NOERROR, &records);
+
+        assert_eq!(&response[..4], &[0x00, 42, 0x81, 0x80]);
+        assert_eq!(&response[4..12], &[0, 1, 0, 1, 0, 0, 0, 0]);
+        let (name, end) = decode_name(&response, 12).unwrap();
+        assert_eq!(name, "example.com");
+        let answer = &response[end + 4..];
+        assert_eq!(&answer[..2], &[0xC0, 0x0C]);
+        assert_eq!(&answer[2..6], &[0, TYPE_A as u8, 0, 1]);
+        assert_eq!(&answer[6..10], &60u32.to_be_bytes());
+        assert_eq!(&answer[10..], &[0, 4, 93, 184, 216, 34]);
+    }
+
+    #[test]
+    fn test_parse_ptr_name() {
+        assert_eq!(parse_ptr_name("4.3.2.1.in-addr.arpa"), Some("1.2.3.4".parse().unwrap()));
+        assert_eq!(
+            parse_ptr_name("b.a.9.8.7.6.5.0.4.0.0.0.3.0.0.0.2.0.0.0.1.0.0.0.0.0.0.0.1.2.3.4.ip6.arpa"),
+            Some("4321:0:1:2:3:4:567:89ab".parse().unwrap())
+        );
+        assert_eq!(parse_ptr_name("3.2.1.in-addr.arpa"), None);
+        assert_eq!(parse_ptr_name("256.3.2.1.in-addr.arpa"), None);
+        assert_eq!(parse_ptr_name("example.com"), None);
+    }
+
+    #[tokio::test]
+    async fn test_onion_answers() {
+        let onion = "dnstestexampleonionaddressxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx.onion";
+        let query = parse_query(&query_packet(1, onion, TYPE_A)).unwrap();
+
+        let blocked = SocksRouterConfig::default();
+        assert_eq!(answer(&query, &blocked).await, (RCODE_NXDOMAIN, Vec::new()));
+
+        let config = SocksRouterConfig {
+            dns_onion: OnionDnsMode::Synthetic,
+            ..Default::default()
+        };
+        let (rcode, records) = answer(&query, &config).await;
+        assert_eq!(rcode, RCODE_NOERROR);
+        let RecordData::A(address) = records[0].data else {
+            panic!("expected an A record");
+        };
+        assert_eq!(address.octets()[..2], [127, 192]);
+        // Stable, and the router maps it back
+        assert_eq!(synthetic_address(onion), address);
+        let target = unmap_synthetic(TargetAddress::IPv4(address, 80));
+        assert_eq!(target.to_string(), format!("{}:80", onion));
+
+        let [a, b, c, d] = address.octets();
+        let ptr = parse_query(&query_packet(2, &format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a), TYPE_PTR)).unwrap();
+        let (_, records) = answer(&ptr, &config).await;
+        assert_eq!(records[0].data, RecordData::Ptr(onion.to_string()));
+    }
+
+    #[test]
+    fn test_cache() {
+        let key = "cache-test.example";
+        assert_eq!(cached(key), None);
+        store(key, &Resolved::Address("10.1.2.3".parse().unwrap()));
+        let (resolved, ttl) = cached(key).unwrap();
+        assert_eq!(resolved, Resolved::Address("10.1.2.3".parse().unwrap()));
+        assert!(ttl <= MAX_TTL.as_secs() as u32);
+
+        // Tor failures aren't cached, missing names are
+        store("servfail-test.example", &Resolved::Failed(RCODE_SERVFAIL));
+        assert_eq!(cached("servfail-test.example"), None);
+        store("nxdomain-test.example", &Resolved::Failed(RCODE_NXDOMAIN));
+        assert!(cached("nxdomain-test.example").unwrap().1 <= NEGATIVE_TTL.as_secs() as u32);
+    }
+}
@@ -13,7 +13,7 @@ use crate::torrc_parser;
 /// - For relay mode: APP_ELTOR_TOR_RELAY_CONTROL_PASSWORD
 /// - For client mode: APP_ELTOR_TOR_CONTROL_PASSWORD
 /// - Fallback: "password1234_" as default
-fn get_tor_control_password(mode: &EltorMode) -> String {
+pub(crate) fn get_tor_control_password(mode: &EltorMode) -> String {
     let env_var = match mode {
         EltorMode::Client => "APP_ELTOR_TOR_CONTROL_PASSWORD",
         EltorMode::Relay | EltorMode::Both => "APP_ELTOR_TOR_RELAY_CONTROL_PASSWORD",
@@ -53,12 +53,12 @@ pub struct EltorProcessHandle {
     task_handle: tokio::task::JoinHandle<()>,
     abort_handle: tokio::task::AbortHandle,
     mode: EltorMode,
-    tor_pids: Vec<u32>, // Track actual Tor daemon PIDs
+    tor_processes: Vec<String>, // Process registry names of the spawned Tor daemons
 }
 
 impl EltorProcessHandle {
     async fn stop(&mut self) -> Result<(), String> {
-        info!("🛑 Stopping {} process with {} Tor daemon(s)", self.mode, self.tor_pids.len());
+        info!("🛑 Stopping {} process with {} Tor daemon(s)", self.mode, self.tor_processes.len());
 
         // Step 1: Stop Arti if this is the last eltord process stopping
         info!("🛑 Stopping Arti process...");
@@ -74,15 +74,16 @@ impl EltorProcessHandle {
         // Step 3: Wait a moment for graceful shutdown
         tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
 
-        // Step 4: Force kill all tracked Tor daemon PIDs
-        for pid in &self.tor_pids {
-            info!("🔪 Killing Tor daemon PID: {}", pid);
-            if let Err(e) = kill_process_by_pid(*pid).await {
-                warn!("⚠️ Failed to kill Tor daemon PID {}: {}", pid, e);
+        // Step 4: Force kill all tracked Tor daemons that are still the processes we found
+        for name in &self.tor_processes {
+            match crate::processes::terminate(name) {
+                Ok(Some(record)) => info!("🔪 Killed Tor daemon {} (PID: {})", name, record.pid),
+                Ok(None) => info!("ℹ️  Tor daemon {} already exited", name),
+                Err(e) => warn!("⚠️ Failed to kill Tor daemon {}: {}", name, e),
             }
         }
 
-        info!("✅ {} process stopped and {} Tor daemon(s) killed", self.mode, self.tor_pids.len());
+        info!("✅ {} process stopped and {} Tor daemon(s) killed", self.mode, self.tor_processes.len());
         Ok(())
     }
 }
@@ -122,27 +123,15 @@ impl EltorMode {
     }
 }
 
-/// Get the PID file path for a given mode and path configuration
-/// 
-/// This centralizes the logic for determining where PID files are stored:
-/// - Tauri mode: Uses app_data_dir (e.g., ~/Library/Application Support/eltor/)
-/// - Web mode: Uses bin_dir/data/
-fn get_pid_file_path(mode: &EltorMode, path_config: &PathConfig) -> std::path::PathBuf {
-    let path = if let Some(app_data_dir) = &path_config.app_data_dir {
-        // Tauri mode - PID files in app data directory
-        match mode {
-            EltorMode::Client => app_data_dir.join("eltord-client.pid"),
-            EltorMode::Relay | EltorMode::Both => app_data_dir.join("eltord-relay.pid"),
-        }
-    } else {
-        // Non-Tauri mode - PID files in bin/data
-        match mode {
-            EltorMode::Client => path_config.bin_dir.join("data").join("eltord-client.pid"),
-            EltorMode::Relay | EltorMode::Both => path_config.bin_dir.join("data").join("eltord-relay.pid"),
-        }
-    };
-    // eprintln!("🔧 [get_pid_file_path] mode={:?}, is_tauri={}, path={:?}", mode, path_config.app_data_dir.is_some(), path);
-    path
+/// Process registry and supervisor name for an eltord mode
+///
+/// "both" runs a single eltord that owns the relay control port, so it
+/// shares the relay entry.
+pub(crate) fn eltord_process_name(mode: &EltorMode) -> &'static str {
+    match mode {
+        EltorMode::Client => "eltord-client",
+        EltorMode::Relay | EltorMode::Both => "eltord-relay",
+    }
 }
 
 /// Clean up old data files before activation
@@ -439,14 +428,18 @@ impl EltorManager {
         tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
 
         // Discover the PIDs of spawned Tor daemons by checking the control ports
-        let mut tor_pids = Vec::new();
+        let mut tor_processes = Vec::new();
         let control_port = mode.get_control_port(&self.path_config).await;
         
         // info!("🔍 Looking for Tor daemon on control port {}", control_port);
         if let Ok(port_num) = control_port.parse::<u16>() {
             if let Ok(Some(pid)) = crate::ports::get_pid_using_port(port_num).await {
                 info!("✅ Found Tor daemon PID {} on port {}", pid, control_port);
-                tor_pids.push(pid);
+                let name = format!("eltor-{}-tor-{}", mode, control_port);
+                match crate::processes::register(&name, pid, Some(mode.to_string())) {
+                    Ok(_) => tor_processes.push(name),
+                    Err(e) => warn!("⚠️ Could not register Tor daemon PID {}: {}", pid, e),
+                }
             } else {
                 warn!("⚠️ No process found on control port {} yet", control_port);
             }
@@ -459,20 +452,24 @@ impl EltorManager {
             if let Ok(port_num) = client_port.parse::<u16>() {
                 if let Ok(Some(pid)) = crate::ports::get_pid_using_port(port_num).await {
                     info!("✅ Found client Tor daemon PID {} on port {}", pid, client_port);
-                    tor_pids.push(pid);
+                    let name = format!("eltor-{}-tor-{}", mode, client_port);
+                    match crate::processes::register(&name, pid, Some(mode.to_string())) {
+                        Ok(_) => tor_processes.push(name),
+                        Err(e) => warn!("⚠️ Could not register client Tor daemon PID {}: {}", pid, e),
+                    }
                 } else {
                     warn!("⚠️ No process found on client control port {} yet", client_port);
                 }
             }
         }
 
-        info!("📋 Tracking {} Tor daemon PID(s) for {} mode", tor_pids.len(), &mode);
+        info!("📋 Tracking {} Tor daemon PID(s) for {} mode", tor_processes.len(), &mode);
 
         Ok(EltorProcessHandle {
             task_handle,
             abort_handle,
             mode: mode.clone(),
-            tor_pids,
+            tor_processes,
         })
     }
 
@@ -563,31 +560,6 @@ impl EltorManager {
         &self,
         port: &str,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        use tokio::io::{AsyncReadExt, AsyncWriteExt};
-        use tokio::net::TcpStream;
-
-        info!(
-            "🔌 Connecting to Tor control port {} to send shutdown...",
-            port
-        );
-
-        let mut stream = match tokio::time::timeout(
-            tokio::time::Duration::from_secs(2),
-            TcpStream::connect(format!("127.0.0.1:{}", port)),
-        )
-        .await
-        {
-            Ok(Ok(stream)) => stream,
-            Ok(Err(e)) => {
-                warn!("⚠️ Could not connect to Tor control port {}: {}", port, e);
-                return Err(e.into());
-            }
-            Err(_) => {
-                warn!("⚠️ Timeout connecting to Tor control port {}", port);
-                return Err("Connection timeout".into());
-            }
-        };
-
         // Determine the mode based on the port to get the correct password
         // We need to check which mode uses this port
         let client_port = EltorMode::Client.get_control_port(&self.path_config).await;
@@ -602,33 +574,7 @@ impl EltorManager {
             EltorMode::Relay
         };
 
-        let password = get_tor_control_password(&mode);
-
-        // Authenticate
-        let auth_command = format!("AUTHENTICATE \"{}\"\r\n", password);
-        stream.write_all(auth_command.as_bytes()).await?;
-
-        let mut buf = vec![0; 1024];
-        let n = tokio::time::timeout(tokio::time::Duration::from_secs(2), stream.read(&mut buf))
-            .await??;
-        let response = String::from_utf8_lossy(&buf[..n]);
-
-        if response.contains("250 OK") {
-            let shutdown_command = "SIGNAL SHUTDOWN\r\n";
-            stream.write_all(shutdown_command.as_bytes()).await?;
-            info!("🛑 Sent shutdown command to Tor on port {}", port);
-
-            // Give Tor a moment to process the shutdown command
-            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        } else {
-            warn!(
-                "⚠️ Failed to authenticate with Tor control port {}: {}",
-                port,
-                response.trim()
-            );
-        }
-
-        Ok(())
+        send_tor_shutdown_command(port, &mode).await
     }
 
     /// Get the current status of eltor processes
@@ -688,79 +634,58 @@ fn start_arti_and_socks_router(mode: String, path_config: PathConfig) {
             }
             
             // Start SOCKS router after Arti is ready
+            if let Err(e) = crate::routing::load_routing_config(&path_config) {
+                warn!("⚠️ Failed to load routing rules, using default routing: {}", e);
+            }
+            if let Err(e) = crate::killswitch::load_kill_switch(&path_config) {
+                warn!("⚠️ Failed to load kill switch setting: {}", e);
+            }
             info!("🔀 Starting SOCKS Router...");
-            if let Err(e) = crate::socks::start_socks_router().await {
-                warn!("⚠️ SOCKS Router failed to start: {}", e);
-                info!("   This is non-critical - eltord will still function without the SOCKS router");
-            } else {
-                let router_port = std::env::var("APP_ELTOR_SOCKS_ROUTER_PORT")
-                    .unwrap_or_else(|_| "18048".to_string());
-                info!("✅ SOCKS Router started successfully on port {}", router_port);
+            match crate::socks::start_socks_router().await {
+                Ok(status) => info!(
+                    "✅ SOCKS Router running on {}",
+                    status.bind_address.unwrap_or_default()
+                ),
+                Err(e) => {
+                    warn!("⚠️ SOCKS Router failed to start: {}", e);
+                    info!("   This is non-critical - eltord will still function without the SOCKS router");
+                }
             }
         });
     });
 }
 
-/// Check if eltord is running by reading PID file and verifying process exists
-pub async fn is_eltord_running(mode: EltorMode, path_config: &PathConfig) -> bool {
-    // eprintln!("🔍 [is_eltord_running] Checking mode={:?}, is_tauri={}", mode, path_config.app_data_dir.is_some());
-    let pid_file = get_pid_file_path(&mode, path_config);
-    log::info!("🔍 [is_eltord_running] Checking PID file: {:?} (Tauri: {})", pid_file, path_config.app_data_dir.is_some());
-
-    // Read PID from file
-    let pid = match tokio::fs::read_to_string(&pid_file).await {
-        Ok(content) => match content.trim().parse::<u32>() {
-            Ok(pid) => {
-                // eprintln!("✅ [is_eltord_running] Found PID {} in file {:?}", pid, pid_file);
-                pid
-            },
-            Err(_) => {
-                // eprintln!("❌ [is_eltord_running] Invalid PID in file {:?}", pid_file);
-                return false;
-            }
-        },
-        Err(_e) => {
-            // eprintln!("❌ [is_eltord_running] No PID file at {:?}: {}", pid_file, e);
-            return false;
-        }
-    };
-
-    // Verify process is actually running
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command as StdCommand;
-        match StdCommand::new("kill")
-            .arg("-0") // Signal 0 just checks if process exists
-            .arg(pid.to_string())
-            .output()
-        {
-            Ok(output) => {
-                let is_running = output.status.success();
-                // eprintln!("🔍 [is_eltord_running] Process {} exists: {}", pid, is_running);
-                is_running
-            },
-            Err(_e) => {
-                // eprintln!("❌ [is_eltord_running] Failed to check process {}: {}", pid, e);
-                false
-            }
-        }
+/// Stop the SOCKS router unless another eltord (client or relay) still uses it
+async fn stop_socks_router_if_unused() {
+    let in_use = [EltorMode::Client, EltorMode::Relay]
+        .iter()
+        .any(|mode| crate::processes::is_running(eltord_process_name(mode)));
+    if in_use {
+        log::info!("🔀 Keeping SOCKS Router running for the other eltord instance");
+        return;
     }
-
-    #[cfg(target_os = "linux")]
-    {
-        // Check if /proc/<pid> exists
-        std::path::Path::new(&format!("/proc/{}", pid)).exists()
+    log::info!("🔀 Stopping SOCKS Router...");
+    if let Err(e) = crate::socks::stop_socks_router().await {
+        log::warn!("⚠️ Failed to stop SOCKS Router: {}", e);
+    } else {
+        log::info!("✅ SOCKS Router stopped successfully");
     }
+}
 
-    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-    {
-        // Fallback - assume running if PID file exists
-        true
-    }
+/// Check if eltord is running by looking it up in the process registry
+///
+/// The registered PID only counts if it still belongs to the eltord we
+/// started (same start time and executable), so a recycled PID reads as stopped.
+pub async fn is_eltord_running(mode: EltorMode, path_config: &PathConfig) -> bool {
+    crate::processes::attach(path_config);
+    let name = eltord_process_name(&mode);
+    let running = crate::processes::is_running(name);
+    log::info!("🔍 [is_eltord_running] {} running: {} (Tauri: {})", name, running, path_config.app_data_dir.is_some());
+    running
 }
 
-/// Get eltord status by checking PID files
-pub async fn get_eltord_status_from_pid_files(path_config: &PathConfig) -> EltorStatus {
+/// Get eltord status from the process registry
+pub async fn get_eltord_status_from_registry(path_config: &PathConfig) -> EltorStatus {
     let client_running = is_eltord_running(EltorMode::Client, path_config).await;
     let relay_running = is_eltord_running(EltorMode::Relay, path_config).await;
 
@@ -777,62 +702,51 @@ async fn send_tor_shutdown_command(
     port: &str,
     mode: &EltorMode,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    use tokio::io::{AsyncReadExt, AsyncWriteExt};
-    use tokio::net::TcpStream;
+    use crate::control::{ControlClient, ControlError, Signal};
 
     info!(
         "🔌 Connecting to Tor control port {} to send shutdown...",
         port
     );
 
-    let mut stream = match tokio::time::timeout(
-        tokio::time::Duration::from_secs(2),
-        TcpStream::connect(format!("127.0.0.1:{}", port)),
-    )
-    .await
-    {
-        Ok(Ok(stream)) => stream,
-        Ok(Err(e)) => {
+    let client = match ControlClient::connect(&format!("127.0.0.1:{}", port)).await {
+        Ok(client) => client.with_timeout(tokio::time::Duration::from_secs(2)),
+        Err(e) => {
             warn!("⚠️ Could not connect to Tor control port {}: {}", port, e);
             return Err(e.into());
         }
-        Err(_) => {
-            warn!("⚠️ Timeout connecting to Tor control port {}", port);
-            return Err("Connection timeout".into());
-        }
     };
 
     let password = get_tor_control_password(mode);
 
-    // Authenticate
-    let auth_command = format!("AUTHENTICATE \"{}\"\r\n", password);
-    stream.write_all(auth_command.as_bytes()).await?;
-
-    let mut buf = vec![0; 1024];
-    let n = tokio::time::timeout(tokio::time::Duration::from_secs(2), stream.read(&mut buf))
-        .await??;
-    let response = String::from_utf8_lossy(&buf[..n]);
-
-    if response.contains("250 OK") {
-        let shutdown_command = "SIGNAL SHUTDOWN\r\n";
-        stream.write_all(shutdown_command.as_bytes()).await?;
-        info!("🛑 Sent shutdown command to Tor on port {}", port);
+    match client.authenticate(&password).await {
+        Ok(()) => {}
+        Err(ControlError::Authentication(msg)) => {
+            warn!(
+                "⚠️ Failed to authenticate with Tor control port {}: {}",
+                port, msg
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    }
 
-        // Give Tor a moment to process the shutdown command
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-    } else {
-        warn!(
-            "⚠️ Failed to authenticate with Tor control port {}: {}",
-            port,
-            response.trim()
-        );
+    match client.signal(Signal::Shutdown).await {
+        // Tor may close the socket before we see the 250
+        Ok(()) | Err(ControlError::Closed) => {
+            info!("🛑 Sent shutdown command to Tor on port {}", port);
+        }
+        Err(e) => return Err(e.into()),
     }
 
+    // Give Tor a moment to process the shutdown command
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
     Ok(())
 }
 
 /// Helper function to check if a process with given PID is still running
-fn is_process_running(pid: u32) -> bool {
+pub(crate) fn is_process_running(pid: u32) -> bool {
     #[cfg(target_os = "macos")]
     {
         use std::process::Command as StdCommand;
@@ -876,7 +790,10 @@ fn is_process_running(pid: u32) -> bool {
     }
 }
 
-/// Deactivate eltord by reading PID file and attempting graceful shutdown first
+/// Deactivate eltord via the process registry, attempting graceful shutdown first
+///
+/// The registered PID is verified (start time and executable) before anything
+/// is signalled, so a PID that was reused by another program is never killed.
 pub async fn deactivate_eltord_process(mode: String) -> Result<String, String> {
     // eprintln!("🛑 [deactivate_eltord_process] Called with mode={}", mode);
     let mode_enum = match EltorMode::from_str(&mode) {
@@ -886,6 +803,9 @@ pub async fn deactivate_eltord_process(mode: String) -> Result<String, String> {
         }
     };
 
+    // Intentional shutdown - make sure the supervisor doesn't bring it back
+    crate::supervisor::unwatch(eltord_process_name(&mode_enum));
+
     // Get path config
     let path_config = match crate::paths::PathConfig::new() {
         Ok(pc) => {
@@ -896,30 +816,39 @@ pub async fn deactivate_eltord_process(mode: String) -> Result<String, String> {
             return Err(format!("Failed to get path config: {}", e));
         }
     };
-    
-    let pid_file = get_pid_file_path(&mode_enum, &path_config);
-    log::info!("🛑 [deactivate_eltord_process] Looking for PID file: {:?} (Tauri: {})", pid_file, path_config.app_data_dir.is_some());
-
-    // Read PID from file
-    let pid = match std::fs::read_to_string(&pid_file) {
-        Ok(content) => match content.trim().parse::<u32>() {
-            Ok(pid) => {
-                // eprintln!("✅ [deactivate_eltord_process] Found PID {} in {:?}", pid, pid_file);
-                pid
-            },
-            Err(_) => {
-                // eprintln!("❌ [deactivate_eltord_process] Invalid PID in file {:?}", pid_file);
-                return Err(format!("Invalid PID in file {:?}", pid_file));
-            }
-        },
-        Err(_e) => {
-            // eprintln!("❌ [deactivate_eltord_process] No PID file found at {:?}: {}", pid_file, e);
-            return Err(format!("No PID file found at {:?} - process may not be running", pid_file));
+    crate::processes::attach(&path_config);
+
+    let process_name = eltord_process_name(&mode_enum);
+    log::info!("🛑 [deactivate_eltord_process] Looking up {} in process registry (Tauri: {})", process_name, path_config.app_data_dir.is_some());
+
+    let record = match crate::processes::get(process_name) {
+        Some(record) => record,
+        None => {
+            return Err(format!("Eltord {} is not running (no registered process)", mode_enum));
         }
     };
+    let pid = record.pid;
+
+    match crate::processes::verify(&record) {
+        crate::processes::ProcessState::Running => {}
+        crate::processes::ProcessState::Exited => {
+            crate::processes::unregister_pid(process_name, pid);
+            crate::bootstrap::stop_bootstrap_monitor(&mode_enum);
+            return Err(format!("Eltord {} is not running (PID {} already exited)", mode_enum, pid));
+        }
+        crate::processes::ProcessState::Recycled => {
+            log::warn!("🛡️ PID {} no longer belongs to eltord {}, refusing to signal it", pid, mode_enum);
+            crate::processes::unregister_pid(process_name, pid);
+            crate::bootstrap::stop_bootstrap_monitor(&mode_enum);
+            return Err(format!("Eltord {} is not running (PID {} was reused by another process)", mode_enum, pid));
+        }
+    }
 
     log::info!("🛑 Attempting graceful shutdown of eltord {} (PID: {})", mode_enum, pid);
 
+    // Stop following bootstrap progress before the control port goes away
+    crate::bootstrap::stop_bootstrap_monitor(&mode_enum);
+
     // Step 1: Attempt graceful shutdown via Tor control port
     let control_port = mode_enum.get_control_port(&path_config).await;
     
@@ -937,29 +866,18 @@ pub async fn deactivate_eltord_process(mode: String) -> Result<String, String> {
         for poll_count in 0..MAX_POLLS {
             tokio::time::sleep(tokio::time::Duration::from_millis(POLL_INTERVAL_MS)).await;
             
-            if !is_process_running(pid) {
+            if crate::processes::verify(&record) != crate::processes::ProcessState::Running {
                 log::info!("✅ Process {} exited gracefully after {}ms", 
                     pid, poll_count * POLL_INTERVAL_MS as u32);
                 
-                // Process has exited - skip kill_process and just clean up PID file
-                if let Err(e) = std::fs::remove_file(&pid_file) {
-                    // eprintln!("⚠️ [deactivate_eltord_process] Failed to remove PID file {:?}: {}", pid_file, e);
-                    log::warn!("⚠️ Failed to remove PID file {:?}: {}", pid_file, e);
-                } else {
-                    // eprintln!("✅ [deactivate_eltord_process] Removed PID file: {:?}", pid_file);
-                    log::info!("🗑️ Removed PID file: {:?}", pid_file);
-                }
+                // Process has exited - skip the kill and just forget it
+                crate::processes::unregister_pid(process_name, pid);
                 
                 log::info!("✅ Eltord {} stopped gracefully (PID: {})", mode_enum, pid);
                 // eprintln!("✅ [deactivate_eltord_process] Successfully deactivated {} (PID: {})", mode_enum, pid);
                 
                 // Stop SOCKS router after successful eltord deactivation
-                log::info!("🔀 Stopping SOCKS Router...");
-                if let Err(e) = crate::socks::stop_socks_router().await {
-                    log::warn!("⚠️ Failed to stop SOCKS Router: {}", e);
-                } else {
-                    log::info!("✅ SOCKS Router stopped successfully");
-                }
+                stop_socks_router_if_unused().await;
                 
                 // Stop Arti after successful eltord deactivation
                 log::info!("🛑 Stopping Arti...");
@@ -978,34 +896,20 @@ pub async fn deactivate_eltord_process(mode: String) -> Result<String, String> {
     }
 
     // Step 3: Fallback to forceful kill if graceful shutdown failed or timed out
+    // terminate() re-verifies the PID right before signalling it
     log::info!("🔪 Force killing eltord {} (PID: {})", mode_enum, pid);
-    use crate::ports::kill_process;
-    if let Err(e) = kill_process(pid) {
+    if let Err(e) = crate::processes::terminate(process_name) {
         // eprintln!("❌ [deactivate_eltord_process] Failed to kill process {}: {}", pid, e);
         log::error!("❌ Failed to force kill process {}: {}", pid, e);
         return Err(format!("Failed to kill process {}: {}", pid, e));
     }
     // eprintln!("✅ [deactivate_eltord_process] Killed process {}", pid);
 
-    // Remove PID file
-    if let Err(e) = std::fs::remove_file(&pid_file) {
-        // eprintln!("⚠️ [deactivate_eltord_process] Failed to remove PID file {:?}: {}", pid_file, e);
-        log::warn!("⚠️ Failed to remove PID file {:?}: {}", pid_file, e);
-    } else {
-        // eprintln!("✅ [deactivate_eltord_process] Removed PID file: {:?}", pid_file);
-        log::info!("🗑️ Removed PID file: {:?}", pid_file);
-    }
-
     log::info!("✅ Eltord {} stopped (force killed, PID: {})", mode_enum, pid);
     // eprintln!("✅ [deactivate_eltord_process] Successfully deactivated {} (PID: {})", mode_enum, pid);
     
     // Stop SOCKS router after successful eltord deactivation
-    log::info!("🔀 Stopping SOCKS Router...");
-    if let Err(e) = crate::socks::stop_socks_router().await {
-        log::warn!("⚠️ Failed to stop SOCKS Router: {}", e);
-    } else {
-        log::info!("✅ SOCKS Router stopped successfully");
-    }
+    stop_socks_router_if_unused().await;
     
     // Stop Arti after successful eltord deactivation
     log::info!("🛑 Stopping Arti...");
@@ -1027,8 +931,8 @@ pub async fn cleanup_all_eltord_processes() {
         match deactivate_eltord_process(mode.to_string()).await {
             Ok(msg) => log::info!("✅ {}", msg),
             Err(e) => {
-                // Only warn if it's not a "no PID file" error
-                if !e.contains("No PID file found") && !e.contains("not running") {
+                // Only warn if it's not a "nothing to stop" error
+                if !e.contains("not running") {
                     log::warn!("⚠️ {}", e);
                 }
             }
@@ -1038,8 +942,43 @@ pub async fn cleanup_all_eltord_processes() {
     log::info!("✅ All eltord processes cleaned up");
 }
 
+/// Hand a freshly spawned eltord to the supervisor so it is restarted if it crashes
+fn supervise_eltord(mode: &EltorMode, pid: u32, enable_logging: bool) {
+    let mode_str = mode.to_string().to_string();
+    crate::supervisor::watch(
+        eltord_process_name(mode),
+        pid,
+        std::sync::Arc::new(move || {
+            let mode_str = mode_str.clone();
+            Box::pin(async move {
+                // Activation re-registers the new PID with the supervisor
+                tokio::task::spawn_blocking(move || activate_eltord_process(mode_str, enable_logging))
+                    .await
+                    .map_err(|e| format!("Activation task failed: {}", e))?
+                    .map(|_| ())
+            })
+        }),
+    );
+}
+
+/// Spawn eltord for a mode and return its PID
+///
+/// This only covers getting the process running - use
+/// `crate::activation::start_activation` to also follow it through bootstrap.
+pub fn activate_eltord_process(mode: String, enable_logging: bool) -> Result<u32, String> {
+    activate_eltord_process_with(mode, enable_logging, &|| {})
+}
+
 // TODO clean this up
-pub fn activate_eltord_process(mode: String, enable_logging: bool) {
+/// Same as `activate_eltord_process`, calling `on_spawning` once preparation
+/// is done and eltord is about to be spawned
+// Each platform branch below returns from its own cfg block
+#[allow(clippy::needless_return)]
+pub(crate) fn activate_eltord_process_with(
+    mode: String,
+    enable_logging: bool,
+    on_spawning: &dyn Fn(),
+) -> Result<u32, String> {
     // eprintln!("🚀 [activate_eltord_process] Called with mode={}, enable_logging={}", mode, enable_logging);
     log::info!("🚀 [activate_eltord_process] mode={}, enable_logging={}", mode, enable_logging);
 
@@ -1047,7 +986,7 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
         Ok(m) => m,
         Err(_) => {
             warn!("⚠️ Invalid eltor mode specified for activation: {}", mode);
-            return;
+            return Err(format!("Invalid eltor mode: {}", mode));
         }
     };
 
@@ -1063,14 +1002,14 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
             Some(dir) => dir.join("eltor"),
             None => {
                 warn!("⚠️ Failed to get app data directory");
-                return;
+                return Err("Failed to get app data directory".to_string());
             }
         };
         
         // Try to create app data directory
         if let Err(e) = std::fs::create_dir_all(&app_data_dir) {
             warn!("⚠️ Failed to create app data directory: {}", e);
-            return;
+            return Err(format!("Failed to create app data directory: {}", e));
         }
         
         // In Tauri mode, bin_dir should come from environment variable set by Tauri frontend
@@ -1111,7 +1050,7 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
             Ok(pc) => pc,
             Err(e) => {
                 warn!("⚠️ Failed to get path config: {}", e);
-                return;
+                return Err(format!("Failed to get path config: {}", e));
             }
         }
     };
@@ -1134,8 +1073,9 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
         path_config.bin_dir.join("data").join("eltor.log")
     };
     
-    let pid_file = get_pid_file_path(&mode_enum, &path_config);
-    log::info!("🚀 [activate_eltord_process] Will write PID file to: {:?} (Tauri: {})", pid_file, path_config.app_data_dir.is_some());
+    let process_name = eltord_process_name(&mode_enum);
+    crate::processes::attach(&path_config);
+    log::info!("🚀 [activate_eltord_process] Will register process as: {} (Tauri: {})", process_name, path_config.app_data_dir.is_some());
 
     // if is_tauri_context() {
     //     eprintln!("isTauriContext=true, {:?}", path_config);
@@ -1157,11 +1097,13 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
     log::info!("   Torrc path string: {}", torrc_path_str);
     log::info!("   Log path: {:?}", eltord_log_path);
     log::info!("   Log path string: {}", log_path_str);
-    log::info!("   PID file: {:?}", pid_file);
+    log::info!("   Registry name: {}", process_name);
     log::info!("   Working dir: {:?}", path_config.bin_dir);
 
     // Use std::process::Command for true isolation - NO tokio involvement
     use std::process::Command as StdCommand;
+
+    on_spawning();
     
     #[cfg(target_os = "macos")]
     {
@@ -1177,14 +1119,14 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
         log::info!("   Mode: {}", mode_enum);
         log::info!("   Torrc: {}", torrc_path_str);
         log::info!("   Log file: {}", log_path_str);
-        log::info!("   PID file: {:?}", pid_file);
+        log::info!("   Registry name: {}", process_name);
         
         // Check if binary exists and is executable
         if !eltord_path.exists() {
             let error_msg = format!("❌ eltord binary not found at {:?}", eltord_path);
             // eprintln!("{}", error_msg);
             log::error!("{}", error_msg);
-            return;
+            return Err(format!("eltord binary not found at {:?}", eltord_path));
         }
         
         let mut cmd = StdCommand::new(&eltord_path);
@@ -1208,46 +1150,25 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
             // On macOS, prefer posix_spawn over fork (avoid multi-thread fork issues)
             // This is critical for Tauri apps which have multiple threads running
         
-        match cmd.spawn()
+        return match cmd.spawn()
         {
             Ok(child) => {
                 let pid = child.id();
                 log::info!("✅ Eltord {} spawned with PID: {} - process is now independent", mode_enum, pid);
                 log::info!("⏳ Tor will bootstrap in background (10-15 seconds typical)");
                 
-                // Start Arti after eltord successfully starts
-                log::info!("🚀 Starting Arti for eltord mode: {}", mode_str_for_logging);
-                let mode_for_arti = mode_str_for_arti.clone();
-                let path_for_arti = path_config.clone();
-                std::thread::spawn(move || {
-                    let rt = tokio::runtime::Runtime::new().unwrap();
-                    rt.block_on(async {
-                        if let Err(e) = crate::arti::start_arti_with_eltord(&mode_for_arti, &path_for_arti).await {
-                            warn!("⚠️ Failed to start Arti: {}", e);
-                        } else {
-                            info!("✅ Arti started successfully for mode: {}", mode_for_arti);
-                        }
-                        
-                        // Start SOCKS router after Arti is ready
-                        info!("🔀 Starting SOCKS Router...");
-                        if let Err(e) = crate::socks::start_socks_router().await {
-                            warn!("⚠️ SOCKS Router failed to start: {}", e);
-                            info!("   This is non-critical - eltord will still function without the SOCKS router");
-                        } else {
-                            let router_port = std::env::var("APP_ELTOR_SOCKS_ROUTER_PORT")
-                                .unwrap_or_else(|_| "18048".to_string());
-                            info!("✅ SOCKS Router started successfully on port {}", router_port);
-                        }
-                    });
-                });
+                // Follow bootstrap progress over the control port
+                crate::bootstrap::start_bootstrap_monitor(mode_enum.clone(), path_config.clone());
+
+                // No reaper on macOS - the supervisor polls the PID instead
+                supervise_eltord(&mode_enum, pid, enable_logging);
+
+                // Start Arti and the SOCKS router after eltord successfully starts
+                start_arti_and_socks_router(mode_str_for_arti.clone(), path_config.clone());
                 
-                // Write PID to file synchronously
-                if let Err(e) = std::fs::write(&pid_file, pid.to_string()) {
-                    // eprintln!("⚠️ [activate_eltord_process] Failed to write PID file {:?}: {}", pid_file, e);
-                    log::warn!("⚠️ Failed to write PID file {:?}: {}", pid_file, e);
-                } else {
-                    // eprintln!("✅ [activate_eltord_process] Wrote PID {} to {:?}", pid, pid_file);
-                    log::info!("✅ Wrote PID {} to {:?}", pid, pid_file);
+                // Record PID, start time and executable so we never signal a recycled PID
+                if let Err(e) = crate::processes::register(process_name, pid, Some(mode_enum.to_string())) {
+                    log::warn!("⚠️ Failed to register eltord process: {}", e);
                 }
                 
                 // Process is now 100% isolated - we don't even wait on it
@@ -1256,6 +1177,7 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
                 
                 // eprintln!("✅ [activate_eltord_process] Successfully activated {} (PID: {})", mode_enum, pid);
                 log::info!("🎯 Activation complete - eltord is running independently (PID: {})", pid);
+                Ok(pid)
             }
             Err(e) => {
                 let error_msg = format!("❌ Failed to spawn eltord {}: {}", mode_enum, e);
@@ -1269,8 +1191,9 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
                 if let Some(os_error) = e.raw_os_error() {
                     log::error!("   OS error code: {}", os_error);
                 }
+                Err(format!("Failed to spawn eltord {}: {}", mode_enum, e))
             }
-        }
+        };
     }
     
     #[cfg(all(unix, not(target_os = "macos")))]
@@ -1310,7 +1233,7 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
                 });
         }
         
-        match cmd.spawn() {
+        return match cmd.spawn() {
             Ok(mut child) => {
                 let pid = child.id();
                 log::info!("✅ Eltord {} spawned with PID: {} - process is now independent", mode_enum, pid);
@@ -1318,19 +1241,29 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
                 
                 // Start Arti and SOCKS router after eltord successfully starts
                 start_arti_and_socks_router(mode_str_for_arti.clone(), path_config.clone());
+
+                // Follow bootstrap progress over the control port
+                crate::bootstrap::start_bootstrap_monitor(mode_enum.clone(), path_config.clone());
                 
-                // Write PID to file synchronously
-                if let Err(e) = std::fs::write(&pid_file, pid.to_string()) {
-                    log::warn!("⚠️ Failed to write PID file {:?}: {}", pid_file, e);
+                // Record PID, start time and executable so we never signal a recycled PID
+                if let Err(e) = crate::processes::register(process_name, pid, Some(mode_enum.to_string())) {
+                    log::warn!("⚠️ Failed to register eltord process: {}", e);
                 }
                 
+                supervise_eltord(&mode_enum, pid, enable_logging);
+
                 // Spawn a background task to reap the child process when it exits
                 // This prevents zombie processes while still allowing it to run independently
+                let service_name = eltord_process_name(&mode_enum);
                 std::thread::spawn(move || {
                     // Wait for the child process to exit (non-blocking for the main app)
                     match child.wait() {
                         Ok(status) => {
                             log::info!("🧹 Eltord process exited with status: {}", status);
+                            // Once reaped the PID is free for reuse - drop it from the registry
+                            crate::processes::unregister_pid(process_name, pid);
+                            // Ignored by the supervisor if this was an intentional shutdown
+                            crate::supervisor::record_exit(service_name, pid, status.code());
                         }
                         Err(e) => {
                             log::warn!("⚠️ Error waiting for eltord process: {}", e);
@@ -1340,12 +1273,14 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
                 
                 // eprintln!("✅ [activate_eltord_process] Successfully activated {} (PID: {})", mode_enum, pid);
                 log::info!("🎯 Activation complete - eltord is running independently (PID: {})", pid);
+                Ok(pid)
             }
             Err(e) => {
                 // eprintln!("❌ [activate_eltord_process] Failed to spawn eltord {}: {}", mode_enum, e);
                 log::error!("❌ Failed to spawn eltord {}: {}", mode_enum, e);
+                Err(format!("Failed to spawn eltord {}: {}", mode_enum, e))
             }
-        }
+        };
     }
     
     #[cfg(not(unix))]
@@ -1369,7 +1304,7 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
             .stderr(Stdio::null())
             .stdin(Stdio::null());
         
-        match cmd.spawn()
+        return match cmd.spawn()
         {
             Ok(child) => {
                 let pid = child.id();
@@ -1378,19 +1313,26 @@ pub fn activate_eltord_process(mode: String, enable_logging: bool) {
                 
                 // Start Arti and SOCKS router after eltord successfully starts
                 start_arti_and_socks_router(mode_str_for_arti.clone(), path_config.clone());
+
+                // Follow bootstrap progress over the control port
+                crate::bootstrap::start_bootstrap_monitor(mode_enum.clone(), path_config.clone());
                 
-                // Write PID to file synchronously
-                if let Err(e) = std::fs::write(&pid_file, pid.to_string()) {
-                    log::warn!("⚠️ Failed to write PID file {:?}: {}", pid_file, e);
+                // Record PID, start time and executable so we never signal a recycled PID
+                if let Err(e) = crate::processes::register(process_name, pid, Some(mode_enum.to_string())) {
+                    log::warn!("⚠️ Failed to register eltord process: {}", e);
                 }
                 
                 std::mem::forget(child);
+
+                supervise_eltord(&mode_enum, pid, enable_logging);
                 
                 log::info!("🎯 Activation complete - eltord is running independently (PID: {})", pid);
+                Ok(pid)
             }
             Err(e) => {
                 log::error!("❌ Failed to spawn eltord {}: {}", mode_enum, e);
+                Err(format!("Failed to spawn eltord {}: {}", mode_enum, e))
             }
-        }
+        };
     }
 }
@@ -0,0 +1,152 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::control::{self, Signal};
+use crate::eltor::{self, EltorMode};
+use crate::paths::PathConfig;
+
+/// Tor delays NEWNYM signals that arrive less than 10 seconds apart
+pub const NEWNYM_RATE_LIMIT: Duration = Duration::from_secs(10);
+
+/// When we last sent NEWNYM to the client
+static LAST_NEWNYM: OnceLock<Mutex<Option<Instant>>> = OnceLock::new();
+
+fn last_newnym() -> &'static Mutex<Option<Instant>> {
+    LAST_NEWNYM.get_or_init(|| Mutex::new(None))
+}
+
+/// Result of a "new identity" request
+#[derive(Debug, Clone, Serialize)]
+pub struct NewIdentityResult {
+    /// Whether SIGNAL NEWNYM was sent to the client
+    pub sent: bool,
+    /// The request came inside Tor's rate limit window and was not sent
+    pub throttled: bool,
+    /// Seconds until a new identity can be requested again
+    pub retry_after_secs: u64,
+    pub message: String,
+}
+
+/// Time left in the rate limit window, if the last signal was too recent
+fn remaining_wait(last: Option<Instant>, now: Instant) -> Option<Duration> {
+    let elapsed = now.saturating_duration_since(last?);
+    if elapsed < NEWNYM_RATE_LIMIT {
+        Some(NEWNYM_RATE_LIMIT - elapsed)
+    } else {
+        None
+    }
+}
+
+/// The running eltord that carries client traffic: the client, else a combined client+relay
+fn running_client_mode() -> Option<EltorMode> {
+    if crate::processes::is_running(eltor::eltord_process_name(&EltorMode::Client)) {
+        return Some(EltorMode::Client);
+    }
+    // "both" runs under the relay's process name, the registry records which mode it is
+    crate::processes::get_running(eltor::eltord_process_name(&EltorMode::Both))
+        .filter(|record| record.mode.as_deref() == Some(EltorMode::Both.to_string()))
+        .map(|_| EltorMode::Both)
+}
+
+/// Claim the rate limit window, or report how long is left in it
+///
+/// Returns the previous send time so a failed signal can hand the window back.
+fn reserve_newnym(now: Instant) -> Result<Option<Instant>, Duration> {
+    let mut last = last_newnym().lock().unwrap();
+    if let Some(wait) = remaining_wait(*last, now) {
+        return Err(wait);
+    }
+    Ok(last.replace(now))
+}
+
+/// Undo a reservation, unless another request has claimed the window since
+fn release_newnym(reserved: Instant, previous: Option<Instant>) {
+    let mut last = last_newnym().lock().unwrap();
+    if *last == Some(reserved) {
+        *last = previous;
+    }
+}
+
+/// Switch the eltord client to clean circuits with SIGNAL NEWNYM
+///
+/// New streams stop using existing circuits, so an expensive or slow path is
+/// dropped without restarting the client. Tor only honours one NEWNYM every
+/// 10 seconds and silently delays the rest, so requests inside that window are
+/// reported as throttled instead of being queued.
+pub async fn new_identity(path_config: &PathConfig) -> Result<NewIdentityResult, String> {
+    let Some(mode) = running_client_mode() else {
+        return Err("Eltord client is not running".to_string());
+    };
+
+    let now = Instant::now();
+    let previous = match reserve_newnym(now) {
+        Ok(previous) => previous,
+        Err(wait) => {
+            let retry_after_secs = wait.as_secs_f64().ceil() as u64;
+            info!("⏳ New identity throttled, retry in {}s", retry_after_secs);
+            return Ok(NewIdentityResult {
+                sent: false,
+                throttled: true,
+                retry_after_secs,
+                message: format!("New identity was requested too recently, try again in {}s", retry_after_secs),
+            });
+        }
+    };
+
+    let result = match control::connect_for_mode(&mode, path_config).await {
+        Ok(client) => {
+            let result = client.signal(Signal::Newnym).await;
+            client.quit().await;
+            result.map_err(|e| {
+                warn!("⚠️ SIGNAL NEWNYM failed: {}", e);
+                format!("Failed to request new identity: {}", e)
+            })
+        }
+        Err(e) => Err(format!("Failed to connect to {} control port: {}", mode, e)),
+    };
+    if let Err(e) = result {
+        release_newnym(now, previous);
+        return Err(e);
+    }
+
+    info!("🎭 New identity requested ({} mode), new streams will use fresh circuits", mode);
+    Ok(NewIdentityResult {
+        sent: true,
+        throttled: false,
+        retry_after_secs: NEWNYM_RATE_LIMIT.as_secs(),
+        message: "New identity requested, new connections will use fresh circuits".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remaining_wait() {
+        let now = Instant::now();
+        assert_eq!(remaining_wait(None, now), None);
+
+        let wait = remaining_wait(Some(now), now + Duration::from_secs(3)).unwrap();
+        assert_eq!(wait, Duration::from_secs(7));
+
+        assert_eq!(remaining_wait(Some(now), now + NEWNYM_RATE_LIMIT), None);
+    }
+
+    #[test]
+    fn test_reserve_newnym() {
+        let now = Instant::now() + Duration::from_secs(3600);
+        let previous = reserve_newnym(now).unwrap();
+
+        // A second request inside the window is refused, even before the signal is sent
+        assert!(reserve_newnym(now + Duration::from_secs(1)).is_err());
+
+        // A failed signal hands the window back
+        release_newnym(now, previous);
+        assert_eq!(*last_newnym().lock().unwrap(), previous);
+        assert!(reserve_newnym(now).is_ok());
+        release_newnym(now, previous);
+    }
+}
@@ -0,0 +1,317 @@
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::bootstrap;
+use crate::eltor::EltorMode;
+use crate::paths::PathConfig;
+use crate::socks::TargetAddress;
+use crate::transparent::{self, NftRules};
+
+const KILL_SWITCH_FILE: &str = "kill_switch.json";
+/// nftables table for the egress rules, separate from the transparent proxy's
+const NFT_TABLE: &str = "eltor_kill_switch";
+/// Backstop for bootstrap changes we don't get an event for (eltord stopped)
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Persisted kill switch setting
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KillSwitchConfig {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KillSwitchStatus {
+    pub enabled: bool,
+    /// The El Tor client has bootstrapped to 100%
+    pub client_ready: bool,
+    /// Clearnet connections are being refused right now
+    pub engaged: bool,
+    /// Connections refused since startup
+    pub blocked_connections: u64,
+}
+
+/// Sent when the kill switch is toggled, engages, releases or refuses a connection
+#[derive(Debug, Clone, Serialize)]
+pub struct KillSwitchEvent {
+    /// "enabled", "disabled", "engaged", "released" or "blocked"
+    pub kind: String,
+    pub message: String,
+    /// Refused target, for "blocked"
+    pub target: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+struct KillSwitch {
+    enabled: AtomicBool,
+    /// Last engaged state we told subscribers about
+    engaged: Mutex<Option<bool>>,
+    blocked: AtomicU64,
+    sender: broadcast::Sender<KillSwitchEvent>,
+}
+
+fn kill_switch() -> &'static KillSwitch {
+    static KILL_SWITCH: OnceLock<KillSwitch> = OnceLock::new();
+    KILL_SWITCH.get_or_init(|| {
+        let (sender, _) = broadcast::channel(100);
+        let enabled = std::env::var("APP_ELTOR_KILL_SWITCH")
+            .map(|value| matches!(value.trim(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+        KillSwitch {
+            enabled: AtomicBool::new(enabled),
+            engaged: Mutex::new(None),
+            blocked: AtomicU64::new(0),
+            sender,
+        }
+    })
+}
+
+fn emit(kind: &str, message: String, target: Option<String>) {
+    let _ = kill_switch().sender.send(KillSwitchEvent {
+        kind: kind.to_string(),
+        message,
+        target,
+        timestamp: Utc::now(),
+    });
+}
+
+pub fn is_enabled() -> bool {
+    kill_switch().enabled.load(Ordering::Relaxed)
+}
+
+/// The eltord instance that can carry client traffic and reports bootstrap 100%
+pub(crate) fn ready_mode() -> Option<EltorMode> {
+    [EltorMode::Client, EltorMode::Both].into_iter().find(|mode| {
+        bootstrap::get_bootstrap_status(mode.to_string()).is_some_and(|status| status.is_ready())
+    })
+}
+
+/// Whether the El Tor client (or a combined client+relay) reports bootstrap 100%
+pub fn client_ready() -> bool {
+    ready_mode().is_some()
+}
+
+/// Clearnet connections are refused while the switch is on and the client isn't ready
+pub fn is_engaged() -> bool {
+    is_enabled() && !client_ready()
+}
+
+/// Tell subscribers when the engaged state changes
+fn refresh() {
+    let engaged = is_engaged();
+    let mut last = kill_switch().engaged.lock().unwrap();
+    if *last == Some(engaged) {
+        return;
+    }
+    let first = last.is_none();
+    *last = Some(engaged);
+    drop(last);
+
+    match (engaged, first) {
+        (true, _) => {
+            warn!("🛑 Kill switch engaged: refusing clearnet connections until the El Tor client is bootstrapped");
+            emit("engaged", "El Tor client not ready, clearnet connections are refused".to_string(), None);
+        }
+        (false, false) => {
+            info!("✅ Kill switch released: El Tor client is ready");
+            emit("released", "El Tor client ready, connections allowed".to_string(), None);
+        }
+        (false, true) => {}
+    }
+}
+
+/// Count and announce a refused connection
+pub(crate) fn record_blocked(target: &TargetAddress) {
+    kill_switch().blocked.fetch_add(1, Ordering::Relaxed);
+    debug!("🛑 Kill switch refused connection to {}", target);
+    emit("blocked", format!("Refused {}: El Tor client not ready", target), Some(target.to_string()));
+}
+
+/// Follow bootstrap progress so engage/release events go out without traffic (once per process)
+pub(crate) fn start_watcher() {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async {
+        let mut bootstrap_events = bootstrap::subscribe_bootstrap();
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                received = bootstrap_events.recv() => {
+                    if let Err(broadcast::error::RecvError::Closed) = received {
+                        break;
+                    }
+                }
+            }
+            refresh();
+        }
+    });
+}
+
+pub fn get_kill_switch_status() -> KillSwitchStatus {
+    let client_ready = client_ready();
+    let enabled = is_enabled();
+    KillSwitchStatus {
+        enabled,
+        client_ready,
+        engaged: enabled && !client_ready,
+        blocked_connections: kill_switch().blocked.load(Ordering::Relaxed),
+    }
+}
+
+pub fn subscribe_kill_switch() -> broadcast::Receiver<KillSwitchEvent> {
+    kill_switch().sender.subscribe()
+}
+
+pub fn kill_switch_config_path(path_config: &PathConfig) -> PathBuf {
+    path_config.data_dir.join(KILL_SWITCH_FILE)
+}
+
+/// Load the saved setting; without a file the APP_ELTOR_KILL_SWITCH default stays
+pub fn load_kill_switch(path_config: &PathConfig) -> Result<KillSwitchStatus, String> {
+    let path = kill_switch_config_path(path_config);
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            let config: KillSwitchConfig = serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid kill switch config {:?}: {}", path, e))?;
+            kill_switch().enabled.store(config.enabled, Ordering::Relaxed);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read kill switch config {:?}: {}", path, e)),
+    }
+    if is_enabled() {
+        info!("🛑 Kill switch is on");
+    }
+    refresh();
+    Ok(get_kill_switch_status())
+}
+
+/// Turn the kill switch on or off and remember it
+pub fn set_kill_switch(path_config: &PathConfig, enabled: bool) -> Result<KillSwitchStatus, String> {
+    let path = kill_switch_config_path(path_config);
+    let json = serde_json::to_string_pretty(&KillSwitchConfig { enabled }).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write kill switch config {:?}: {}", path, e))?;
+
+    if kill_switch().enabled.swap(enabled, Ordering::Relaxed) != enabled {
+        let (kind, state) = if enabled { ("enabled", "on") } else { ("disabled", "off") };
+        info!("🛑 Kill switch turned {}", state);
+        emit(kind, format!("Kill switch turned {}", state), None);
+    }
+    refresh();
+    Ok(get_kill_switch_status())
+}
+
+/// Users whose egress the firewall rules restrict
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FirewallRulesRequest {
+    /// User names or uids
+    pub users: Vec<String>,
+    /// Still allow private and link-local networks (default false)
+    pub allow_lan: Option<bool>,
+}
+
+/// Build nftables rules that only let the chosen users talk to this machine
+///
+/// The El Tor proxies listen on loopback, so proxied traffic still flows
+/// (including traffic the transparent proxy rules redirect) while anything
+/// that tries to go around them is rejected.
+pub fn generate_firewall_rules(request: &FirewallRulesRequest) -> Result<NftRules, String> {
+    if request.users.is_empty() {
+        return Err("Choose at least one user to restrict".to_string());
+    }
+    let mut uids = Vec::new();
+    for user in &request.users {
+        let uid = transparent::resolve_uid(user.trim())?;
+        if transparent::own_uid() == Some(uid) {
+            return Err(format!("User {} runs El Tor itself; blocking it would cut off Tor", user));
+        }
+        if !uids.contains(&uid) {
+            uids.push(uid);
+        }
+    }
+
+    let mut warnings = Vec::new();
+    if !is_enabled() {
+        warnings.push("The router kill switch is off, so direct routing rules still reach the clearnet".to_string());
+    }
+
+    let mut rules = vec![
+        "# Generated by El Tor: chosen users may only reach the proxies on this machine".to_string(),
+        format!("table inet {}", NFT_TABLE),
+        format!("delete table inet {}", NFT_TABLE),
+        format!("table inet {} {{", NFT_TABLE),
+        "\tchain output {".to_string(),
+        "\t\ttype filter hook output priority filter; policy accept;".to_string(),
+    ];
+    for uid in uids {
+        rules.push(format!("\t\tmeta skuid {} jump restrict", uid));
+    }
+    rules.push("\t}".to_string());
+    rules.push(String::new());
+    rules.push("\tchain restrict {".to_string());
+    rules.push("\t\toifname \"lo\" accept".to_string());
+    if request.allow_lan.unwrap_or(false) {
+        rules.push("\t\tip daddr { 10.0.0.0/8, 169.254.0.0/16, 172.16.0.0/12, 192.168.0.0/16 } accept".to_string());
+        rules.push("\t\tip6 daddr { fc00::/7, fe80::/10 } accept".to_string());
+    }
+    rules.extend(["\t\tcounter reject".to_string(), "\t}".to_string(), "}".to_string()]);
+
+    Ok(NftRules {
+        ruleset: rules.join("\n") + "\n",
+        warnings,
+    })
+}
+
+/// Load the egress rules (needs root or CAP_NET_ADMIN)
+pub async fn apply_firewall_rules(rules: &NftRules) -> Result<(), String> {
+    transparent::run_nft(&["-f", "-"], Some(&rules.ruleset)).await?;
+    info!("🛑 Applied kill switch firewall rules (table inet {})", NFT_TABLE);
+    Ok(())
+}
+
+/// Remove the egress rules; fine if there are none
+pub async fn remove_firewall_rules() -> Result<(), String> {
+    transparent::delete_table(NFT_TABLE).await?;
+    info!("🛑 Removed kill switch firewall rules");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_firewall_rules() {
+        let request = FirewallRulesRequest {
+            users: vec!["4242".to_string(), "4243".to_string(), "4242".to_string()],
+            allow_lan: None,
+        };
+        let ruleset = generate_firewall_rules(&request).unwrap().ruleset;
+        assert_eq!(ruleset.matches("jump restrict").count(), 2);
+        assert!(ruleset.contains("meta skuid 4243 jump restrict"));
+        assert!(ruleset.contains("oifname \"lo\" accept"));
+        assert!(ruleset.contains("counter reject"));
+        assert!(!ruleset.contains("192.168.0.0/16"));
+
+        let request = FirewallRulesRequest {
+            allow_lan: Some(true),
+            ..request
+        };
+        assert!(generate_firewall_rules(&request).unwrap().ruleset.contains("192.168.0.0/16"));
+
+        assert!(generate_firewall_rules(&FirewallRulesRequest::default()).is_err());
+        let own = FirewallRulesRequest {
+            users: vec![transparent::own_uid().unwrap_or(0).to_string()],
+            allow_lan: None,
+        };
+        assert!(generate_firewall_rules(&own).is_err());
+    }
+}
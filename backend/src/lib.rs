@@ -6,34 +6,73 @@ use tokio::sync::RwLock;
 use log::{Log, Metadata, Record, info, warn};
 use chrono::Utc;
 
+pub mod activation;
 pub mod arti;
+pub mod arti_config;
+pub mod arti_logs;
+pub mod bandwidth;
+pub mod bootstrap;
+pub mod circuits;
+pub mod connections;
+pub mod control;
+pub mod dns;
 pub mod eltor;
+pub mod http_proxy;
+pub mod identity;
 pub mod ip;
+pub mod killswitch;
 pub mod lightning;
+pub mod live_config;
+pub mod pac;
 pub mod paths;
 pub mod ports;
+pub mod processes;
+pub mod profiles;
 pub mod routes;
+pub mod routing;
 pub mod socks;
 pub mod state;
+pub mod supervisor;
 pub mod static_files;
 pub mod torrc_parser;
+pub mod transparent;
+pub mod upstream_health;
 pub mod wallet;
 pub mod debug_info;
 
 // Re-export commonly used types for convenience
-pub use arti::{start_arti_with_eltord, stop_arti, is_arti_running, get_arti_status, cleanup_arti};
+pub use activation::{get_activation_job, start_activation, subscribe_activation, ActivationJob, ActivationState};
+pub use arti::{start_arti_with_eltord, stop_arti, is_arti_running, get_arti_status, cleanup_arti, apply_arti_settings, reset_arti_storage};
+pub use arti_config::{get_arti_config, load_arti_settings, ArtiConfigInfo, ArtiSettings, StreamIsolation};
+pub use bandwidth::{get_bandwidth, get_bandwidth_history, subscribe_bandwidth, BandwidthHistory, BandwidthSample, BandwidthStatus};
+pub use bootstrap::{get_bootstrap_status, subscribe_bootstrap, BootstrapStatus};
+pub use circuits::{get_circuits, Circuit, CircuitHop};
+pub use connections::{get_connections, ConnectionInfo, ConnectionsSnapshot};
+pub use control::{ControlClient, ControlError, ControlEvent, Signal};
+pub use identity::{new_identity, NewIdentityResult};
+pub use killswitch::{get_kill_switch_status, load_kill_switch, set_kill_switch, subscribe_kill_switch, KillSwitchEvent, KillSwitchStatus};
 pub use eltor::{
     EltorActivateParams, EltorDeactivateParams,
     EltorManager, EltorStatus, cleanup_all_eltord_processes,
 };
-pub use socks::{start_socks_router, stop_socks_router, is_socks_router_running, SocksRouterConfig};
+pub use routing::{get_routing_config, load_routing_config, RouteAction, RouteDecision, RoutingConfig, RoutingRule};
+pub use socks::{
+    get_socks_router_status, is_socks_router_running, restart_socks_router, start_socks_router,
+    start_socks_router_with_config, stop_socks_router, SocksRouterConfig, SocksRouterConfigUpdate,
+    SocksRouterStatus,
+};
+pub use live_config::{apply_torrc_changes, ApplyMethod, ApplyOutcome};
 pub use lightning::{LightningNode, ListTransactionsResponse, WalletBalanceResponse};
 pub use paths::PathConfig;
+pub use processes::{list_processes, ProcessInfo, ProcessRecord, ProcessState};
+pub use profiles::{list_profiles, Profile, ProfileInfo, ProfilePorts, ProfileRequest};
 pub use ports::{
     cleanup_ports, cleanup_ports_startup, cleanup_ports_with_torrc, cleanup_tor_ports_only,
     get_ports_to_check, get_tor_ports_only, cleanup_backend_port,
 };
 pub use state::{AppState, EltordStatusResponse, LogEntry, MessageResponse, StatusResponse};
+pub use upstream_health::{get_upstream_health, UpstreamHealth};
+pub use supervisor::{get_service_statuses, subscribe_supervisor, ServiceStatus, SupervisorEvent};
 use tokio::sync::broadcast;
 pub use wallet::{start_phoenixd, stop_phoenixd, read_phoenixd_logs, read_phoenixd_stderr_logs};
 pub use debug_info::DebugInfo;
@@ -155,7 +194,14 @@ impl Log for BroadcastLogger {
 
 /// Create a new AppState for Tauri usage
 pub fn create_app_state(use_phoenixd_embedded: bool, path_config: PathConfig) -> AppState {
-    AppState::new(use_phoenixd_embedded, path_config)
+    // Pick up processes a previous backend run left behind
+    processes::attach(&path_config);
+    let state = AppState::new(use_phoenixd_embedded, path_config);
+    // Crash/restart reports from the supervisor show up in the log stream
+    supervisor::attach_app_state(state.clone());
+    // So does everything Arti prints
+    arti_logs::attach_app_state(state.clone());
+    state
 }
 
 /// Set up custom logger to capture ALL logs (including from eltor library) and send them to broadcast channel
@@ -193,6 +239,7 @@ pub async fn initialize_app_state_with_path_config(
     
     // Update the path_config in AppState
     app_state.path_config = Arc::new(path_config.clone());
+    processes::attach(&path_config);
     
     let manager = eltor::EltorManager::new(state.clone(), path_config);
     app_state.set_eltor_manager(manager);
@@ -225,15 +272,11 @@ pub async fn initialize_phoenixd(state: Arc<RwLock<AppState>>) -> Result<(), Str
     }
 }
 
-/// Activate eltord - requires manager in AppState
-pub fn activate_eltord(mode: String, enable_logging: bool) -> Result<String, String> {
-    // **Important** Use spawn_blocking to isolate the synchronous process spawning from the async runtime
+/// Activate eltord and return the job tracking it through bootstrap
+pub fn activate_eltord(mode: String, enable_logging: bool) -> Result<ActivationJob, String> {
+    // The job spawns eltord on its own thread, isolated from the async runtime,
     // or else the C tor binary maybe have networking issues and interruptions
-    tokio::task::spawn_blocking(move || {
-        eltor::activate_eltord_process(mode, enable_logging);
-    });
-    
-    Ok("activation started".to_string())
+    activation::start_activation(&mode, enable_logging)
 }
 
 /// Deactivate eltord - requires manager in AppState
@@ -249,10 +292,10 @@ pub async fn deactivate_eltord(state: Arc<RwLock<AppState>>, mode: EltorMode) ->
     manager.deactivate(params).await
 }
 
-/// Get eltord status - uses PID files instead of manager
+/// Get eltord status - uses the process registry instead of manager
 pub async fn get_eltord_status(state: Arc<RwLock<AppState>>) -> EltordStatusResponse {
     let app_state = state.read().await;
-    let status = crate::eltor::get_eltord_status_from_pid_files(&app_state.path_config).await;
+    let status = crate::eltor::get_eltord_status_from_registry(&app_state.path_config).await;
     drop(app_state); // Release the read lock
     
     EltordStatusResponse {
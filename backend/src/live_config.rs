@@ -0,0 +1,223 @@
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::control::{self, ControlClient, ControlError, Signal};
+use crate::eltor::{self, EltorMode};
+use crate::paths::PathConfig;
+use crate::torrc_parser;
+
+/// How a torrc change reached (or didn't reach) the running daemon
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyMethod {
+    /// Pushed with SETCONF
+    Setconf,
+    /// Tor refused SETCONF but picked the change up on SIGNAL RELOAD
+    Reload,
+    /// The daemon is running with the old value until it is restarted
+    RestartRequired,
+    /// eltord isn't running for this mode - the file is read on next start
+    NotRunning,
+}
+
+/// Result of pushing torrc edits to a live eltord
+#[derive(Debug, Clone, Serialize)]
+pub struct ApplyOutcome {
+    pub mode: String,
+    pub method: ApplyMethod,
+    pub restart_required: bool,
+    pub message: String,
+}
+
+impl ApplyOutcome {
+    fn new(mode: &EltorMode, method: ApplyMethod, message: String) -> Self {
+        Self {
+            mode: mode.to_string().to_string(),
+            method,
+            restart_required: method == ApplyMethod::RestartRequired,
+            message,
+        }
+    }
+}
+
+/// Push the current torrc values of `keys` to the running eltord for a mode
+///
+/// Call this after editing the mode's torrc file. Each option is tried with
+/// SETCONF first (no circuits are torn down); if Tor refuses, the whole file is
+/// re-read with SIGNAL RELOAD and the live values are compared against the
+/// file to tell whether a restart is still needed.
+pub async fn apply_torrc_changes(mode: &EltorMode, path_config: &PathConfig, keys: &[&str]) -> ApplyOutcome {
+    if !crate::processes::is_running(eltor::eltord_process_name(mode)) {
+        return ApplyOutcome::new(
+            mode,
+            ApplyMethod::NotRunning,
+            format!("eltord {} is not running, changes apply on next start", mode),
+        );
+    }
+
+    let torrc_path = path_config.get_torrc_path(Some(mode.get_torrc_file()));
+    let mut desired = Vec::with_capacity(keys.len());
+    for key in keys {
+        desired.push((key.to_string(), torrc_parser::get_torrc_config(&torrc_path, key).await));
+    }
+
+    let client = match control::connect_for_mode(mode, path_config).await {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("⚠️ [{}] Could not reach control port to apply {:?}: {}", mode, keys, e);
+            return ApplyOutcome::new(
+                mode,
+                ApplyMethod::RestartRequired,
+                format!("Could not reach the {} control port ({}), restart eltord to apply", mode, e),
+            );
+        }
+    };
+
+    let outcome = match apply_with_client(&client, &desired).await {
+        Ok((method, message)) => ApplyOutcome::new(mode, method, message),
+        Err(e) => ApplyOutcome::new(
+            mode,
+            ApplyMethod::RestartRequired,
+            format!("Failed to apply changes to the running daemon ({}), restart eltord to apply", e),
+        ),
+    };
+    client.quit().await;
+
+    if outcome.restart_required {
+        warn!("⚠️ [{}] {}", mode, outcome.message);
+    } else {
+        info!("🔄 [{}] {}", mode, outcome.message);
+    }
+    outcome
+}
+
+/// Push lightning payment settings to both running daemons
+///
+/// The client torrc carries the node configs; the relay torrc also has the
+/// BOLT12 offer it advertises.
+pub async fn apply_lightning_config_changes(path_config: &PathConfig) -> Vec<ApplyOutcome> {
+    vec![
+        apply_torrc_changes(&EltorMode::Client, path_config, &["PaymentLightningNodeConfig"]).await,
+        apply_torrc_changes(
+            &EltorMode::Relay,
+            path_config,
+            &["PaymentLightningNodeConfig", "PaymentBolt12Offer"],
+        )
+        .await,
+    ]
+}
+
+/// Note to append to an API message when a change is still waiting on a restart
+pub fn restart_note(outcomes: &[ApplyOutcome]) -> String {
+    let modes: Vec<&str> = outcomes
+        .iter()
+        .filter(|outcome| outcome.restart_required)
+        .map(|outcome| outcome.mode.as_str())
+        .collect();
+    if modes.is_empty() {
+        String::new()
+    } else {
+        format!(" (restart eltord {} to apply)", modes.join(" and "))
+    }
+}
+
+/// SETCONF, falling back to SIGNAL RELOAD plus a GETCONF check
+///
+/// `desired` holds every value the torrc file has for each key; an empty list
+/// resets the option to its default.
+pub(crate) async fn apply_with_client(
+    client: &ControlClient,
+    desired: &[(String, Vec<String>)],
+) -> Result<(ApplyMethod, String), ControlError> {
+    let keys: Vec<&str> = desired.iter().map(|(key, _)| key.as_str()).collect();
+
+    let mut options: Vec<(&str, Option<&str>)> = Vec::new();
+    for (key, values) in desired {
+        if values.is_empty() {
+            options.push((key, None));
+        }
+        for value in values {
+            options.push((key, Some(value)));
+        }
+    }
+
+    let refusal = match client.set_conf(&options).await {
+        Ok(()) => {
+            return Ok((ApplyMethod::Setconf, format!("Applied {} to the running daemon", keys.join(", "))));
+        }
+        // 552 unrecognized option / 553 transition not allowed / 513 bad value
+        Err(ControlError::Reply { code, message }) => format!("{} {}", code, message),
+        Err(e) => return Err(e),
+    };
+
+    client.signal(Signal::Reload).await?;
+
+    for (key, values) in desired {
+        let live = match client.get_conf(key).await {
+            Ok(live) => live,
+            Err(ControlError::Reply { .. }) => {
+                return Ok((
+                    ApplyMethod::RestartRequired,
+                    format!("{} can't be changed while running (Tor replied {}), restart eltord to apply", key, refusal),
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+        if &live != values {
+            return Ok((
+                ApplyMethod::RestartRequired,
+                format!("{} can't be changed while running (Tor replied {}), restart eltord to apply", key, refusal),
+            ));
+        }
+    }
+
+    Ok((ApplyMethod::Reload, format!("Reloaded torrc, {} now live", keys.join(", "))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::tests::mock_control_port;
+
+    fn desired(key: &str, value: &str) -> Vec<(String, Vec<String>)> {
+        vec![(key.to_string(), vec![value.to_string()])]
+    }
+
+    #[tokio::test]
+    async fn test_apply_with_setconf() {
+        let port = mock_control_port(vec![("SETCONF PaymentRateMsats=\"5000\"", "250 OK\r\n")]).await;
+        let client = ControlClient::connect_port(port).await.unwrap();
+
+        let (method, _) = apply_with_client(&client, &desired("PaymentRateMsats", "5000")).await.unwrap();
+        assert_eq!(method, ApplyMethod::Setconf);
+    }
+
+    #[tokio::test]
+    async fn test_apply_falls_back_to_reload() {
+        let port = mock_control_port(vec![
+            ("SETCONF PaymentRateMsats=\"5000\"", "553 Transition not allowed\r\n"),
+            ("SIGNAL RELOAD", "250 OK\r\n"),
+            ("GETCONF PaymentRateMsats", "250 PaymentRateMsats=5000\r\n"),
+        ])
+        .await;
+        let client = ControlClient::connect_port(port).await.unwrap();
+
+        let (method, _) = apply_with_client(&client, &desired("PaymentRateMsats", "5000")).await.unwrap();
+        assert_eq!(method, ApplyMethod::Reload);
+    }
+
+    #[tokio::test]
+    async fn test_apply_reports_restart_required() {
+        let port = mock_control_port(vec![
+            ("SETCONF ORPort=\"9001\"", "553 Transition not allowed\r\n"),
+            ("SIGNAL RELOAD", "250 OK\r\n"),
+            ("GETCONF ORPort", "250 ORPort=9000\r\n"),
+        ])
+        .await;
+        let client = ControlClient::connect_port(port).await.unwrap();
+
+        let (method, message) = apply_with_client(&client, &desired("ORPort", "9001")).await.unwrap();
+        assert_eq!(method, ApplyMethod::RestartRequired);
+        assert!(message.contains("553"));
+    }
+}
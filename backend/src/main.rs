@@ -122,14 +122,27 @@ async fn main() {
         state.set_lightning_node(node);
     }
 
-    // Start SOCKS router in background
+    // Load SOCKS routing rules before the router takes connections
+    if let Err(e) = eltor_backend::load_routing_config(&path_config) {
+        info!("⚠️ Failed to load routing rules, using default routing: {}", e);
+    }
+    if let Err(e) = eltor_backend::load_kill_switch(&path_config) {
+        info!("⚠️ Failed to load kill switch setting: {}", e);
+    }
+    // The router reaches Arti on the port from the managed Arti settings
+    if let Err(e) = eltor_backend::load_arti_settings(&path_config) {
+        info!("⚠️ Failed to load Arti settings, using defaults: {}", e);
+    }
+
+    // Start SOCKS router (runs on its own runtime until stopped)
     info!("🔀 Starting SOCKS Router...");
-    tokio::spawn(async {
-        if let Err(e) = eltor_backend::start_socks_router().await {
-            info!("⚠️ SOCKS Router failed: {}", e);
-        }
-    });
-    info!("✅ SOCKS Router started in background");
+    match eltor_backend::start_socks_router().await {
+        Ok(status) => info!(
+            "✅ SOCKS Router started on {}",
+            status.bind_address.unwrap_or_default()
+        ),
+        Err(e) => info!("⚠️ SOCKS Router failed: {}", e),
+    }
 
     // Initialize shared EltorManager
     let state_arc = std::sync::Arc::new(tokio::sync::RwLock::new(state.clone()));
@@ -208,6 +221,12 @@ async fn main() {
         .merge(eltor_backend::routes::wallet::create_routes())
         .merge(eltor_backend::routes::phoenix::create_routes())
         .merge(eltor_backend::routes::debug::create_routes())
+        .merge(eltor_backend::routes::supervisor::create_routes())
+        .merge(eltor_backend::routes::processes::create_routes())
+        .merge(eltor_backend::routes::profiles::create_routes())
+        .merge(eltor_backend::routes::routing::create_routes())
+        .merge(eltor_backend::routes::socks::create_routes())
+        .merge(eltor_backend::routes::arti::create_routes())
         // Serve static frontend files (this should be last to catch all non-API routes)
         .fallback(static_files::serve_static)
         .layer(cors)
@@ -239,9 +258,19 @@ async fn main() {
     info!("� SOCKS Router: {}:{}", bind_address, socks_router_port);
     info!("�📋 API endpoints:");
     info!("   POST /api/eltord/activate/:mode");
+    info!("   GET  /api/eltord/activation/:id");
+    info!("   GET  /api/eltord/activation/:id/stream");
     info!("   POST /api/eltord/deactivate/:mode");
     info!("   GET  /api/eltord/status");
     info!("   GET  /api/eltord/logs");
+    info!("   GET  /api/eltord/bootstrap/:mode");
+    info!("   GET  /api/eltord/bootstrap/stream/:mode");
+    info!("   GET  /api/eltord/bandwidth");
+    info!("   GET  /api/eltord/bandwidth/:mode");
+    info!("   GET  /api/eltord/bandwidth/stream/:mode");
+    info!("   GET  /api/eltord/circuits?mode=client");
+    info!("   POST /api/eltord/new-identity");
+    info!("   GET  /api/eltord/tor-status/:mode");
     info!("   GET  /api/wallet/info");
     info!("   GET  /api/wallet/balance");
     info!("   POST /api/wallet/invoice");
@@ -255,6 +284,42 @@ async fn main() {
     info!("   POST /api/phoenix/start");
     info!("   POST /api/phoenix/stop");
     info!("   GET  /api/debug");
+    info!("   GET  /api/supervisor/status");
+    info!("   GET  /api/supervisor/events");
+    info!("   GET  /api/processes");
+    info!("   GET  /api/processes/:name");
+    info!("   GET  /api/profiles");
+    info!("   POST /api/profiles");
+    info!("   GET  /api/profiles/:id");
+    info!("   DELETE /api/profiles/:id");
+    info!("   POST /api/profiles/:id/clone");
+    info!("   POST /api/profiles/:id/start");
+    info!("   POST /api/profiles/:id/stop");
+    info!("   POST /api/profiles/:id/lightning");
+    info!("   DELETE /api/profiles/:id/lightning");
+    info!("   GET  /api/socks/routing");
+    info!("   PUT  /api/socks/routing");
+    info!("   POST /api/socks/routing/test");
+    info!("   GET  /api/socks/upstreams");
+    info!("   GET  /api/socks/status");
+    info!("   POST /api/socks/start");
+    info!("   POST /api/socks/stop");
+    info!("   POST /api/socks/restart");
+    info!("   GET  /api/socks/connections");
+    info!("   POST /api/socks/transparent/rules");
+    info!("   DELETE /api/socks/transparent/rules");
+    info!("   POST /api/socks/transparent/apply");
+    info!("   GET  /api/socks/kill-switch");
+    info!("   PUT  /api/socks/kill-switch");
+    info!("   GET  /api/socks/kill-switch/events");
+    info!("   POST /api/socks/kill-switch/firewall/rules");
+    info!("   DELETE /api/socks/kill-switch/firewall/rules");
+    info!("   POST /api/socks/kill-switch/firewall/apply");
+    info!("   GET  /api/socks/proxy.pac");
+    info!("   GET  /api/socks/browser-config");
+    info!("   GET  /api/arti/config");
+    info!("   PUT  /api/arti/config");
+    info!("   POST /api/arti/reset");
     info!("📁 Static files served from frontend/dist/");
     info!("🔧 Environment variables injected into frontend:");
     info!("   BACKEND_PORT: {}", backend_port);
@@ -0,0 +1,424 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::IpAddr;
+
+use crate::routing::{self, RouteAction, RoutingConfig, RoutingRule};
+use crate::socks::SocksRouterConfig;
+
+/// Path the backend serves the PAC file on
+pub const PAC_PATH: &str = "/api/socks/proxy.pac";
+
+/// Which traffic the PAC file sends to the router
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PacMode {
+    /// Everything but local and bypassed hosts, following the routing rules (default)
+    #[default]
+    All,
+    /// Only .onion (and hosts routing rules send through Tor); the rest goes direct
+    Onion,
+}
+
+/// Query options for the PAC and browser config endpoints
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PacOptions {
+    pub mode: Option<PacMode>,
+    /// Comma-separated domain suffixes or globs the browser connects to directly
+    pub bypass: Option<String>,
+    /// Host the browser should use to reach the router; by default the
+    /// router's listen address, or the host the request came in on when the
+    /// router listens on all interfaces
+    pub host: Option<String>,
+}
+
+impl PacOptions {
+    fn bypass_list(&self) -> Vec<String> {
+        self.bypass
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|entry| entry.trim().to_lowercase())
+            .filter(|entry| !entry.is_empty())
+            .collect()
+    }
+
+    /// Query string that reproduces these options, for the PAC URL
+    fn query(&self) -> String {
+        let mut params = form_urlencoded::Serializer::new(String::new());
+        if let Some(PacMode::Onion) = self.mode {
+            params.append_pair("mode", "onion");
+        }
+        let bypass = self.bypass_list();
+        if !bypass.is_empty() {
+            params.append_pair("bypass", &bypass.join(","));
+        }
+        if let Some(host) = &self.host {
+            params.append_pair("host", host);
+        }
+        match params.finish() {
+            query if query.is_empty() => String::new(),
+            query => format!("?{}", query),
+        }
+    }
+}
+
+/// Proxy settings in the shapes browsers and their policy files expect
+#[derive(Debug, Clone, Serialize)]
+pub struct BrowserConfig {
+    pub pac_url: String,
+    /// "host:port" of the SOCKS5 router
+    pub socks_proxy: String,
+    /// "host:port" of the HTTP proxy, when it is enabled
+    pub http_proxy: Option<String>,
+    /// Firefox policies.json
+    pub firefox: serde_json::Value,
+    /// Chromium/Chrome managed policy
+    pub chromium: serde_json::Value,
+    pub warnings: Vec<String>,
+}
+
+/// Host part of a Host header ("example:5174", "[::1]:5174")
+fn strip_port(host: &str) -> &str {
+    if let Some(rest) = host.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or_default();
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') && port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+fn host_port(host: &str, port: u16) -> String {
+    match host.contains(':') {
+        true => format!("[{}]:{}", host, port),
+        false => format!("{}:{}", host, port),
+    }
+}
+
+/// Address a browser can reach a listener on
+fn advertised_host(listen_addr: Option<IpAddr>, options: &PacOptions, request_host: Option<&str>) -> String {
+    if let Some(host) = options.host.as_deref().map(str::trim).filter(|host| !host.is_empty()) {
+        return strip_port(host).to_string();
+    }
+    match listen_addr {
+        Some(ip) if !ip.is_unspecified() => ip.to_string(),
+        Some(_) => request_host
+            .map(strip_port)
+            .filter(|host| !host.is_empty())
+            .unwrap_or("127.0.0.1")
+            .to_string(),
+        None => "127.0.0.1".to_string(),
+    }
+}
+
+fn socks_proxy(config: &SocksRouterConfig, options: &PacOptions, request_host: Option<&str>) -> String {
+    host_port(&advertised_host(config.listen_addr, options, request_host), config.listen_port)
+}
+
+fn http_proxy(config: &SocksRouterConfig, options: &PacOptions, request_host: Option<&str>) -> Option<String> {
+    let port = config.http_proxy_port?;
+    Some(host_port(&advertised_host(config.http_proxy_addr, options, request_host), port))
+}
+
+/// JavaScript string literal
+fn js_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn domain_condition(pattern: &str) -> String {
+    let pattern = pattern.trim().to_lowercase();
+    if pattern.contains(['*', '?']) {
+        return format!("shExpMatch(host, {})", js_string(&pattern));
+    }
+    let suffix = pattern.trim_start_matches('.');
+    format!("(host == {} || dnsDomainIs(host, {}))", js_string(suffix), js_string(&format!(".{}", suffix)))
+}
+
+fn any_of(conditions: Vec<String>) -> Option<String> {
+    match conditions.len() {
+        0 => None,
+        1 => conditions.into_iter().next(),
+        _ => Some(format!("({})", conditions.join(" || "))),
+    }
+}
+
+/// PAC condition for a routing rule, None if it can never match in a browser
+///
+/// PAC can't test IPv6 ranges; rules that send traffic to the router treat
+/// any IPv6 literal as a match (the router decides properly), while direct
+/// rules ignore those ranges so nothing is sent direct by mistake.
+fn rule_condition(rule: &RoutingRule) -> Option<String> {
+    let mut groups = Vec::new();
+    if !rule.domains.is_empty() {
+        groups.push(any_of(rule.domains.iter().map(|domain| domain_condition(domain)).collect())?);
+    }
+    if !rule.cidrs.is_empty() {
+        let direct = rule.action == RouteAction::Direct;
+        let mut conditions = Vec::new();
+        for (ip, prefix) in rule.cidrs.iter().filter_map(|cidr| routing::parse_cidr(cidr).ok()) {
+            match ip {
+                IpAddr::V4(network) => {
+                    let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                    let mask = std::net::Ipv4Addr::from(mask);
+                    conditions.push(format!(
+                        "(isIpv4(host) && isInNet(host, {}, {}))",
+                        js_string(&network.to_string()),
+                        js_string(&mask.to_string())
+                    ));
+                }
+                IpAddr::V6(_) if !direct => conditions.push("isIpv6(host)".to_string()),
+                IpAddr::V6(_) => {}
+            }
+        }
+        conditions.dedup();
+        groups.push(any_of(conditions)?);
+    }
+    if !rule.ports.is_empty() {
+        let conditions = rule
+            .ports
+            .iter()
+            .filter_map(|range| routing::parse_port_range(range).ok())
+            .map(|(start, end)| match start == end {
+                true => format!("port == {}", start),
+                false => format!("(port >= {} && port <= {})", start, end),
+            })
+            .collect();
+        groups.push(any_of(conditions)?);
+    }
+    match groups.is_empty() {
+        true => Some("true".to_string()),
+        false => Some(groups.join(" && ")),
+    }
+}
+
+const PAC_HELPERS: &str = r#"function isIpv4(host) {
+    return /^\d{1,3}(\.\d{1,3}){3}$/.test(host);
+}
+
+function isIpv6(host) {
+    return host.indexOf(":") >= 0;
+}
+
+function portOf(url) {
+    var match = url.match(/^([a-z0-9+.-]+):\/\/(?:[^@\/]*@)?(?:\[[^\]]*\]|[^:\/?#]*)(?::(\d+))?/i);
+    if (match && match[2]) {
+        return parseInt(match[2], 10);
+    }
+    var scheme = match ? match[1].toLowerCase() : "";
+    return scheme == "https" || scheme == "wss" ? 443 : 80;
+}
+"#;
+
+/// Render the PAC file for the router config and routing rules
+///
+/// .onion always goes to the router and local names never do. Routing rules
+/// are applied in order: direct rules become DIRECT, everything else goes to
+/// the router, which applies the same rules again. There is no DIRECT
+/// fallback, so a stopped router fails closed. With the kill switch on
+/// nothing but local names goes direct.
+pub fn generate_pac(
+    config: &SocksRouterConfig,
+    routing: &RoutingConfig,
+    kill_switch: bool,
+    options: &PacOptions,
+    request_host: Option<&str>,
+) -> String {
+    let mut proxies = vec![format!("SOCKS5 {}", socks_proxy(config, options, request_host))];
+    if let Some(http_proxy) = http_proxy(config, options, request_host) {
+        proxies.push(format!("PROXY {}", http_proxy));
+    }
+
+    let mut lines = vec![
+        "// El Tor proxy auto-config, generated by the El Tor backend".to_string(),
+        format!("var ROUTER = {};", js_string(&proxies.join("; "))),
+        String::new(),
+        PAC_HELPERS.to_string(),
+        "function FindProxyForURL(url, host) {".to_string(),
+        "    host = host.toLowerCase();".to_string(),
+        "    var port = portOf(url);".to_string(),
+        "    if (host == \"onion\" || dnsDomainIs(host, \".onion\")) return ROUTER;".to_string(),
+        "    if (isPlainHostName(host) || host == \"localhost\" || dnsDomainIs(host, \".localhost\")) return \"DIRECT\";".to_string(),
+        "    if (host == \"::1\" || (isIpv4(host) && isInNet(host, \"127.0.0.0\", \"255.0.0.0\"))) return \"DIRECT\";".to_string(),
+    ];
+
+    if kill_switch {
+        lines.push("    // Kill switch is on: everything else goes to the router".to_string());
+        lines.push("    return ROUTER;".to_string());
+        lines.push("}".to_string());
+        return lines.join("\n") + "\n";
+    }
+
+    let bypass = options.bypass_list();
+    if !bypass.is_empty() {
+        lines.push("    // Bypass list".to_string());
+        for pattern in &bypass {
+            lines.push(format!("    if ({}) return \"DIRECT\";", domain_condition(pattern)));
+        }
+    }
+
+    for (index, rule) in routing.rules.iter().enumerate() {
+        let label = match &rule.name {
+            Some(name) => format!("rule {} ({})", index, name.replace(['\r', '\n'], " ")),
+            None => format!("rule {}", index),
+        };
+        let result = match rule.action {
+            RouteAction::Direct => "\"DIRECT\"",
+            _ => "ROUTER",
+        };
+        match rule_condition(rule) {
+            Some(condition) => {
+                lines.push(format!("    // Routing {}", label));
+                lines.push(format!("    if ({}) return {};", condition, result));
+            }
+            None => lines.push(format!("    // Routing {} can't match in a browser, left to the router", label)),
+        }
+    }
+
+    match options.mode.unwrap_or_default() {
+        PacMode::All => lines.push("    return ROUTER;".to_string()),
+        PacMode::Onion => lines.push("    return \"DIRECT\";".to_string()),
+    }
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// Proxy settings for Firefox and Chromium, pointing them at the PAC file
+///
+/// `backend_host` is the Host the backend was reached on, used to build the
+/// PAC URL.
+pub fn browser_config(
+    config: &SocksRouterConfig,
+    kill_switch: bool,
+    options: &PacOptions,
+    backend_host: Option<&str>,
+) -> BrowserConfig {
+    let backend_host = backend_host.filter(|host| !host.is_empty()).unwrap_or("127.0.0.1:5174");
+    let pac_url = format!("http://{}{}{}", backend_host, PAC_PATH, options.query());
+
+    let mut warnings = Vec::new();
+    if !config.credentials.is_empty() {
+        warnings.push("The router requires credentials, which browsers can't send to SOCKS proxies".to_string());
+    }
+    if kill_switch && (options.mode == Some(PacMode::Onion) || !options.bypass_list().is_empty()) {
+        warnings.push("The kill switch is on, so onion-only mode and the bypass list are ignored".to_string());
+    }
+    if config.listen_addr.is_some_and(|ip| ip.is_unspecified()) && options.host.is_none() {
+        warnings.push(format!(
+            "The router listens on all interfaces; the proxy address follows the host in the PAC URL ({})",
+            strip_port(backend_host)
+        ));
+    }
+
+    BrowserConfig {
+        firefox: json!({
+            "policies": {
+                "Proxy": {
+                    "Mode": "autoConfig",
+                    "AutoConfigURL": pac_url,
+                    "UseProxyForDNS": true,
+                    "Locked": false
+                }
+            }
+        }),
+        chromium: json!({
+            "ProxyMode": "pac_script",
+            "ProxyPacUrl": pac_url,
+            "ProxyPacMandatory": true
+        }),
+        pac_url,
+        socks_proxy: socks_proxy(config, options, Some(backend_host)),
+        http_proxy: http_proxy(config, options, Some(backend_host)),
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(domains: &[&str], cidrs: &[&str], ports: &[&str], action: RouteAction) -> RoutingRule {
+        RoutingRule {
+            name: None,
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            cidrs: cidrs.iter().map(|c| c.to_string()).collect(),
+            ports: ports.iter().map(|p| p.to_string()).collect(),
+            action,
+        }
+    }
+
+    #[test]
+    fn test_generate_pac() {
+        let config = SocksRouterConfig {
+            http_proxy_port: Some(18049),
+            ..SocksRouterConfig::default()
+        };
+        let routing = RoutingConfig {
+            rules: vec![
+                rule(&["Intranet.example"], &[], &[], RouteAction::Direct),
+                rule(&[], &["10.0.0.0/8", "fd00::/8"], &["80", "8000-9000"], RouteAction::Direct),
+                rule(&[], &["fd00::/8"], &[], RouteAction::Direct),
+                rule(&["*.corp.*"], &["fd00::/8"], &[], RouteAction::Arti),
+            ],
+        };
+        let options = PacOptions {
+            bypass: Some(" lan.example ,".to_string()),
+            ..PacOptions::default()
+        };
+
+        let pac = generate_pac(&config, &routing, false, &options, None);
+        assert!(pac.contains(r#"var ROUTER = "SOCKS5 127.0.0.1:18048; PROXY 127.0.0.1:18049";"#));
+        assert!(pac.contains(r#"if ((host == "lan.example" || dnsDomainIs(host, ".lan.example"))) return "DIRECT";"#));
+        assert!(pac.contains(r#"dnsDomainIs(host, ".intranet.example"))) return "DIRECT";"#));
+        assert!(pac.contains(
+            r#"if ((isIpv4(host) && isInNet(host, "10.0.0.0", "255.0.0.0")) && (port == 80 || (port >= 8000 && port <= 9000))) return "DIRECT";"#
+        ));
+        assert!(pac.contains("// Routing rule 2 can't match in a browser"));
+        assert!(pac.contains(r#"if (shExpMatch(host, "*.corp.*") && isIpv6(host)) return ROUTER;"#));
+        assert!(pac.trim_end().ends_with("return ROUTER;\n}"));
+
+        let onion = PacOptions {
+            mode: Some(PacMode::Onion),
+            ..options.clone()
+        };
+        assert!(generate_pac(&config, &routing, false, &onion, None).trim_end().ends_with("return \"DIRECT\";\n}"));
+
+        // The kill switch leaves nothing but local names direct
+        let pac = generate_pac(&config, &routing, true, &onion, None);
+        assert!(!pac.contains("lan.example") && !pac.contains("intranet.example"));
+        assert!(pac.trim_end().ends_with("return ROUTER;\n}"));
+    }
+
+    #[test]
+    fn test_browser_config() {
+        let config = SocksRouterConfig {
+            listen_addr: Some("0.0.0.0".parse().unwrap()),
+            ..SocksRouterConfig::default()
+        };
+        let options = PacOptions {
+            mode: Some(PacMode::Onion),
+            ..PacOptions::default()
+        };
+        let browser = browser_config(&config, false, &options, Some("192.168.1.5:5174"));
+        assert_eq!(browser.pac_url, "http://192.168.1.5:5174/api/socks/proxy.pac?mode=onion");
+        assert_eq!(browser.socks_proxy, "192.168.1.5:18048");
+        assert_eq!(browser.http_proxy, None);
+        assert_eq!(browser.firefox["policies"]["Proxy"]["AutoConfigURL"], browser.pac_url.as_str());
+        assert_eq!(browser.chromium["ProxyPacUrl"], browser.pac_url.as_str());
+        assert_eq!(browser.warnings.len(), 1);
+
+        // Values with URL syntax in them can't break or extend the PAC URL
+        let options = PacOptions {
+            bypass: Some("a&b.example, c#d=e f".to_string()),
+            host: Some("10.0.0.1&mode=all".to_string()),
+            ..PacOptions::default()
+        };
+        assert_eq!(
+            options.query(),
+            "?bypass=a%26b.example%2Cc%23d%3De+f&host=10.0.0.1%26mode%3Dall"
+        );
+
+        assert_eq!(strip_port("[::1]:5174"), "::1");
+        assert_eq!(strip_port("::1"), "::1");
+        assert_eq!(host_port("::1", 18048), "[::1]:18048");
+    }
+}
@@ -0,0 +1,480 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+use crate::paths::PathConfig;
+
+/// File the registry is persisted to, next to where the old PID files lived
+const REGISTRY_FILE: &str = "processes.json";
+/// PID files written by older versions, imported once and then removed
+const LEGACY_PID_FILES: [(&str, &str); 2] = [
+    ("eltord-client.pid", "eltord-client"),
+    ("eltord-relay.pid", "eltord-relay"),
+];
+/// Start times come from boot time + clock ticks and can be off by a second between reads
+const START_TIME_TOLERANCE_SECS: u64 = 1;
+
+/// A process we spawned and may later need to signal
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProcessRecord {
+    /// Registry key, e.g. "eltord-client", "arti", "phoenixd"
+    pub name: String,
+    pub pid: u32,
+    /// Process start time in seconds since the epoch, as reported by the OS
+    pub start_time: u64,
+    pub exe: Option<PathBuf>,
+    /// eltord mode the process belongs to, if any
+    pub mode: Option<String>,
+    pub registered_at: DateTime<Utc>,
+}
+
+/// What the OS says about a registered PID right now
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessState {
+    Running,
+    Exited,
+    /// The PID is alive but belongs to a different process than the one we started
+    Recycled,
+}
+
+/// A registry entry together with its verified state
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessInfo {
+    #[serde(flatten)]
+    pub record: ProcessRecord,
+    pub state: ProcessState,
+}
+
+struct Registry {
+    file: Option<PathBuf>,
+    records: BTreeMap<String, ProcessRecord>,
+}
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+fn lock() -> MutexGuard<'static, Registry> {
+    REGISTRY
+        .get_or_init(|| {
+            Mutex::new(Registry {
+                file: None,
+                records: BTreeMap::new(),
+            })
+        })
+        .lock()
+        .unwrap()
+}
+
+/// The registry, falling back to the default data directory if nothing attached it yet
+fn registry() -> MutexGuard<'static, Registry> {
+    let mut registry = lock();
+    if registry.file.is_none() {
+        match PathConfig::new() {
+            Ok(path_config) => load_into(&mut registry, &registry_dir(&path_config)),
+            Err(e) => warn!("⚠️ Process registry has no path config, not persisting: {}", e),
+        }
+    }
+    registry
+}
+
+/// Directory the registry file lives in
+///
+/// - Tauri mode: app_data_dir (e.g., ~/Library/Application Support/eltor/)
+/// - Web mode: bin_dir/data/
+fn registry_dir(path_config: &PathConfig) -> PathBuf {
+    match &path_config.app_data_dir {
+        Some(app_data_dir) => app_data_dir.clone(),
+        None => path_config.bin_dir.join("data"),
+    }
+}
+
+/// Point the registry at the data directory of a path config
+///
+/// Loads whatever a previous backend run left behind, dropping entries whose
+/// process is gone or whose PID has since been reused, and merges it with the
+/// processes already registered. Calling it again with the same path config
+/// is a no-op.
+pub fn attach(path_config: &PathConfig) {
+    let dir = registry_dir(path_config);
+    let mut registry = lock();
+    if registry.file.as_deref() != Some(dir.join(REGISTRY_FILE).as_path()) {
+        load_into(&mut registry, &dir);
+    }
+}
+
+fn load_into(registry: &mut Registry, dir: &Path) {
+    let file = dir.join(REGISTRY_FILE);
+    let stored: BTreeMap<String, ProcessRecord> = match std::fs::read_to_string(&file) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("⚠️ Ignoring unreadable process registry {:?}: {}", file, e);
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    };
+
+    let loaded: Vec<(String, ProcessRecord)> = stored
+        .into_iter()
+        .filter(|(name, record)| match verify(record) {
+            ProcessState::Running => {
+                info!("📋 Re-adopted {} (PID: {}) from process registry", name, record.pid);
+                true
+            }
+            ProcessState::Exited => false,
+            ProcessState::Recycled => {
+                warn!("⚠️ PID {} of {} now belongs to another process, forgetting it", record.pid, name);
+                false
+            }
+        })
+        .collect();
+    // Processes registered before a re-attach stay tracked; they're newer
+    // than anything the file says under the same name
+    for (name, record) in loaded {
+        registry.records.entry(name).or_insert(record);
+    }
+    registry.file = Some(file);
+
+    import_legacy_pid_files(registry, dir);
+    save(registry);
+}
+
+/// Adopt eltord processes started by a version that still used PID files
+fn import_legacy_pid_files(registry: &mut Registry, dir: &Path) {
+    for (file_name, name) in LEGACY_PID_FILES {
+        let pid_file = dir.join(file_name);
+        let Ok(content) = std::fs::read_to_string(&pid_file) else {
+            continue;
+        };
+        if let Err(e) = std::fs::remove_file(&pid_file) {
+            warn!("⚠️ Failed to remove legacy PID file {:?}: {}", pid_file, e);
+        }
+
+        let Ok(pid) = content.trim().parse::<u32>() else {
+            continue;
+        };
+        if registry.records.contains_key(name) {
+            continue;
+        }
+        // A bare PID can't be verified, so only trust it if it still looks like eltord
+        let is_eltord = crate::ports::get_process_info(pid)
+            .map(|process_name| process_name.contains("eltord"))
+            .unwrap_or(false);
+        if !is_eltord {
+            continue;
+        }
+        if let Some((start_time, exe)) = inspect(pid) {
+            info!("📋 Imported {} (PID: {}) from legacy PID file", name, pid);
+            let mode = name.trim_start_matches("eltord-").to_string();
+            registry.records.insert(
+                name.to_string(),
+                ProcessRecord {
+                    name: name.to_string(),
+                    pid,
+                    start_time,
+                    exe,
+                    mode: Some(mode),
+                    registered_at: Utc::now(),
+                },
+            );
+        }
+    }
+}
+
+fn save(registry: &Registry) {
+    let Some(file) = &registry.file else {
+        return;
+    };
+    let content = match serde_json::to_string_pretty(&registry.records) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("⚠️ Failed to serialize process registry: {}", e);
+            return;
+        }
+    };
+    if let Some(dir) = file.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    // Write then rename so a crash never leaves a half-written registry
+    let tmp = file.with_extension("json.tmp");
+    if let Err(e) = std::fs::write(&tmp, content).and_then(|_| std::fs::rename(&tmp, file)) {
+        warn!("⚠️ Failed to write process registry {:?}: {}", file, e);
+    }
+}
+
+/// Start time and executable of a live PID
+fn inspect(pid: u32) -> Option<(u64, Option<PathBuf>)> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        ProcessRefreshKind::new().with_exe(UpdateKind::OnlyIfNotSet),
+    );
+    system
+        .process(pid)
+        .map(|process| (process.start_time(), process.exe().map(Path::to_path_buf)))
+}
+
+/// Linux reports replaced binaries as "/path/to/exe (deleted)"
+fn same_exe(a: &Path, b: &Path) -> bool {
+    let strip = |p: &Path| p.to_string_lossy().trim_end_matches(" (deleted)").to_string();
+    strip(a) == strip(b)
+}
+
+/// Check whether a record still describes the process running under its PID
+pub fn verify(record: &ProcessRecord) -> ProcessState {
+    if !crate::eltor::is_process_running(record.pid) {
+        return ProcessState::Exited;
+    }
+    let Some((start_time, exe)) = inspect(record.pid) else {
+        return ProcessState::Exited;
+    };
+
+    if start_time.abs_diff(record.start_time) > START_TIME_TOLERANCE_SECS {
+        return ProcessState::Recycled;
+    }
+    if let (Some(expected), Some(actual)) = (&record.exe, &exe) {
+        if !same_exe(expected, actual) {
+            return ProcessState::Recycled;
+        }
+    }
+    ProcessState::Running
+}
+
+/// Record a freshly spawned process, replacing any previous entry with the same name
+pub fn register(name: &str, pid: u32, mode: Option<&str>) -> Result<ProcessRecord, String> {
+    let (start_time, exe) =
+        inspect(pid).ok_or_else(|| format!("Process {} ({}) exited before it could be registered", name, pid))?;
+
+    let record = ProcessRecord {
+        name: name.to_string(),
+        pid,
+        start_time,
+        exe,
+        mode: mode.map(|m| m.to_string()),
+        registered_at: Utc::now(),
+    };
+
+    let mut registry = registry();
+    registry.records.insert(name.to_string(), record.clone());
+    save(&registry);
+    info!("📋 Registered {} (PID: {})", name, pid);
+    Ok(record)
+}
+
+/// Forget a process without signalling it
+pub fn unregister(name: &str) -> Option<ProcessRecord> {
+    let mut registry = registry();
+    let record = registry.records.remove(name);
+    if record.is_some() {
+        save(&registry);
+    }
+    record
+}
+
+/// Forget a process only if the entry still refers to `pid`
+///
+/// Exit watchers use this so a late exit can't remove the entry of a newer
+/// process that was registered under the same name.
+pub fn unregister_pid(name: &str, pid: u32) -> bool {
+    let mut registry = registry();
+    if registry.records.get(name).map(|r| r.pid) != Some(pid) {
+        return false;
+    }
+    registry.records.remove(name);
+    save(&registry);
+    true
+}
+
+/// Registry entry for a name, without checking the process
+pub fn get(name: &str) -> Option<ProcessRecord> {
+    registry().records.get(name).cloned()
+}
+
+/// Verified running record for a name
+///
+/// Entries whose process has exited or whose PID was reused are removed.
+pub fn get_running(name: &str) -> Option<ProcessRecord> {
+    let record = get(name)?;
+    match verify(&record) {
+        ProcessState::Running => Some(record),
+        state => {
+            if state == ProcessState::Recycled {
+                warn!("⚠️ PID {} of {} now belongs to another process, forgetting it", record.pid, name);
+            }
+            unregister_pid(name, record.pid);
+            None
+        }
+    }
+}
+
+/// Whether the process registered under a name is still the one we started
+pub fn is_running(name: &str) -> bool {
+    get_running(name).is_some()
+}
+
+/// Every managed process with its verified state
+pub fn list_processes() -> Vec<ProcessInfo> {
+    let records: Vec<ProcessRecord> = registry().records.values().cloned().collect();
+    records
+        .into_iter()
+        .map(|record| ProcessInfo {
+            state: verify(&record),
+            record,
+        })
+        .collect()
+}
+
+/// Kill the process registered under a name and forget it
+///
+/// The PID is only signalled after its start time and executable have been
+/// checked, so a PID the OS has handed to another program is never killed.
+/// Returns the record of the process that was killed, or None if there was
+/// nothing (still) running under that name.
+pub fn terminate(name: &str) -> Result<Option<ProcessRecord>, String> {
+    let Some(record) = get(name) else {
+        return Ok(None);
+    };
+
+    match verify(&record) {
+        ProcessState::Running => {
+            crate::ports::kill_process(record.pid)?;
+            unregister_pid(name, record.pid);
+            Ok(Some(record))
+        }
+        ProcessState::Exited => {
+            unregister_pid(name, record.pid);
+            Ok(None)
+        }
+        ProcessState::Recycled => {
+            warn!(
+                "🛡️ Not signalling PID {}: it no longer belongs to {}, forgetting it",
+                record.pid, name
+            );
+            unregister_pid(name, record.pid);
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn own_record(name: &str) -> ProcessRecord {
+        let pid = std::process::id();
+        let (start_time, exe) = inspect(pid).unwrap();
+        ProcessRecord {
+            name: name.to_string(),
+            pid,
+            start_time,
+            exe,
+            mode: None,
+            registered_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_verify_detects_recycled_pid() {
+        let record = own_record("test");
+        assert_eq!(verify(&record), ProcessState::Running);
+
+        let restarted = ProcessRecord {
+            start_time: record.start_time + 3600,
+            ..record.clone()
+        };
+        assert_eq!(verify(&restarted), ProcessState::Recycled);
+
+        let other_binary = ProcessRecord {
+            exe: Some(PathBuf::from("/definitely/not/this/binary")),
+            ..record.clone()
+        };
+        assert_eq!(verify(&other_binary), ProcessState::Recycled);
+    }
+
+    #[test]
+    fn test_verify_detects_exited_process() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        let record = ProcessRecord {
+            name: "exited".to_string(),
+            pid,
+            start_time: 0,
+            exe: None,
+            mode: None,
+            registered_at: Utc::now(),
+        };
+        assert_eq!(verify(&record), ProcessState::Exited);
+    }
+
+    #[test]
+    fn test_same_exe_ignores_deleted_suffix() {
+        assert!(same_exe(Path::new("/bin/eltord"), Path::new("/bin/eltord (deleted)")));
+        assert!(!same_exe(Path::new("/bin/eltord"), Path::new("/bin/arti")));
+    }
+
+    #[test]
+    fn test_load_keeps_only_live_records() {
+        let dir = std::env::temp_dir().join(format!("eltor-registry-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let live = own_record("live");
+        let recycled = ProcessRecord {
+            name: "recycled".to_string(),
+            start_time: live.start_time + 3600,
+            ..live.clone()
+        };
+        let stored: BTreeMap<_, _> = [live.clone(), recycled]
+            .into_iter()
+            .map(|r| (r.name.clone(), r))
+            .collect();
+        std::fs::write(dir.join(REGISTRY_FILE), serde_json::to_string(&stored).unwrap()).unwrap();
+
+        let mut registry = Registry {
+            file: None,
+            records: BTreeMap::new(),
+        };
+        load_into(&mut registry, &dir);
+        assert_eq!(registry.records.len(), 1);
+        assert_eq!(registry.records.get("live"), Some(&live));
+
+        // The pruned registry is written back
+        let saved: BTreeMap<String, ProcessRecord> =
+            serde_json::from_str(&std::fs::read_to_string(dir.join(REGISTRY_FILE)).unwrap()).unwrap();
+        assert_eq!(saved.len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_reattach_keeps_registered_records() {
+        let root = std::env::temp_dir().join(format!("eltor-registry-reattach-{}", std::process::id()));
+        let (first, second) = (root.join("first"), root.join("second"));
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+
+        let stored: BTreeMap<_, _> = [("from-file".to_string(), own_record("from-file"))].into_iter().collect();
+        std::fs::write(second.join(REGISTRY_FILE), serde_json::to_string(&stored).unwrap()).unwrap();
+
+        let mut registry = Registry {
+            file: None,
+            records: BTreeMap::new(),
+        };
+        load_into(&mut registry, &first);
+        registry.records.insert("registered".to_string(), own_record("registered"));
+
+        // Switching data directories adds the file's records to what we already track
+        load_into(&mut registry, &second);
+        assert!(registry.records.contains_key("registered"));
+        assert!(registry.records.contains_key("from-file"));
+        let saved: BTreeMap<String, ProcessRecord> =
+            serde_json::from_str(&std::fs::read_to_string(second.join(REGISTRY_FILE)).unwrap()).unwrap();
+        assert_eq!(saved.len(), 2);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,521 @@
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::eltor::{self, EltorMode};
+use crate::paths::PathConfig;
+use crate::{ports, processes, torrc_parser};
+
+const PROFILES_DIR: &str = "profiles";
+const PROFILE_FILE: &str = "profile.json";
+const PROFILE_TORRC: &str = "torrc";
+const PROFILE_LOG: &str = "eltor.log";
+
+/// Where to start looking for free ports for new profiles, well clear of the
+/// built-in client (18058/9992) and relay (18057/7781/9996) ports
+const SOCKS_PORT_BASE: u16 = 18100;
+const CONTROL_PORT_BASE: u16 = 9100;
+const OR_PORT_BASE: u16 = 9200;
+
+/// A named eltord setup, e.g. "home relay" or "testnet client"
+///
+/// Each profile lives in its own directory with a torrc, Tor data directory
+/// and log file, and is registered under its own process name, so profiles can
+/// run side by side with each other and with the built-in client/relay.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Profile {
+    /// Directory name and API identifier, derived from the name
+    pub id: String,
+    pub name: String,
+    /// "client", "relay" or "both"
+    pub mode: String,
+    pub cloned_from: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Ports a profile's torrc listens on
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProfilePorts {
+    pub socks_port: Option<u16>,
+    pub control_port: Option<u16>,
+    /// Only set for relay profiles
+    pub or_port: Option<u16>,
+}
+
+impl ProfilePorts {
+    fn named(&self) -> Vec<(&'static str, u16)> {
+        [
+            ("SocksPort", self.socks_port),
+            ("ControlPort", self.control_port),
+            ("ORPort", self.or_port),
+        ]
+        .into_iter()
+        .filter_map(|(key, port)| port.map(|port| (key, port)))
+        .collect()
+    }
+}
+
+/// A profile with its paths, ports and process state
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileInfo {
+    #[serde(flatten)]
+    pub profile: Profile,
+    pub ports: ProfilePorts,
+    pub torrc_path: PathBuf,
+    pub data_dir: PathBuf,
+    pub log_path: PathBuf,
+    pub process_name: String,
+    pub running: bool,
+    pub pid: Option<u32>,
+}
+
+/// Settings for a new or cloned profile; unset ports are picked automatically
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProfileRequest {
+    pub name: String,
+    /// Ignored when cloning - the clone keeps the source profile's mode
+    pub mode: Option<String>,
+    #[serde(flatten)]
+    pub ports: ProfilePorts,
+}
+
+fn profiles_root(path_config: &PathConfig) -> PathBuf {
+    path_config.data_dir.join(PROFILES_DIR)
+}
+
+/// Directory of a profile, refusing ids that could escape the profiles root
+fn profile_dir(path_config: &PathConfig, id: &str) -> Result<PathBuf, String> {
+    if id.is_empty() || id != profile_id(id) {
+        return Err(format!("Profile {} not found", id));
+    }
+    Ok(profiles_root(path_config).join(id))
+}
+
+/// Registry name of a profile's eltord process
+pub fn profile_process_name(id: &str) -> String {
+    format!("eltord-profile-{}", id)
+}
+
+/// Turn a display name into a directory-safe id: "Home Relay" -> "home-relay"
+pub fn profile_id(name: &str) -> String {
+    let mut id = String::new();
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c.to_ascii_lowercase());
+        } else if !id.is_empty() && !id.ends_with('-') {
+            id.push('-');
+        }
+    }
+    id.trim_end_matches('-').to_string()
+}
+
+fn read_profile(dir: &Path) -> Result<Profile, String> {
+    let content = fs::read_to_string(dir.join(PROFILE_FILE))
+        .map_err(|e| format!("Failed to read profile {:?}: {}", dir, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid profile file in {:?}: {}", dir, e))
+}
+
+fn write_profile(dir: &Path, profile: &Profile) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(profile).map_err(|e| e.to_string())?;
+    fs::write(dir.join(PROFILE_FILE), json).map_err(|e| format!("Failed to write profile: {}", e))
+}
+
+/// Ports configured in torrc text
+pub fn parse_profile_ports(content: &str) -> ProfilePorts {
+    let mut ports = ProfilePorts::default();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let port = torrc_parser::parse_port_from_config(value);
+        match key.to_lowercase().as_str() {
+            "socksport" => ports.socks_port = ports.socks_port.or(port),
+            "controlport" => ports.control_port = ports.control_port.or(port),
+            "orport" => ports.or_port = ports.or_port.or(port),
+            _ => {}
+        }
+    }
+    ports
+}
+
+/// Keep the listen address of a port option and swap the port
+fn with_port(value: &str, port: u16) -> String {
+    match value.rfind(':') {
+        Some(colon) => format!("{}:{}", &value[..colon], port),
+        None => port.to_string(),
+    }
+}
+
+/// Point a torrc at a profile's own data directory, logs and ports
+///
+/// Everything else - directory authorities, payment settings, lightning
+/// config, control password - is carried over untouched.
+pub fn rewrite_torrc(content: &str, tor_data_dir: &Path, ports: &ProfilePorts) -> String {
+    let tor_data_dir = tor_data_dir.to_string_lossy();
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        let trimmed = line.trim();
+        let mut parts = trimmed.split_whitespace();
+        let key = parts.next().unwrap_or_default();
+        let value = parts.next().unwrap_or_default();
+
+        let rewritten = if trimmed.starts_with('#') {
+            None
+        } else {
+            match key.to_lowercase().as_str() {
+                "datadirectory" => Some(format!("{} {}", key, tor_data_dir)),
+                // Log <severity> file <path>
+                "log" if parts.next() == Some("file") => {
+                    Some(format!("{} {} file {}/{}.log", key, value, tor_data_dir, value))
+                }
+                "socksport" => ports.socks_port.map(|port| format!("{} {}", key, with_port(value, port))),
+                "controlport" => ports.control_port.map(|port| format!("{} {}", key, with_port(value, port))),
+                "orport" => ports.or_port.map(|port| format!("{} {}", key, with_port(value, port))),
+                _ => None,
+            }
+        };
+        lines.push(rewritten.unwrap_or_else(|| line.to_string()));
+    }
+    lines.join("\n")
+}
+
+/// Ports used by the built-in torrc files and every profile except `skip`
+fn taken_ports(path_config: &PathConfig, skip: Option<&str>) -> Vec<u16> {
+    let mut taken = Vec::new();
+    for file in ["torrc", "torrc.relay"] {
+        if let Ok(content) = fs::read_to_string(path_config.get_torrc_path(Some(file))) {
+            taken.extend(parse_profile_ports(&content).named().into_iter().map(|(_, port)| port));
+        }
+    }
+    for info in list_profiles(path_config) {
+        if Some(info.profile.id.as_str()) != skip {
+            taken.extend(info.ports.named().into_iter().map(|(_, port)| port));
+        }
+    }
+    taken
+}
+
+/// First port from `base` that no torrc claims and nothing is listening on
+fn allocate_port(base: u16, taken: &mut Vec<u16>) -> Result<u16, String> {
+    for port in base..base.saturating_add(1000) {
+        if taken.contains(&port) || ports::is_port_in_use(port).unwrap_or(false) {
+            continue;
+        }
+        taken.push(port);
+        return Ok(port);
+    }
+    Err(format!("No free port found above {}", base))
+}
+
+fn profile_info(path_config: &PathConfig, profile: Profile) -> ProfileInfo {
+    let dir = profiles_root(path_config).join(&profile.id);
+    let torrc_path = dir.join(PROFILE_TORRC);
+    let ports = fs::read_to_string(&torrc_path)
+        .map(|content| parse_profile_ports(&content))
+        .unwrap_or_default();
+    let process_name = profile_process_name(&profile.id);
+    let record = processes::get_running(&process_name);
+
+    ProfileInfo {
+        ports,
+        torrc_path,
+        data_dir: dir.join("tor_data"),
+        log_path: dir.join(PROFILE_LOG),
+        running: record.is_some(),
+        pid: record.map(|r| r.pid),
+        process_name,
+        profile,
+    }
+}
+
+/// All profiles, sorted by id
+pub fn list_profiles(path_config: &PathConfig) -> Vec<ProfileInfo> {
+    let Ok(entries) = fs::read_dir(profiles_root(path_config)) else {
+        return Vec::new();
+    };
+    let mut profiles: Vec<ProfileInfo> = entries
+        .flatten()
+        .filter_map(|entry| match read_profile(&entry.path()) {
+            Ok(profile) => Some(profile_info(path_config, profile)),
+            Err(e) => {
+                warn!("⚠️ Skipping profile directory: {}", e);
+                None
+            }
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.profile.id.cmp(&b.profile.id));
+    profiles
+}
+
+pub fn get_profile(path_config: &PathConfig, id: &str) -> Result<ProfileInfo, String> {
+    let profile = read_profile(&profile_dir(path_config, id)?).map_err(|_| format!("Profile {} not found", id))?;
+    Ok(profile_info(path_config, profile))
+}
+
+/// Write a new profile directory from a source torrc
+fn write_new_profile(
+    path_config: &PathConfig,
+    request: &ProfileRequest,
+    mode: &EltorMode,
+    source_torrc: &Path,
+    cloned_from: Option<String>,
+) -> Result<ProfileInfo, String> {
+    let id = profile_id(&request.name);
+    if id.is_empty() {
+        return Err("Profile name must contain at least one letter or digit".to_string());
+    }
+    let dir = profile_dir(path_config, &id)?;
+    if dir.exists() {
+        return Err(format!("Profile {} already exists", id));
+    }
+
+    let source = fs::read_to_string(source_torrc)
+        .map_err(|e| format!("Failed to read torrc {:?}: {}", source_torrc, e))?;
+    let source_ports = parse_profile_ports(&source);
+
+    let mut taken = taken_ports(path_config, None);
+    let mut pick = |requested: Option<u16>, base: u16| match requested {
+        Some(port) => Ok(Some(port)),
+        None => allocate_port(base, &mut taken).map(Some),
+    };
+    let ports = ProfilePorts {
+        socks_port: pick(request.ports.socks_port, SOCKS_PORT_BASE)?,
+        control_port: pick(request.ports.control_port, CONTROL_PORT_BASE)?,
+        // Client torrcs have no ORPort to move
+        or_port: match source_ports.or_port {
+            Some(_) => pick(request.ports.or_port, OR_PORT_BASE)?,
+            None => None,
+        },
+    };
+
+    let tor_data_dir = dir.join("tor_data");
+    fs::create_dir_all(&tor_data_dir).map_err(|e| format!("Failed to create profile directory: {}", e))?;
+    let profile = Profile {
+        id: id.clone(),
+        name: request.name.trim().to_string(),
+        mode: mode.to_string().to_string(),
+        cloned_from,
+        created_at: Utc::now(),
+    };
+    let written = fs::write(dir.join(PROFILE_TORRC), rewrite_torrc(&source, &tor_data_dir, &ports))
+        .map_err(|e| format!("Failed to write profile torrc: {}", e))
+        .and_then(|_| write_profile(&dir, &profile));
+    if let Err(e) = written {
+        let _ = fs::remove_dir_all(&dir);
+        return Err(e);
+    }
+
+    info!("🗂️ Created profile {} ({} mode) in {:?}", id, profile.mode, dir);
+    Ok(profile_info(path_config, profile))
+}
+
+/// Create a profile from the built-in torrc for its mode
+pub fn create_profile(path_config: &PathConfig, request: &ProfileRequest) -> Result<ProfileInfo, String> {
+    let mode = EltorMode::from_str(request.mode.as_deref().unwrap_or("client"))?;
+    path_config.ensure_torrc_files()?;
+    let source = path_config.get_torrc_path(Some(mode.get_torrc_file()));
+    write_new_profile(path_config, request, &mode, &source, None)
+}
+
+/// Copy a profile's torrc (lightning config and all) into a new profile
+pub fn clone_profile(path_config: &PathConfig, source_id: &str, request: &ProfileRequest) -> Result<ProfileInfo, String> {
+    let source = get_profile(path_config, source_id)?;
+    let mode = EltorMode::from_str(&source.profile.mode)?;
+    write_new_profile(path_config, request, &mode, &source.torrc_path, Some(source_id.to_string()))
+}
+
+/// Delete a stopped profile and its data directory
+pub fn delete_profile(path_config: &PathConfig, id: &str) -> Result<(), String> {
+    let info = get_profile(path_config, id)?;
+    if info.running {
+        return Err(format!("Profile {} is running, stop it first", id));
+    }
+    fs::remove_dir_all(profile_dir(path_config, id)?).map_err(|e| format!("Failed to delete profile {}: {}", id, e))?;
+    info!("🗑️ Deleted profile {}", id);
+    Ok(())
+}
+
+/// Ports of a profile that something else is already using
+fn port_conflicts(path_config: &PathConfig, info: &ProfileInfo) -> Vec<String> {
+    let mut owners: HashMap<u16, String> = HashMap::new();
+    for (mode, file) in [(EltorMode::Client, "torrc"), (EltorMode::Relay, "torrc.relay")] {
+        if !processes::is_running(eltor::eltord_process_name(&mode)) {
+            continue;
+        }
+        if let Ok(content) = fs::read_to_string(path_config.get_torrc_path(Some(file))) {
+            for (_, port) in parse_profile_ports(&content).named() {
+                owners.insert(port, format!("eltord {}", mode));
+            }
+        }
+    }
+    for other in list_profiles(path_config) {
+        if other.running && other.profile.id != info.profile.id {
+            for (_, port) in other.ports.named() {
+                owners.insert(port, format!("profile {}", other.profile.id));
+            }
+        }
+    }
+
+    info.ports
+        .named()
+        .into_iter()
+        .filter_map(|(key, port)| match owners.get(&port) {
+            Some(owner) => Some(format!("{} {} is used by {}", key, port, owner)),
+            None if ports::is_port_in_use(port).unwrap_or(false) => Some(format!("{} {} is already in use", key, port)),
+            None => None,
+        })
+        .collect()
+}
+
+/// Start eltord for a profile
+///
+/// Refuses to start when any of the profile's ports collide with another
+/// running profile, the built-in client/relay or anything else listening.
+pub fn start_profile(path_config: &PathConfig, id: &str, enable_logging: bool) -> Result<u32, String> {
+    let info = get_profile(path_config, id)?;
+    if let Some(pid) = info.pid {
+        return Err(format!("Profile {} is already running (PID: {})", id, pid));
+    }
+    let conflicts = port_conflicts(path_config, &info);
+    if !conflicts.is_empty() {
+        return Err(format!("Can't start profile {}: {}", id, conflicts.join(", ")));
+    }
+
+    let mode = EltorMode::from_str(&info.profile.mode)?;
+    let eltord_path = path_config.bin_dir.join("eltord");
+    if !eltord_path.exists() {
+        return Err(format!("eltord binary not found at {:?}", eltord_path));
+    }
+    fs::create_dir_all(&info.data_dir).map_err(|e| format!("Failed to create profile data directory: {}", e))?;
+
+    let mut cmd = Command::new(&eltord_path);
+    cmd.arg(mode.to_string())
+        .arg("-f")
+        .arg(&info.torrc_path)
+        .arg("-p")
+        .arg(eltor::get_tor_control_password(&mode));
+    if enable_logging {
+        cmd.arg("-l").arg(&info.log_path).arg("-k");
+    }
+    cmd.current_dir(&path_config.bin_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Failed to spawn eltord for profile {}: {}", id, e))?;
+    let pid = child.id();
+    processes::attach(path_config);
+    if let Err(e) = processes::register(&info.process_name, pid, Some(mode.to_string())) {
+        let _ = child.try_wait();
+        return Err(e);
+    }
+
+    // Reap the child so it doesn't linger as a zombie, then free its registry entry
+    let process_name = info.process_name.clone();
+    std::thread::spawn(move || {
+        if let Ok(status) = child.wait() {
+            info!("🧹 Profile eltord {} exited with status: {}", process_name, status);
+        }
+        processes::unregister_pid(&process_name, pid);
+    });
+
+    info!("✅ Started profile {} ({} mode) with PID: {}", id, mode, pid);
+    Ok(pid)
+}
+
+/// Stop a profile's eltord
+pub fn stop_profile(id: &str) -> Result<String, String> {
+    match processes::terminate(&profile_process_name(id))? {
+        Some(record) => Ok(format!("Profile {} stopped (PID: {})", id, record.pid)),
+        None => Err(format!("Profile {} is not running", id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TORRC: &str = "\
+# Logging
+Log notice file /data/tor_data/client/notice.log
+DataDirectory /data/tor_data/client
+SocksPort 0.0.0.0:18058
+ControlPort 9992
+HashedControlPassword 16:ABC
+PaymentLightningNodeConfig type=phoenixd url=http://localhost:9740 password=pw default=true";
+
+    #[test]
+    fn test_profile_id() {
+        assert_eq!(profile_id("Home Relay"), "home-relay");
+        assert_eq!(profile_id("  testnet client #2 "), "testnet-client-2");
+        assert_eq!(profile_id("!!!"), "");
+    }
+
+    #[test]
+    fn test_parse_profile_ports() {
+        let ports = parse_profile_ports(TORRC);
+        assert_eq!(ports.socks_port, Some(18058));
+        assert_eq!(ports.control_port, Some(9992));
+        assert_eq!(ports.or_port, None);
+    }
+
+    #[test]
+    fn test_rewrite_torrc() {
+        let ports = ProfilePorts {
+            socks_port: Some(18100),
+            control_port: Some(9100),
+            or_port: None,
+        };
+        let rewritten = rewrite_torrc(TORRC, Path::new("/profiles/test/tor_data"), &ports);
+
+        assert!(rewritten.contains("Log notice file /profiles/test/tor_data/notice.log"));
+        assert!(rewritten.contains("DataDirectory /profiles/test/tor_data\n"));
+        assert!(rewritten.contains("SocksPort 0.0.0.0:18100"));
+        assert!(rewritten.contains("ControlPort 9100"));
+        // Lightning config and password carry over
+        assert!(rewritten.contains("HashedControlPassword 16:ABC"));
+        assert!(rewritten.contains("PaymentLightningNodeConfig type=phoenixd"));
+        assert_eq!(parse_profile_ports(&rewritten), ports);
+    }
+
+    #[test]
+    fn test_delete_profile_rejects_traversal() {
+        let root = std::env::temp_dir().join(format!("eltor-profiles-test-{}", std::process::id()));
+        let path_config = PathConfig {
+            bin_dir: root.join("bin"),
+            data_dir: root.join("data"),
+            app_data_dir: None,
+        };
+        // A profile-looking directory next to (not inside) the profiles root
+        let outside = path_config.data_dir.join("x");
+        fs::create_dir_all(&outside).unwrap();
+        fs::create_dir_all(profiles_root(&path_config)).unwrap();
+        let profile = Profile {
+            id: "x".to_string(),
+            name: "x".to_string(),
+            mode: "client".to_string(),
+            cloned_from: None,
+            created_at: Utc::now(),
+        };
+        write_profile(&outside, &profile).unwrap();
+
+        for id in ["../x", "..", "", "a/b"] {
+            let err = delete_profile(&path_config, id).unwrap_err();
+            assert!(err.ends_with("not found"), "{}: {}", id, err);
+        }
+        assert!(outside.join(PROFILE_FILE).exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
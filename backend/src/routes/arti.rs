@@ -0,0 +1,45 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::arti;
+use crate::arti_config::{self, ArtiConfigInfo, ArtiSettings};
+use crate::state::AppState;
+
+/// Managed Arti settings and where Arti keeps its config, state, cache and log
+pub async fn get_arti_config(State(state): State<AppState>) -> ResponseJson<ArtiConfigInfo> {
+    ResponseJson(arti_config::get_arti_config(&state.path_config))
+}
+
+/// Save Arti settings; a running Arti is restarted to apply them
+pub async fn update_arti_config(
+    State(state): State<AppState>,
+    Json(settings): Json<ArtiSettings>,
+) -> Result<ResponseJson<ArtiConfigInfo>, (StatusCode, String)> {
+    settings.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    arti::apply_arti_settings(&state.path_config, settings)
+        .await
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Delete Arti's state and cache so it bootstraps from scratch
+pub async fn reset_arti_storage(
+    State(state): State<AppState>,
+) -> Result<ResponseJson<ArtiConfigInfo>, (StatusCode, String)> {
+    arti::reset_arti_storage(&state.path_config)
+        .await
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Create Arti config routes
+pub fn create_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/arti/config", get(get_arti_config).put(update_arti_config))
+        .route("/api/arti/reset", post(reset_arti_storage))
+}
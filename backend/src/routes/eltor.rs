@@ -13,8 +13,14 @@ use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
 use std::io::SeekFrom;
 use serde::{Deserialize, Serialize};
 
-use crate::eltor::activate_eltord_process;
-use crate::state::{AppState, EltordStatusResponse, MessageResponse};
+use crate::activation::{self, ActivationJob};
+use crate::bandwidth::{self, BandwidthHistory, BandwidthStatus};
+use crate::bootstrap::{self, BootstrapStatus};
+use crate::circuits::{self, Circuit};
+use crate::identity::{self, NewIdentityResult};
+use crate::eltor::EltorMode;
+use crate::live_config::{self, ApplyOutcome};
+use crate::state::{AppState, EltordStatusResponse, MessageResponse, StatusResponse};
 use crate::torrc_parser::update_torrc_config_line;
 
 #[derive(Deserialize)]
@@ -26,6 +32,8 @@ pub struct PaymentRateRequest {
 pub struct PaymentRateResponse {
     pub message: String,
     pub rate_msats: u64,
+    /// Whether the running relay picked up the new rate or needs a restart
+    pub applied: ApplyOutcome,
 }
 
 #[derive(Deserialize)]
@@ -34,24 +42,78 @@ pub struct ActivateParams {
     enable_logging: bool,
 }
 
+/// Start an activation job - poll `/api/eltord/activation/:id` or stream it to follow along
 #[axum::debug_handler(state = AppState)]
 pub async fn activate_eltord_route(
     axum::extract::Path(mode): axum::extract::Path<String>,
     axum::extract::Query(params): axum::extract::Query<ActivateParams>,
-) -> ResponseJson<MessageResponse> {
-    
-    let mode_clone = mode.clone();
-    let enable_logging = params.enable_logging;
-    
-    // **Important** Spawn on blocking thread pool to completely isolate from tokio runtime
-    // This prevents any async code in activate_eltord_process from interfering
-    tokio::task::spawn_blocking(move || {
-        activate_eltord_process(mode_clone, enable_logging);
-    });
+) -> Result<ResponseJson<ActivationJob>, (axum::http::StatusCode, String)> {
+    // The job spawns eltord on its own thread to completely isolate it from the tokio runtime
+    activation::start_activation(&mode, params.enable_logging)
+        .map(ResponseJson)
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))
+}
 
-    ResponseJson(MessageResponse {
-        message: format!("{} activation started", mode),
-    })
+/// All known activation jobs, newest first
+pub async fn list_activation_jobs() -> ResponseJson<Vec<ActivationJob>> {
+    ResponseJson(activation::list_activation_jobs())
+}
+
+/// Current state of one activation job
+pub async fn get_activation_job(
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<ResponseJson<ActivationJob>, (axum::http::StatusCode, String)> {
+    activation::get_activation_job(&id)
+        .map(ResponseJson)
+        .ok_or((axum::http::StatusCode::NOT_FOUND, format!("Unknown activation job: {}", id)))
+}
+
+/// SSE stream of one activation job's state changes, ends once it is Ready or Failed
+pub async fn stream_activation_job(
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // Subscribe before reading the current state so no transition is missed
+    let mut receiver = activation::subscribe_activation();
+
+    let stream = async_stream::stream! {
+        match activation::get_activation_job(&id) {
+            Some(job) => {
+                let json = serde_json::to_string(&job).unwrap_or_default();
+                yield Ok(Event::default().data(json).event("activation"));
+                if job.state.is_finished() {
+                    return;
+                }
+            }
+            None => {
+                yield Ok(Event::default().data("{\"error\":\"unknown_job\"}").event("error"));
+                return;
+            }
+        }
+
+        loop {
+            match receiver.recv().await {
+                Ok(job) => {
+                    if job.id != id {
+                        continue;
+                    }
+                    let finished = job.state.is_finished();
+                    let json = serde_json::to_string(&job).unwrap_or_default();
+                    yield Ok(Event::default().data(json).event("activation"));
+                    if finished {
+                        break;
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    )
 }
 
 #[axum::debug_handler(state = AppState)]
@@ -71,8 +133,8 @@ pub async fn deactivate_eltord_route(
 pub async fn get_eltord_status(
     AxumState(state): AxumState<AppState>,
 ) -> ResponseJson<EltordStatusResponse> {
-    // Use PID file checking instead of manager
-    let status = crate::eltor::get_eltord_status_from_pid_files(&state.path_config).await;
+    // Use the process registry instead of manager
+    let status = crate::eltor::get_eltord_status_from_registry(&state.path_config).await;
     
     ResponseJson(EltordStatusResponse {
         running: status.running,
@@ -234,6 +296,182 @@ pub async fn get_eltord_logs(
     ResponseJson(LogsResponse { logs })
 }
 
+/// Bootstrap progress for every mode being tracked
+pub async fn get_bootstrap_status_all() -> ResponseJson<Vec<BootstrapStatus>> {
+    ResponseJson(bootstrap::get_all_bootstrap_status())
+}
+
+/// Bootstrap progress for a single mode
+pub async fn get_bootstrap_status(
+    axum::extract::Path(mode): axum::extract::Path<String>,
+) -> Result<ResponseJson<BootstrapStatus>, (axum::http::StatusCode, String)> {
+    bootstrap::get_bootstrap_status(&mode)
+        .map(ResponseJson)
+        .ok_or((
+            axum::http::StatusCode::NOT_FOUND,
+            format!("No bootstrap status for {} - is eltord activated?", mode),
+        ))
+}
+
+/// Stream bootstrap progress for a mode via SSE
+///
+/// Sends the current status first (if any), then every change.
+pub async fn stream_bootstrap_status(
+    axum::extract::Path(mode): axum::extract::Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut receiver = bootstrap::subscribe_bootstrap();
+
+    let stream = async_stream::stream! {
+        if let Some(status) = bootstrap::get_bootstrap_status(&mode) {
+            let json = serde_json::to_string(&status).unwrap_or_default();
+            yield Ok(Event::default().data(json).event("bootstrap"));
+        }
+
+        loop {
+            match receiver.recv().await {
+                Ok(status) => {
+                    if status.mode != mode {
+                        continue;
+                    }
+                    let json = serde_json::to_string(&status).unwrap_or_default();
+                    yield Ok(Event::default().data(json).event("bootstrap"));
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    )
+}
+
+/// Current bandwidth rates for every tracked mode
+pub async fn get_bandwidth_all() -> ResponseJson<Vec<BandwidthStatus>> {
+    ResponseJson(bandwidth::get_all_bandwidth())
+}
+
+/// Per-second and per-minute bandwidth history for a mode
+pub async fn get_bandwidth_history(
+    axum::extract::Path(mode): axum::extract::Path<String>,
+) -> Result<ResponseJson<BandwidthHistory>, (axum::http::StatusCode, String)> {
+    bandwidth::get_bandwidth_history(&mode)
+        .map(ResponseJson)
+        .ok_or_else(|| (
+            axum::http::StatusCode::NOT_FOUND,
+            format!("No bandwidth data for {} - is eltord activated?", mode),
+        ))
+}
+
+/// Stream per-second bandwidth rates for a mode via SSE
+pub async fn stream_bandwidth(
+    axum::extract::Path(mode): axum::extract::Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut receiver = bandwidth::subscribe_bandwidth();
+
+    let stream = async_stream::stream! {
+        if let Some(status) = bandwidth::get_bandwidth(&mode) {
+            let json = serde_json::to_string(&status).unwrap_or_default();
+            yield Ok(Event::default().data(json).event("bandwidth"));
+        }
+
+        loop {
+            match receiver.recv().await {
+                Ok(status) => {
+                    if status.mode != mode {
+                        continue;
+                    }
+                    let json = serde_json::to_string(&status).unwrap_or_default();
+                    yield Ok(Event::default().data(json).event("bandwidth"));
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct CircuitsQuery {
+    #[serde(default = "default_circuits_mode")]
+    mode: String,
+}
+
+fn default_circuits_mode() -> String {
+    "client".to_string()
+}
+
+#[derive(Serialize)]
+pub struct CircuitsResponse {
+    pub mode: String,
+    pub circuits: Vec<Circuit>,
+}
+
+/// List built circuits (with per-hop geolocation) for a mode, client by default
+pub async fn get_circuits(
+    AxumState(state): AxumState<AppState>,
+    axum::extract::Query(query): axum::extract::Query<CircuitsQuery>,
+) -> Result<ResponseJson<CircuitsResponse>, (axum::http::StatusCode, String)> {
+    let mode = EltorMode::from_str(&query.mode)
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+
+    let circuits = circuits::get_circuits(&mode, &state.path_config)
+        .await
+        .map_err(|e| (axum::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+
+    Ok(ResponseJson(CircuitsResponse {
+        mode: mode.to_string().to_string(),
+        circuits,
+    }))
+}
+
+/// Send NEWNYM to the client so new connections use fresh circuits
+pub async fn new_identity(
+    AxumState(state): AxumState<AppState>,
+) -> Result<ResponseJson<NewIdentityResult>, (axum::http::StatusCode, String)> {
+    let result = identity::new_identity(&state.path_config)
+        .await
+        .map_err(|e| (axum::http::StatusCode::SERVICE_UNAVAILABLE, e))?;
+    Ok(ResponseJson(result))
+}
+
+/// Whether Tor is usable for a mode, plus the circuit currently in use
+pub async fn get_tor_status(
+    AxumState(state): AxumState<AppState>,
+    axum::extract::Path(mode): axum::extract::Path<String>,
+) -> Result<ResponseJson<StatusResponse>, (axum::http::StatusCode, String)> {
+    let mode = EltorMode::from_str(&mode)
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+
+    let connected = bootstrap::get_bootstrap_status(mode.to_string())
+        .map(|status| status.is_ready())
+        .unwrap_or(false);
+
+    // Newest general-purpose circuit is the one new streams will use
+    let circuit = if connected {
+        circuits::get_circuits(&mode, &state.path_config)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|c| c.purpose.as_deref().unwrap_or("GENERAL") == "GENERAL")
+            .max_by_key(|c| c.id)
+            .map(|c| c.describe())
+    } else {
+        None
+    };
+
+    Ok(ResponseJson(StatusResponse { connected, circuit }))
+}
+
 #[axum::debug_handler(state = AppState)]
 pub async fn update_payment_rate(
     AxumState(state): AxumState<AppState>,
@@ -272,10 +510,19 @@ pub async fn update_payment_rate(
     )
     .await
     .map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    // Hot-apply to a running relay so existing circuits survive the price change
+    let applied = live_config::apply_torrc_changes(&EltorMode::Relay, &state.path_config, &["PaymentRateMsats"]).await;
     
     Ok(ResponseJson(PaymentRateResponse {
-        message: format!("Payment rate updated to {} msats/min ({} sats/min)", rate_msats, request.rate_sats_per_min),
+        message: format!(
+            "Payment rate updated to {} msats/min ({} sats/min){}",
+            rate_msats,
+            request.rate_sats_per_min,
+            live_config::restart_note(std::slice::from_ref(&applied))
+        ),
         rate_msats,
+        applied,
     }))
 }
 
@@ -283,6 +530,9 @@ pub async fn update_payment_rate(
 pub fn create_routes() -> Router<AppState> {
     Router::new()
         .route("/api/eltord/activate/:mode", post(activate_eltord_route))
+        .route("/api/eltord/activation", get(list_activation_jobs))
+        .route("/api/eltord/activation/:id", get(get_activation_job))
+        .route("/api/eltord/activation/:id/stream", get(stream_activation_job))
         .route(
             "/api/eltord/deactivate/:mode",
             post(deactivate_eltord_route),
@@ -292,4 +542,13 @@ pub fn create_routes() -> Router<AppState> {
         .route("/api/eltord/logs/:mode", get(get_eltord_logs))           // GET recent logs
         .route("/api/eltord/logs/stream/:mode", get(stream_eltord_logs)) // SSE stream
         .route("/api/eltord/relay/payment-rate", post(update_payment_rate))
+        .route("/api/eltord/bootstrap", get(get_bootstrap_status_all))
+        .route("/api/eltord/bootstrap/:mode", get(get_bootstrap_status))
+        .route("/api/eltord/bootstrap/stream/:mode", get(stream_bootstrap_status))
+        .route("/api/eltord/bandwidth", get(get_bandwidth_all))
+        .route("/api/eltord/bandwidth/:mode", get(get_bandwidth_history))
+        .route("/api/eltord/bandwidth/stream/:mode", get(stream_bandwidth))
+        .route("/api/eltord/circuits", get(get_circuits))
+        .route("/api/eltord/new-identity", post(new_identity))
+        .route("/api/eltord/tor-status/:mode", get(get_tor_status))
 }
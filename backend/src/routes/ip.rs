@@ -38,6 +38,11 @@ pub fn init_ip_database(db_path: PathBuf) -> Result<(), String> {
     Ok(())
 }
 
+/// Whether the IP2Location database was loaded
+pub fn is_ip_database_loaded() -> bool {
+    matches!(IP_DB.get(), Some(Some(_)))
+}
+
 /// Core IP lookup function that can be used by both web and Tauri
 pub fn lookup_ip_location(ip: &str) -> Result<IpLocationResponse, String> {
     let ip_addr: IpAddr = ip.parse()
@@ -1,5 +1,11 @@
+pub mod arti;
 pub mod eltor;
 pub mod wallet;
 pub mod ip;
 pub mod debug;
-pub mod phoenix;
\ No newline at end of file
+pub mod phoenix;
+pub mod supervisor;
+pub mod processes;
+pub mod profiles;
+pub mod routing;
+pub mod socks;
\ No newline at end of file
@@ -390,15 +390,12 @@ pub fn create_routes() -> Router<AppState> {
 async fn start_phoenix_api(State(state): State<AppState>) -> Result<ResponseJson<PhoenixStartResponse>, StatusCode> {
     // Check if phoenixd is already running
     {
-        let process_guard = state.wallet_state.phoenixd_process.lock().unwrap();
-        if let Some(ref child) = *process_guard {
-            // For simplicity, we'll assume if there's a process it's still running
-            // In production you might want to check child.try_wait()
+        if let Some(record) = crate::processes::get_running(crate::wallet::PHOENIXD_SERVICE_NAME) {
             return Ok(ResponseJson(PhoenixStartResponse {
                 success: true,
                 message: "Phoenix daemon is already running".to_string(),
                 downloaded: false,
-                pid: child.id(),
+                pid: Some(record.pid),
                 url: Some(PHOENIX_DEFAULT_URL.to_string()),
                 password: None, // Don't expose existing password in API response
                 is_running: Some(true),
@@ -507,15 +504,13 @@ async fn start_phoenix_api(State(state): State<AppState>) -> Result<ResponseJson
 /// API endpoint to stop Phoenix daemon
 /// This will gracefully terminate the phoenixd process if it's running
 async fn stop_phoenix_api(State(state): State<AppState>) -> Result<ResponseJson<PhoenixStopResponse>, StatusCode> {
-    // Check if phoenixd is currently running and take ownership of the process
-    let child_process = {
-        let mut process_guard = state.wallet_state.phoenixd_process.lock().unwrap();
-        process_guard.take()
-    };
-    
-    match child_process {
-        Some(mut child) => {
-            let pid = child.id();
+    // Intentional stop - make sure the supervisor doesn't bring it back
+    crate::supervisor::unwatch(crate::wallet::PHOENIXD_SERVICE_NAME);
+
+    // Only signals the PID if it is still the phoenixd we started
+    match crate::processes::get_running(crate::wallet::PHOENIXD_SERVICE_NAME) {
+        Some(record) => {
+            let pid = Some(record.pid);
             
             info!("🔥 Stopping Phoenix daemon (PID: {:?})...", pid);
             state.add_log(LogEntry {
@@ -526,52 +521,23 @@ async fn stop_phoenix_api(State(state): State<AppState>) -> Result<ResponseJson<
                 mode: None,
             });
             
-            // Try to gracefully terminate the process
-            match child.kill().await {
+            match crate::processes::terminate(crate::wallet::PHOENIXD_SERVICE_NAME) {
                 Ok(_) => {
-                    // Wait for the process to exit
-                    match child.wait().await {
-                        Ok(exit_status) => {
-                            let success_msg = format!(
-                                "Phoenix daemon stopped successfully (PID: {:?}, Exit: {})",
-                                pid, exit_status
-                            );
-                            info!("✅ {}", success_msg);
-                            state.add_log(LogEntry {
-                                timestamp: Utc::now(),
-                                level: "INFO".to_string(),
-                                message: success_msg.clone(),
-                                source: "phoenix-api".to_string(),
-                                mode: None,
-                            });
-                            
-                            Ok(ResponseJson(PhoenixStopResponse {
-                                success: true,
-                                message: success_msg,
-                                pid,
-                            }))
-                        }
-                        Err(e) => {
-                            let warning_msg = format!(
-                                "Phoenix daemon killed (PID: {:?}) but failed to wait for exit: {}",
-                                pid, e
-                            );
-                            info!("⚠️ {}", warning_msg);
-                            state.add_log(LogEntry {
-                                timestamp: Utc::now(),
-                                level: "WARN".to_string(),
-                                message: warning_msg.clone(),
-                                source: "phoenix-api".to_string(),
-                                mode: None,
-                            });
-                            
-                            Ok(ResponseJson(PhoenixStopResponse {
-                                success: true,
-                                message: warning_msg,
-                                pid,
-                            }))
-                        }
-                    }
+                    let success_msg = format!("Phoenix daemon stopped successfully (PID: {:?})", pid);
+                    info!("✅ {}", success_msg);
+                    state.add_log(LogEntry {
+                        timestamp: Utc::now(),
+                        level: "INFO".to_string(),
+                        message: success_msg.clone(),
+                        source: "phoenix-api".to_string(),
+                        mode: None,
+                    });
+                    
+                    Ok(ResponseJson(PhoenixStopResponse {
+                        success: true,
+                        message: success_msg,
+                        pid,
+                    }))
                 }
                 Err(e) => {
                     let error_msg = format!("Failed to stop Phoenix daemon (PID: {:?}): {}", pid, e);
@@ -609,14 +575,11 @@ async fn stop_phoenix_api(State(state): State<AppState>) -> Result<ResponseJson<
 }
 
 /// API endpoint to detect existing Phoenix configuration
-async fn detect_phoenix_config_api(State(state): State<AppState>) -> Result<ResponseJson<PhoenixStartResponse>, StatusCode> {
+async fn detect_phoenix_config_api(State(_state): State<AppState>) -> Result<ResponseJson<PhoenixStartResponse>, StatusCode> {
     info!("🔍 Attempting to detect existing Phoenix configuration...");
     
-    // Check if Phoenix process is running in our state
-    let is_running = {
-        let process_guard = state.wallet_state.phoenixd_process.lock().unwrap();
-        process_guard.is_some()
-    };
+    // Check if Phoenix process is running in our process registry
+    let is_running = crate::processes::is_running(crate::wallet::PHOENIXD_SERVICE_NAME);
     
     match get_existing_phoenix_config().await {
         Ok((url, password)) => {
@@ -675,8 +638,6 @@ async fn start_phoenixd_process(state: &AppState, path_config: &PathConfig) -> R
         .spawn()
         .map_err(|e| format!("Failed to start phoenixd: {}", e))?;
     
-    let pid = child.id().ok_or("Failed to get process ID")?;
-    
     // Set up log readers for phoenixd (similar to existing wallet.rs implementation)
     if let Some(stdout) = child.stdout.take() {
         let reader = tokio::io::BufReader::new(stdout);
@@ -694,11 +655,8 @@ async fn start_phoenixd_process(state: &AppState, path_config: &PathConfig) -> R
         });
     }
     
-    // Store the phoenixd process
-    {
-        let mut process_guard = state.wallet_state.phoenixd_process.lock().unwrap();
-        *process_guard = Some(child);
-    }
+    // Register the phoenixd process and reap it when it exits
+    let pid = crate::wallet::track_phoenixd(child)?;
     
     info!("✅ Phoenix daemon started with PID: {}", pid);
     info!("⏳ Waiting for Phoenix daemon to initialize and create config file...");
@@ -0,0 +1,31 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::get,
+    Router,
+};
+
+use crate::processes::{self, ProcessInfo};
+use crate::state::AppState;
+
+/// Every managed process (eltord, arti, phoenixd, ...) with its verified state
+pub async fn get_processes() -> ResponseJson<Vec<ProcessInfo>> {
+    ResponseJson(processes::list_processes())
+}
+
+/// A single managed process by registry name, e.g. "eltord-client"
+pub async fn get_process(Path(name): Path<String>) -> Result<ResponseJson<ProcessInfo>, (StatusCode, String)> {
+    processes::list_processes()
+        .into_iter()
+        .find(|info| info.record.name == name)
+        .map(ResponseJson)
+        .ok_or((StatusCode::NOT_FOUND, format!("No process registered as {}", name)))
+}
+
+/// Create process registry routes
+pub fn create_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/processes", get(get_processes))
+        .route("/api/processes/:name", get(get_process))
+}
@@ -0,0 +1,168 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+
+use crate::profiles::{self, ProfileInfo, ProfileRequest};
+use crate::routes::wallet::{DeleteLightningConfigRequest, UpsertLightningConfigRequest};
+use crate::state::{AppState, MessageResponse};
+use crate::torrc_parser::{modify_payment_lightning_config, NodeType, Operation};
+
+#[derive(Debug, Deserialize)]
+pub struct StartProfileQuery {
+    #[serde(default)]
+    pub enable_logging: bool,
+}
+
+fn not_found_or(status: StatusCode, e: String) -> (StatusCode, String) {
+    if e.ends_with("not found") {
+        (StatusCode::NOT_FOUND, e)
+    } else {
+        (status, e)
+    }
+}
+
+/// Every profile with its ports and whether it is running
+pub async fn list_profiles(State(state): State<AppState>) -> ResponseJson<Vec<ProfileInfo>> {
+    ResponseJson(profiles::list_profiles(&state.path_config))
+}
+
+pub async fn get_profile(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<ResponseJson<ProfileInfo>, (StatusCode, String)> {
+    profiles::get_profile(&state.path_config, &id)
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::NOT_FOUND, e))
+}
+
+/// Create a profile from the built-in torrc for its mode
+pub async fn create_profile(
+    State(state): State<AppState>,
+    Json(request): Json<ProfileRequest>,
+) -> Result<ResponseJson<ProfileInfo>, (StatusCode, String)> {
+    profiles::create_profile(&state.path_config, &request)
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Create a new profile from a copy of an existing one
+pub async fn clone_profile(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<ProfileRequest>,
+) -> Result<ResponseJson<ProfileInfo>, (StatusCode, String)> {
+    profiles::clone_profile(&state.path_config, &id, &request)
+        .map(ResponseJson)
+        .map_err(|e| not_found_or(StatusCode::BAD_REQUEST, e))
+}
+
+pub async fn delete_profile(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<ResponseJson<MessageResponse>, (StatusCode, String)> {
+    profiles::delete_profile(&state.path_config, &id).map_err(|e| not_found_or(StatusCode::CONFLICT, e))?;
+    Ok(ResponseJson(MessageResponse {
+        message: format!("Profile {} deleted", id),
+    }))
+}
+
+/// Start a profile's eltord, refusing if its ports collide with anything running
+pub async fn start_profile(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<StartProfileQuery>,
+) -> Result<ResponseJson<ProfileInfo>, (StatusCode, String)> {
+    let path_config = state.path_config.clone();
+    let profile_id = id.clone();
+    // Spawning blocks on process inspection, keep it off the async workers
+    tokio::task::spawn_blocking(move || profiles::start_profile(&path_config, &profile_id, query.enable_logging))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| not_found_or(StatusCode::CONFLICT, e))?;
+
+    profiles::get_profile(&state.path_config, &id)
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+pub async fn stop_profile(Path(id): Path<String>) -> Result<ResponseJson<MessageResponse>, (StatusCode, String)> {
+    profiles::stop_profile(&id)
+        .map(|message| ResponseJson(MessageResponse { message }))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+fn restart_note(info: &ProfileInfo) -> &'static str {
+    if info.running {
+        " (restart the profile to apply)"
+    } else {
+        ""
+    }
+}
+
+/// Upsert a lightning node config in a profile's own torrc
+pub async fn upsert_profile_lightning_config(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<UpsertLightningConfigRequest>,
+) -> Result<ResponseJson<MessageResponse>, (StatusCode, String)> {
+    let info = profiles::get_profile(&state.path_config, &id).map_err(|e| (StatusCode::NOT_FOUND, e))?;
+    let node_type = NodeType::from_str(&request.node_type).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    modify_payment_lightning_config(
+        &info.torrc_path,
+        Operation::Upsert,
+        node_type,
+        Some(request.url.clone()),
+        Some(request.password.clone()),
+        request.set_as_default,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok(ResponseJson(MessageResponse {
+        message: format!(
+            "Upserted {} lightning config for {} in profile {}{}",
+            request.node_type, request.url, id, restart_note(&info)
+        ),
+    }))
+}
+
+/// Delete a lightning node config from a profile's own torrc
+pub async fn delete_profile_lightning_config(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<DeleteLightningConfigRequest>,
+) -> Result<ResponseJson<MessageResponse>, (StatusCode, String)> {
+    let info = profiles::get_profile(&state.path_config, &id).map_err(|e| (StatusCode::NOT_FOUND, e))?;
+    let node_type = NodeType::from_str(&request.node_type).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    modify_payment_lightning_config(&info.torrc_path, Operation::Delete, node_type, request.url.clone(), None, false)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok(ResponseJson(MessageResponse {
+        message: format!(
+            "Deleted {} lightning config from profile {}{}",
+            request.node_type, id, restart_note(&info)
+        ),
+    }))
+}
+
+/// Create profile management routes
+pub fn create_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/profiles", get(list_profiles).post(create_profile))
+        .route("/api/profiles/:id", get(get_profile).delete(delete_profile))
+        .route("/api/profiles/:id/clone", post(clone_profile))
+        .route("/api/profiles/:id/start", post(start_profile))
+        .route("/api/profiles/:id/stop", post(stop_profile))
+        .route(
+            "/api/profiles/:id/lightning",
+            post(upsert_profile_lightning_config).delete(delete_profile_lightning_config),
+        )
+}
@@ -0,0 +1,74 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json as ResponseJson,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::routing::{self, RouteDecision, RoutingConfig};
+use crate::socks::{self, TargetAddress};
+use crate::state::AppState;
+use crate::upstream_health::{self, UpstreamHealth};
+
+#[derive(Debug, Deserialize)]
+pub struct RouteTestRequest {
+    /// Domain name or IP address
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteTestResponse {
+    #[serde(flatten)]
+    pub decision: RouteDecision,
+    /// "socks5://host:port" (several in failover order), "direct", "blocked" or "kill switch"
+    pub upstream: String,
+}
+
+/// Current routing rules
+pub async fn get_routing_rules() -> ResponseJson<RoutingConfig> {
+    ResponseJson(routing::get_routing_config())
+}
+
+/// Replace the routing rules; new connections use them right away
+pub async fn update_routing_rules(
+    State(state): State<AppState>,
+    Json(config): Json<RoutingConfig>,
+) -> Result<ResponseJson<RoutingConfig>, (StatusCode, String)> {
+    routing::save_routing_config(&state.path_config, config)
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Dry run: where would a connection to this target go?
+pub async fn test_route(Json(request): Json<RouteTestRequest>) -> ResponseJson<RouteTestResponse> {
+    let host = request.host.trim().trim_start_matches('[').trim_end_matches(']');
+    let target = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => TargetAddress::IPv4(ip, request.port),
+        Ok(IpAddr::V6(ip)) => TargetAddress::IPv6(ip, request.port),
+        Err(_) => TargetAddress::Domain(host.to_string(), request.port),
+    };
+
+    let decision = routing::get_routing_config().decide(&target);
+    let upstream = socks::resolve_upstream(&decision.action, &socks::socks_router_config());
+    ResponseJson(RouteTestResponse {
+        decision,
+        upstream: upstream.to_string(),
+    })
+}
+
+/// Cached health of every upstream SOCKS proxy the router can use
+pub async fn get_upstream_health() -> ResponseJson<Vec<UpstreamHealth>> {
+    ResponseJson(upstream_health::get_upstream_health())
+}
+
+/// Create SOCKS routing and upstream health routes
+pub fn create_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/socks/routing", get(get_routing_rules).put(update_routing_rules))
+        .route("/api/socks/routing/test", post(test_route))
+        .route("/api/socks/upstreams", get(get_upstream_health))
+}
@@ -0,0 +1,217 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{sse::Event, IntoResponse, Json as ResponseJson, Sse},
+    routing::{get, post},
+    Json, Router,
+};
+use futures::stream::Stream;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use crate::connections::{self, ConnectionsSnapshot};
+use crate::killswitch::{self, FirewallRulesRequest, KillSwitchStatus};
+use crate::pac::{self, BrowserConfig, PacOptions};
+use crate::routing;
+use crate::socks::{self, SocksRouterConfigUpdate, SocksRouterStatus};
+use crate::state::{AppState, MessageResponse};
+use crate::transparent::{self, NftRules, TransparentRulesRequest};
+
+/// Current SOCKS router status
+pub async fn get_socks_status() -> ResponseJson<SocksRouterStatus> {
+    ResponseJson(socks::get_socks_router_status())
+}
+
+/// Start the SOCKS router (no-op if it is already running)
+pub async fn start_socks() -> Result<ResponseJson<SocksRouterStatus>, (StatusCode, String)> {
+    socks::start_socks_router()
+        .await
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Stop the SOCKS router, draining in-flight connections
+pub async fn stop_socks() -> Result<ResponseJson<SocksRouterStatus>, (StatusCode, String)> {
+    socks::stop_socks_router()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(ResponseJson(socks::get_socks_router_status()))
+}
+
+/// Restart the SOCKS router, optionally with new config values
+pub async fn restart_socks(
+    update: Option<Json<SocksRouterConfigUpdate>>,
+) -> Result<ResponseJson<SocksRouterStatus>, (StatusCode, String)> {
+    let config = update.map(|Json(update)| update.apply(socks::socks_router_config()));
+    socks::restart_socks_router(config)
+        .await
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Live and recently closed connections with traffic per destination and upstream
+pub async fn get_socks_connections() -> ResponseJson<ConnectionsSnapshot> {
+    ResponseJson(connections::get_connections())
+}
+
+/// Generate nftables rules sending a user's, cgroup's or interface's traffic to the transparent proxy
+pub async fn generate_transparent_rules(
+    Json(request): Json<TransparentRulesRequest>,
+) -> Result<ResponseJson<NftRules>, (StatusCode, String)> {
+    transparent::generate_rules(&request, &socks::socks_router_config())
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Generate and load the rules (the backend needs CAP_NET_ADMIN)
+pub async fn apply_transparent_rules(
+    Json(request): Json<TransparentRulesRequest>,
+) -> Result<ResponseJson<NftRules>, (StatusCode, String)> {
+    let rules = transparent::generate_rules(&request, &socks::socks_router_config())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    transparent::apply_rules(&rules)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(ResponseJson(rules))
+}
+
+/// Remove the transparent proxy rules
+pub async fn remove_transparent_rules() -> Result<ResponseJson<MessageResponse>, (StatusCode, String)> {
+    transparent::remove_rules()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(ResponseJson(MessageResponse {
+        message: "Transparent proxy rules removed".to_string(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KillSwitchRequest {
+    pub enabled: bool,
+}
+
+/// Kill switch setting and whether it is refusing connections right now
+pub async fn get_kill_switch() -> ResponseJson<KillSwitchStatus> {
+    ResponseJson(killswitch::get_kill_switch_status())
+}
+
+/// Turn the kill switch on or off; applies to new connections right away
+pub async fn set_kill_switch(
+    State(state): State<AppState>,
+    Json(request): Json<KillSwitchRequest>,
+) -> Result<ResponseJson<KillSwitchStatus>, (StatusCode, String)> {
+    killswitch::set_kill_switch(&state.path_config, request.enabled)
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Stream kill switch events via SSE
+///
+/// Sends the current status first, then toggles, engage/release changes and
+/// refused connections.
+pub async fn stream_kill_switch_events() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let mut receiver = killswitch::subscribe_kill_switch();
+
+    let stream = async_stream::stream! {
+        let json = serde_json::to_string(&killswitch::get_kill_switch_status()).unwrap_or_default();
+        yield Ok(Event::default().data(json).event("status"));
+
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let json = serde_json::to_string(&event).unwrap_or_default();
+                    yield Ok(Event::default().data(json).event("kill-switch"));
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    };
+
+    Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(15))
+            .text("keep-alive"),
+    )
+}
+
+/// Generate nftables rules that stop chosen users from bypassing the proxies
+pub async fn generate_firewall_rules(
+    Json(request): Json<FirewallRulesRequest>,
+) -> Result<ResponseJson<NftRules>, (StatusCode, String)> {
+    killswitch::generate_firewall_rules(&request)
+        .map(ResponseJson)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Generate and load the firewall rules (the backend needs CAP_NET_ADMIN)
+pub async fn apply_firewall_rules(
+    Json(request): Json<FirewallRulesRequest>,
+) -> Result<ResponseJson<NftRules>, (StatusCode, String)> {
+    let rules = killswitch::generate_firewall_rules(&request).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    killswitch::apply_firewall_rules(&rules)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(ResponseJson(rules))
+}
+
+/// Remove the kill switch firewall rules
+pub async fn remove_firewall_rules() -> Result<ResponseJson<MessageResponse>, (StatusCode, String)> {
+    killswitch::remove_firewall_rules()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(ResponseJson(MessageResponse {
+        message: "Kill switch firewall rules removed".to_string(),
+    }))
+}
+
+fn request_host(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::HOST).and_then(|host| host.to_str().ok())
+}
+
+/// Proxy auto-config file for the router and the current routing rules
+pub async fn get_pac_file(Query(options): Query<PacOptions>, headers: HeaderMap) -> impl IntoResponse {
+    let pac = pac::generate_pac(
+        &socks::socks_router_config(),
+        &routing::get_routing_config(),
+        killswitch::is_enabled(),
+        &options,
+        request_host(&headers),
+    );
+    ([(header::CONTENT_TYPE, "application/x-ns-proxy-autoconfig")], pac)
+}
+
+/// Proxy settings and Firefox/Chromium policies pointing at the PAC file
+pub async fn get_browser_config(Query(options): Query<PacOptions>, headers: HeaderMap) -> ResponseJson<BrowserConfig> {
+    ResponseJson(pac::browser_config(
+        &socks::socks_router_config(),
+        killswitch::is_enabled(),
+        &options,
+        request_host(&headers),
+    ))
+}
+
+/// Create SOCKS router lifecycle, connection, transparent proxy, kill switch and browser routes
+pub fn create_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/socks/status", get(get_socks_status))
+        .route("/api/socks/start", post(start_socks))
+        .route("/api/socks/stop", post(stop_socks))
+        .route("/api/socks/restart", post(restart_socks))
+        .route("/api/socks/connections", get(get_socks_connections))
+        .route(
+            "/api/socks/transparent/rules",
+            post(generate_transparent_rules).delete(remove_transparent_rules),
+        )
+        .route("/api/socks/transparent/apply", post(apply_transparent_rules))
+        .route("/api/socks/kill-switch", get(get_kill_switch).put(set_kill_switch))
+        .route("/api/socks/kill-switch/events", get(stream_kill_switch_events))
+        .route(
+            "/api/socks/kill-switch/firewall/rules",
+            post(generate_firewall_rules).delete(remove_firewall_rules),
+        )
+        .route("/api/socks/kill-switch/firewall/apply", post(apply_firewall_rules))
+        .route(pac::PAC_PATH, get(get_pac_file))
+        .route("/api/socks/browser-config", get(get_browser_config))
+}
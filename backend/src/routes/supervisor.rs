@@ -0,0 +1,42 @@
+use axum::{
+    response::{sse::Event, Json as ResponseJson, Sse},
+    routing::get,
+    Router,
+};
+use futures::stream::Stream;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+
+use crate::state::AppState;
+use crate::supervisor::{self, ServiceStatus};
+
+/// Restart counts, exit codes and state of every supervised process
+pub async fn get_supervisor_status() -> ResponseJson<Vec<ServiceStatus>> {
+    ResponseJson(supervisor::get_service_statuses())
+}
+
+/// SSE stream of crash / restart events
+pub async fn stream_supervisor_events() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = supervisor::subscribe_supervisor();
+    let stream = BroadcastStream::new(receiver).map(|result| match result {
+        Ok(event) => {
+            let json = serde_json::to_string(&event).unwrap_or_default();
+            Ok(Event::default().data(json).event("supervisor"))
+        }
+        Err(_) => Ok(Event::default().data("{\"error\":\"stream_lagged\"}")),
+    });
+
+    Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(30))
+            .text("keep-alive"),
+    )
+}
+
+/// Create supervisor routes
+pub fn create_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/supervisor/status", get(get_supervisor_status))
+        .route("/api/supervisor/events", get(stream_supervisor_events))
+}
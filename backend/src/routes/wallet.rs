@@ -199,6 +199,13 @@ async fn get_offer(
                     &response.payment_request,
                 ).await {
                     info!("⚠️ Warning: Failed to update PaymentBolt12Offer in torrc.relay: {}", e);
+                } else {
+                    // A running relay should start advertising the new offer right away
+                    crate::live_config::apply_torrc_changes(
+                        &crate::eltor::EltorMode::Relay,
+                        &state.path_config,
+                        &["PaymentBolt12Offer"],
+                    ).await;
                 }
                 
                 Ok(ResponseJson(response))
@@ -352,10 +359,13 @@ async fn upsert_lightning_config(
                 }
             }
             
+            // Push the new config to running daemons
+            let outcomes = crate::live_config::apply_lightning_config_changes(&state.path_config).await;
+            
             Ok(ResponseJson(MessageResponse {
                 message: format!(
-                    "Successfully upserted {} lightning config for {}",
-                    request.node_type, request.url
+                    "Successfully upserted {} lightning config for {}{}",
+                    request.node_type, request.url, crate::live_config::restart_note(&outcomes)
                 ),
             }))
         }
@@ -432,14 +442,18 @@ async fn delete_lightning_config(
                 }
             }
             
+            // Push the remaining config to running daemons
+            let outcomes = crate::live_config::apply_lightning_config_changes(&state.path_config).await;
+            let note = crate::live_config::restart_note(&outcomes);
+            
             let message = match request.url {
                 Some(url) => format!(
-                    "Successfully deleted {} lightning config for {}",
-                    request.node_type, url
+                    "Successfully deleted {} lightning config for {}{}",
+                    request.node_type, url, note
                 ),
                 None => format!(
-                    "Successfully deleted {} lightning config",
-                    request.node_type
+                    "Successfully deleted {} lightning config{}",
+                    request.node_type, note
                 ),
             };
             Ok(ResponseJson(MessageResponse { message }))
@@ -0,0 +1,415 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+
+use crate::paths::PathConfig;
+use crate::socks::TargetAddress;
+
+const ROUTING_FILE: &str = "routing.json";
+
+/// Where a connection matched by a rule is sent
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RouteAction {
+    /// Arti's SOCKS port (regular Tor, used for .onion by default)
+    Arti,
+    /// The eltord client, or the relay if the client isn't running (default)
+    Eltord,
+    EltordClient,
+    EltordRelay,
+    /// Another SOCKS5 proxy at "host:port"
+    Socks { address: String },
+    /// Connect straight to the destination, bypassing Tor
+    Direct,
+    /// Refuse the connection
+    Block,
+}
+
+/// One routing rule
+///
+/// Every condition that is set must match: a rule with domains and ports
+/// matches those domains on those ports. Within a condition any entry can
+/// match. A rule without conditions matches everything, so it works as a
+/// catch-all at the end of the list.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoutingRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Domain suffixes ("example.com" matches it and its subdomains) or
+    /// globs with `*` and `?` ("*.corp.*")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    /// IP addresses or CIDR ranges ("10.0.0.0/8", "2001:db8::/32"). Only
+    /// match IP targets - domains are not resolved before routing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cidrs: Vec<String>,
+    /// Ports or port ranges ("443", "8000-9000")
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<String>,
+    pub action: RouteAction,
+}
+
+/// Ordered rule list; the first matching rule wins
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RoutingConfig {
+    #[serde(default)]
+    pub rules: Vec<RoutingRule>,
+}
+
+/// Which rule (if any) a target matched and what happens to it
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RouteDecision {
+    pub target: String,
+    /// Index of the matching rule, None when the built-in routing applied
+    pub rule: Option<usize>,
+    pub rule_name: Option<String>,
+    pub action: RouteAction,
+}
+
+/// Match a host against a glob where `*` is any run of characters and `?` one character
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` swallow one more character and retry
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+fn domain_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    let host = host.trim_end_matches('.').to_lowercase();
+    if pattern.contains(['*', '?']) {
+        return glob_match(pattern.as_bytes(), host.as_bytes());
+    }
+    let suffix = pattern.trim_start_matches('.');
+    host == suffix || host.ends_with(&format!(".{}", suffix))
+}
+
+/// Parse "10.0.0.0/8", "2001:db8::/32" or a bare address
+pub fn parse_cidr(cidr: &str) -> Result<(IpAddr, u8), String> {
+    let (ip, prefix) = match cidr.split_once('/') {
+        Some((ip, prefix)) => (ip, Some(prefix)),
+        None => (cidr, None),
+    };
+    let ip: IpAddr = ip.trim().parse().map_err(|_| format!("Invalid IP address in {:?}", cidr))?;
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|prefix| *prefix <= max)
+            .ok_or_else(|| format!("Invalid prefix length in {:?}", cidr))?,
+        None => max,
+    };
+    Ok((ip, prefix))
+}
+
+fn cidr_contains(network: IpAddr, prefix: u8, ip: IpAddr) -> bool {
+    match (network, ip.to_canonical()) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Parse "443" or "8000-9000" into an inclusive range
+pub fn parse_port_range(ports: &str) -> Result<(u16, u16), String> {
+    let parse = |port: &str| port.trim().parse::<u16>().map_err(|_| format!("Invalid port {:?}", ports));
+    match ports.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse(start)?, parse(end)?);
+            if start > end {
+                return Err(format!("Invalid port range {:?}", ports));
+            }
+            Ok((start, end))
+        }
+        None => parse(ports).map(|port| (port, port)),
+    }
+}
+
+fn target_host_and_port(target: &TargetAddress) -> (Option<&str>, Option<IpAddr>, u16) {
+    match target {
+        TargetAddress::Domain(domain, port) => (Some(domain.as_str()), domain.parse().ok(), *port),
+        TargetAddress::IPv4(ip, port) => (None, Some(IpAddr::V4(*ip)), *port),
+        TargetAddress::IPv6(ip, port) => (None, Some(IpAddr::V6(*ip)), *port),
+    }
+}
+
+impl RoutingRule {
+    pub fn matches(&self, target: &TargetAddress) -> bool {
+        let (host, ip, port) = target_host_and_port(target);
+
+        let domain_ok = self.domains.is_empty()
+            || host.is_some_and(|host| self.domains.iter().any(|pattern| domain_matches(pattern, host)));
+        let cidr_ok = self.cidrs.is_empty()
+            || ip.is_some_and(|ip| {
+                self.cidrs
+                    .iter()
+                    .filter_map(|cidr| parse_cidr(cidr).ok())
+                    .any(|(network, prefix)| cidr_contains(network, prefix, ip))
+            });
+        let port_ok = self.ports.is_empty()
+            || self
+                .ports
+                .iter()
+                .filter_map(|range| parse_port_range(range).ok())
+                .any(|(start, end)| (start..=end).contains(&port));
+
+        domain_ok && cidr_ok && port_ok
+    }
+
+    fn label(&self, index: usize) -> String {
+        match &self.name {
+            Some(name) => format!("rule {} ({})", index, name),
+            None => format!("rule {}", index),
+        }
+    }
+}
+
+impl RoutingConfig {
+    /// Check every rule parses, so bad entries are rejected instead of silently never matching
+    pub fn validate(&self) -> Result<(), String> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            let label = rule.label(index);
+            if rule.domains.iter().any(|domain| domain.trim().is_empty()) {
+                return Err(format!("{}: empty domain pattern", label));
+            }
+            for cidr in &rule.cidrs {
+                parse_cidr(cidr).map_err(|e| format!("{}: {}", label, e))?;
+            }
+            for ports in &rule.ports {
+                parse_port_range(ports).map_err(|e| format!("{}: {}", label, e))?;
+            }
+            if let RouteAction::Socks { address } = &rule.action {
+                let valid = address
+                    .rsplit_once(':')
+                    .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+                if !valid {
+                    return Err(format!("{}: SOCKS upstream must be host:port, got {:?}", label, address));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Route a target: the first matching rule wins, otherwise .onion goes to
+    /// Arti and everything else to eltord
+    pub fn decide(&self, target: &TargetAddress) -> RouteDecision {
+        let matched = self.rules.iter().enumerate().find(|(_, rule)| rule.matches(target));
+        match matched {
+            Some((index, rule)) => RouteDecision {
+                target: target.to_string(),
+                rule: Some(index),
+                rule_name: rule.name.clone(),
+                action: rule.action.clone(),
+            },
+            None => RouteDecision {
+                target: target.to_string(),
+                rule: None,
+                rule_name: None,
+                action: if target.is_onion() {
+                    RouteAction::Arti
+                } else {
+                    RouteAction::Eltord
+                },
+            },
+        }
+    }
+}
+
+/// Rules the SOCKS router and HTTP proxy are currently using
+static ROUTING: OnceLock<RwLock<RoutingConfig>> = OnceLock::new();
+
+fn routing() -> &'static RwLock<RoutingConfig> {
+    ROUTING.get_or_init(|| RwLock::new(RoutingConfig::default()))
+}
+
+pub fn routing_config_path(path_config: &PathConfig) -> PathBuf {
+    path_config.data_dir.join(ROUTING_FILE)
+}
+
+/// Load the rules file into the router; a missing file means no rules
+pub fn load_routing_config(path_config: &PathConfig) -> Result<RoutingConfig, String> {
+    let path = routing_config_path(path_config);
+    let config = match fs::read_to_string(&path) {
+        Ok(content) => {
+            let config: RoutingConfig = serde_json::from_str(&content)
+                .map_err(|e| format!("Invalid routing config {:?}: {}", path, e))?;
+            config.validate().map_err(|e| format!("Invalid routing config {:?}: {}", path, e))?;
+            config
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => RoutingConfig::default(),
+        Err(e) => return Err(format!("Failed to read routing config {:?}: {}", path, e)),
+    };
+
+    info!("🧭 Loaded {} routing rule(s) from {:?}", config.rules.len(), path);
+    *routing().write().unwrap() = config.clone();
+    Ok(config)
+}
+
+/// Validate, persist and apply new rules; new connections use them immediately
+pub fn save_routing_config(path_config: &PathConfig, config: RoutingConfig) -> Result<RoutingConfig, String> {
+    config.validate()?;
+    let path = routing_config_path(path_config);
+    let json = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write routing config {:?}: {}", path, e))?;
+
+    info!("🧭 Saved {} routing rule(s) to {:?}", config.rules.len(), path);
+    *routing().write().unwrap() = config.clone();
+    Ok(config)
+}
+
+pub fn get_routing_config() -> RoutingConfig {
+    routing().read().unwrap().clone()
+}
+
+/// Route a target with the current rules
+pub fn route(target: &TargetAddress) -> RouteDecision {
+    let decision = routing().read().unwrap().decide(target);
+    if decision.action == RouteAction::Block {
+        warn!("🚫 Blocking {} (rule {:?})", decision.target, decision.rule);
+    }
+    decision
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn domain(host: &str, port: u16) -> TargetAddress {
+        TargetAddress::Domain(host.to_string(), port)
+    }
+
+    fn rule(action: RouteAction) -> RoutingRule {
+        RoutingRule {
+            name: None,
+            domains: Vec::new(),
+            cidrs: Vec::new(),
+            ports: Vec::new(),
+            action,
+        }
+    }
+
+    #[test]
+    fn test_domain_patterns() {
+        assert!(domain_matches("example.com", "example.com"));
+        assert!(domain_matches("example.com", "www.EXAMPLE.com."));
+        assert!(domain_matches(".example.com", "a.b.example.com"));
+        assert!(!domain_matches("example.com", "badexample.com"));
+
+        assert!(domain_matches("*.corp.*", "git.corp.internal"));
+        assert!(!domain_matches("*.corp.*", "corp.internal"));
+        assert!(domain_matches("cdn?.example.com", "cdn1.example.com"));
+        assert!(domain_matches("*", "anything"));
+    }
+
+    #[test]
+    fn test_cidr_and_ports() {
+        let (network, prefix) = parse_cidr("10.0.0.0/8").unwrap();
+        assert!(cidr_contains(network, prefix, "10.1.2.3".parse().unwrap()));
+        assert!(!cidr_contains(network, prefix, "11.0.0.1".parse().unwrap()));
+        // IPv4-mapped IPv6 targets still match IPv4 ranges
+        assert!(cidr_contains(network, prefix, "::ffff:10.0.0.1".parse().unwrap()));
+
+        let (network, prefix) = parse_cidr("2001:db8::/32").unwrap();
+        assert!(cidr_contains(network, prefix, "2001:db8:1::1".parse().unwrap()));
+        assert!(!cidr_contains(network, prefix, "10.0.0.1".parse().unwrap()));
+
+        assert_eq!(parse_cidr("192.168.1.1").unwrap().1, 32);
+        assert_eq!(parse_cidr("0.0.0.0/0").unwrap().1, 0);
+        assert!(parse_cidr("10.0.0.0/33").is_err());
+
+        assert_eq!(parse_port_range("443").unwrap(), (443, 443));
+        assert_eq!(parse_port_range("8000-9000").unwrap(), (8000, 9000));
+        assert!(parse_port_range("9000-8000").is_err());
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let config = RoutingConfig {
+            rules: vec![
+                RoutingRule {
+                    name: Some("lan".to_string()),
+                    cidrs: vec!["192.168.0.0/16".to_string()],
+                    ..rule(RouteAction::Direct)
+                },
+                RoutingRule {
+                    domains: vec!["ads.example".to_string()],
+                    ..rule(RouteAction::Block)
+                },
+                RoutingRule {
+                    domains: vec!["example.com".to_string()],
+                    ports: vec!["443".to_string()],
+                    ..rule(RouteAction::Arti)
+                },
+            ],
+        };
+
+        let lan = config.decide(&TargetAddress::IPv4(Ipv4Addr::new(192, 168, 1, 10), 22));
+        assert_eq!((lan.rule, lan.rule_name.as_deref(), lan.action), (Some(0), Some("lan"), RouteAction::Direct));
+        // Literal IPs sent as domain names still match CIDR rules
+        assert_eq!(config.decide(&domain("192.168.1.10", 80)).action, RouteAction::Direct);
+
+        assert_eq!(config.decide(&domain("tracker.ads.example", 443)).action, RouteAction::Block);
+        assert_eq!(config.decide(&domain("www.example.com", 443)).action, RouteAction::Arti);
+
+        // Port doesn't match, so the built-in routing applies
+        let fallback = config.decide(&domain("www.example.com", 80));
+        assert_eq!((fallback.rule, fallback.action), (None, RouteAction::Eltord));
+        assert_eq!(config.decide(&domain("abc.onion", 80)).action, RouteAction::Arti);
+        assert_eq!(
+            config.decide(&TargetAddress::IPv6(Ipv6Addr::LOCALHOST, 80)).action,
+            RouteAction::Eltord
+        );
+    }
+
+    #[test]
+    fn test_validate_and_serde() {
+        let json = r#"{"rules": [
+            {"name": "corp", "domains": ["*.corp"], "action": {"type": "socks", "address": "10.0.0.5:1080"}},
+            {"ports": ["25"], "action": {"type": "block"}},
+            {"action": {"type": "eltord_client"}}
+        ]}"#;
+        let config: RoutingConfig = serde_json::from_str(json).unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.rules[2].action, RouteAction::EltordClient);
+        // Catch-all rule matches anything
+        assert_eq!(config.decide(&domain("abc.onion", 80)).rule, Some(2));
+
+        let bad = |rule: RoutingRule| RoutingConfig { rules: vec![rule] }.validate();
+        assert!(bad(RoutingRule {
+            cidrs: vec!["not-an-ip".to_string()],
+            ..rule(RouteAction::Direct)
+        })
+        .is_err());
+        assert!(bad(rule(RouteAction::Socks {
+            address: "localhost".to_string()
+        }))
+        .is_err());
+    }
+}
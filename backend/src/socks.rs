@@ -1,21 +1,51 @@
+use chrono::{DateTime, Utc};
 use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
 
-#[derive(Clone, Debug)]
-enum SocksCommand {
+use crate::connections::TrackedConnection;
+use crate::dns::{self, OnionDnsMode};
+use crate::eltor::EltorMode;
+use crate::killswitch;
+use crate::routing::{self, RouteAction};
+use crate::upstream_health;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SocksCommand {
     Connect = 0x01,
+    /// Tor extension: resolve a hostname to an address
+    Resolve = 0xF0,
+    /// Tor extension: reverse lookup of an IP address
+    ResolvePtr = 0xF1,
 }
 
-#[derive(Clone, Debug)]
+impl SocksCommand {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(SocksCommand::Connect),
+            0xF0 => Some(SocksCommand::Resolve),
+            0xF1 => Some(SocksCommand::ResolvePtr),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum AuthMethod {
     NoAuth = 0x00,
+    UsernamePassword = 0x02,
     NoAcceptable = 0xFF,
 }
 
-#[derive(Clone, Debug)]
-enum ReplyCode {
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ReplyCode {
     Success = 0x00,
     GeneralFailure = 0x01,
     ConnectionNotAllowed = 0x02,
@@ -25,15 +55,62 @@ enum ReplyCode {
     TtlExpired = 0x06,
     CommandNotSupported = 0x07,
     AddressTypeNotSupported = 0x08,
+    /// Unassigned in RFC 1928, so clients can tell a kill-switch refusal
+    /// apart from anything Tor itself replies
+    KillSwitch = 0x09,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug)]
 enum AddressType {
     IPv4 = 0x01,
     DomainName = 0x03,
     IPv6 = 0x04,
 }
 
+/// RFC 1929 username/password pair
+#[derive(Clone, PartialEq)]
+pub struct SocksCredential {
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for SocksCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SocksCredential")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+impl SocksCredential {
+    /// Parse a comma separated "user:pass,user2:pass2" list, skipping malformed entries
+    pub fn parse_list(list: &str) -> Vec<SocksCredential> {
+        list.split(',')
+            .filter_map(|entry| {
+                let (username, password) = entry.trim().split_once(':')?;
+                if username.is_empty() || username.len() > 255 || password.len() > 255 {
+                    warn!("⚠️ Ignoring invalid SOCKS credential for user {:?}", username);
+                    return None;
+                }
+                Some(SocksCredential {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// RFC 1929 sub-negotiation request: VER ULEN UNAME PLEN PASSWD
+    fn encode(&self) -> Vec<u8> {
+        let mut request = vec![0x01, self.username.len() as u8];
+        request.extend_from_slice(self.username.as_bytes());
+        request.push(self.password.len() as u8);
+        request.extend_from_slice(self.password.as_bytes());
+        request
+    }
+}
+
 /// Configuration for the SOCKS router
 #[derive(Clone, Debug)]
 pub struct SocksRouterConfig {
@@ -42,6 +119,20 @@ pub struct SocksRouterConfig {
     pub arti_socks_port: u16,
     pub eltord_client_socks_port: u16,
     pub eltord_relay_socks_port: u16,
+    /// When non-empty, clients must authenticate with one of these.
+    /// When empty, username/password is still accepted (unchecked) so apps
+    /// can pick their own credentials for stream isolation.
+    pub credentials: Vec<SocksCredential>,
+    /// Port for the optional HTTP proxy listener (disabled if None)
+    pub http_proxy_port: Option<u16>,
+    pub http_proxy_addr: Option<IpAddr>,
+    /// Port for the optional DNS resolver, UDP and TCP (disabled if None)
+    pub dns_port: Option<u16>,
+    pub dns_addr: Option<IpAddr>,
+    pub dns_onion: OnionDnsMode,
+    /// Port for the optional transparent proxy, Linux only (disabled if None)
+    pub transparent_port: Option<u16>,
+    pub transparent_addr: Option<IpAddr>,
 }
 
 impl Default for SocksRouterConfig {
@@ -52,6 +143,14 @@ impl Default for SocksRouterConfig {
             arti_socks_port: 18050,
             eltord_client_socks_port: 18058, // Client mode SOCKS port
             eltord_relay_socks_port: 18057, // Relay mode SOCKS port
+            credentials: Vec::new(),
+            http_proxy_port: None,
+            http_proxy_addr: None,
+            dns_port: None,
+            dns_addr: None,
+            dns_onion: OnionDnsMode::Block,
+            transparent_port: None,
+            transparent_addr: None,
         }
     }
 }
@@ -108,24 +207,112 @@ impl SocksRouterConfig {
         }
         
         let (router_ip, router_port) = parse_addr_port("APP_ELTOR_SOCKS_ROUTER_PORT");
-        let (_, arti_port) = parse_addr_port("APP_ARTI_SOCKS_PORT");
         let (_, eltord_client_port) = parse_addr_port("APP_ELTOR_TOR_SOCKS_PORT");
         let (_, eltord_relay_port) = parse_addr_port("APP_ELTOR_TOR_RELAY_SOCKS_PORT");
+        let (http_proxy_ip, http_proxy_port) = parse_addr_port("APP_ELTOR_HTTP_PROXY_PORT");
+        let (dns_ip, dns_port) = parse_addr_port("APP_ELTOR_DNS_PORT");
+        let (transparent_ip, transparent_port) = parse_addr_port("APP_ELTOR_TRANSPARENT_PORT");
+        let dns_onion = std::env::var("APP_ELTOR_DNS_ONION")
+            .ok()
+            .and_then(|mode| OnionDnsMode::parse(&mode))
+            .unwrap_or_default();
         
         Self {
             listen_port: router_port.unwrap_or(18048),
             listen_addr: router_ip,
-            arti_socks_port: arti_port.unwrap_or(18050),
+            // Managed Arti settings, which default to APP_ARTI_SOCKS_PORT
+            arti_socks_port: crate::arti_config::arti_socks_port(),
             eltord_client_socks_port: eltord_client_port.unwrap_or(18058),
             eltord_relay_socks_port: eltord_relay_port.unwrap_or(18057),
+            credentials: std::env::var("APP_ELTOR_SOCKS_ROUTER_CREDENTIALS")
+                .map(|list| SocksCredential::parse_list(&list))
+                .unwrap_or_default(),
+            http_proxy_port,
+            http_proxy_addr: http_proxy_ip.or(router_ip),
+            dns_port,
+            dns_addr: dns_ip.or(router_ip),
+            dns_onion,
+            transparent_port,
+            transparent_addr: transparent_ip.or(router_ip),
         }
     }
 }
 
-/// SOCKS router that routes .onion domains to Arti and other traffic to eltord
+/// How long in-flight connections get to finish when the router stops
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an upstream gets to accept the connection and answer the greeting
+///
+/// A stalled handshake means the upstream itself is stuck, so it is marked
+/// unhealthy and the next one is tried.
+const UPSTREAM_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an upstream gets to answer the request itself
+///
+/// Covers Tor building a circuit and reaching the target, so a slow answer
+/// says nothing about the upstream; like Tor's own SocksTimeout.
+const UPSTREAM_REPLY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Connection counters shared by the SOCKS and HTTP listeners
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    active: AtomicUsize,
+    total: AtomicU64,
+}
+
+impl ConnectionStats {
+    /// Count a new connection; it stays active until the guard is dropped
+    pub fn open(self: &Arc<Self>) -> ConnectionGuard {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        self.total.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self.clone())
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+}
+
+/// Marks a connection closed when dropped, including when its task is aborted
+pub struct ConnectionGuard(Arc<ConnectionStats>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Wait for in-flight connections to finish, closing whatever is left after DRAIN_TIMEOUT
+pub(crate) async fn drain_connections(mut connections: JoinSet<()>, name: &str) {
+    if connections.is_empty() {
+        return;
+    }
+    info!("⏳ Draining {} {} connection(s)...", connections.len(), name);
+    let drained = tokio::time::timeout(DRAIN_TIMEOUT, async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            "⚠️ {} {} connection(s) still open after {}s, closing them",
+            connections.len(),
+            name,
+            DRAIN_TIMEOUT.as_secs()
+        );
+        connections.shutdown().await;
+    }
+}
+
+/// SOCKS router that routes .onion domains to Arti and other traffic to eltord,
+/// unless a routing rule says otherwise
 pub struct SocksRouter {
     config: SocksRouterConfig,
     listener: Option<TcpListener>,
+    stats: Arc<ConnectionStats>,
 }
 
 impl SocksRouter {
@@ -133,6 +320,7 @@ impl SocksRouter {
         Self {
             config,
             listener: None,
+            stats: Arc::new(ConnectionStats::default()),
         }
     }
     
@@ -148,6 +336,13 @@ impl SocksRouter {
                 info!("   .onion domains -> Arti SOCKS (port {})", self.config.arti_socks_port);
                 info!("   Other domains -> eltord client SOCKS (port {}) or relay SOCKS (port {})", 
                     self.config.eltord_client_socks_port, self.config.eltord_relay_socks_port);
+                if !self.config.credentials.is_empty() {
+                    info!("   Username/password required ({} credential(s))", self.config.credentials.len());
+                }
+                let rules = routing::get_routing_config().rules.len();
+                if rules > 0 {
+                    info!("   {} routing rule(s) active", rules);
+                }
                 
                 self.listener = Some(listener);
                 Ok(())
@@ -157,238 +352,1645 @@ impl SocksRouter {
             }
         }
     }
+
+    /// Address the router is bound to, once started
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref()?.local_addr().ok()
+    }
+
+    pub fn stats(&self) -> Arc<ConnectionStats> {
+        self.stats.clone()
+    }
     
-    /// Run the SOCKS router server
-    pub async fn run(&mut self) -> Result<(), String> {
+    /// Run the SOCKS router server until `shutdown` fires, then drain
+    /// in-flight connections
+    pub async fn run(&mut self, mut shutdown: watch::Receiver<bool>) -> Result<(), String> {
         let listener = self.listener.take().ok_or("SOCKS router not started")?;
+        let mut connections = JoinSet::new();
         
         loop {
-            match listener.accept().await {
-                Ok((client_stream, client_addr)) => {
-                    let config = self.config.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = handle_socks_connection(client_stream, client_addr, config).await {
-                            warn!("⚠️ SOCKS connection error from {}: {}", client_addr, e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    error!("❌ Failed to accept SOCKS connection: {}", e);
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                }
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                // Reap finished connections so the set doesn't grow
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                accepted = listener.accept() => match accepted {
+                    Ok((client_stream, client_addr)) => {
+                        let config = self.config.clone();
+                        let guard = self.stats.open();
+                        let connection = crate::connections::track("socks", client_addr);
+                        connections.spawn(async move {
+                            let _guard = guard;
+                            match handle_socks_connection(client_stream, client_addr, config, &connection).await {
+                                Ok(()) => connection.close("closed"),
+                                Err(e) => {
+                                    warn!("⚠️ SOCKS connection error from {}: {}", client_addr, e);
+                                    connection.close(e);
+                                }
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("❌ Failed to accept SOCKS connection: {}", e);
+                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                    }
+                },
             }
         }
+
+        // Free the port right away, then let open connections finish
+        drop(listener);
+        info!("🔀 SOCKS Router stopped accepting connections");
+        drain_connections(connections, "SOCKS").await;
+        Ok(())
     }
 }
 
-/// Handle a single SOCKS5 connection using the cleaner approach
+/// Result of parsing one message off the front of a buffer: `Ok(None)` means
+/// more bytes are needed, `Ok(Some((message, used)))` is a complete message
+/// that took `used` bytes
+type Parsed<T, E = String> = Result<Option<(T, usize)>, E>;
+
+/// Largest handshake message we buffer (an RFC 1929 request is at most 513 bytes)
+const MAX_FRAME_SIZE: usize = 1024;
+
+/// Buffers client bytes during the handshake
+///
+/// Clients may split a message across reads, or pipeline the next message
+/// (and even their first application data) without waiting for our reply, so
+/// each message is parsed off the front of the buffer and the rest is kept.
+#[derive(Default)]
+struct FrameReader {
+    buffer: Vec<u8>,
+}
+
+impl FrameReader {
+    fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    fn consume(&mut self, used: usize) {
+        self.buffer.drain(..used);
+    }
+
+    /// Read more bytes from the client
+    async fn fill(&mut self, stream: &mut TcpStream) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.buffer.len() >= MAX_FRAME_SIZE {
+            return Err("SOCKS message too long".into());
+        }
+        let mut chunk = [0u8; 512];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err("Client closed the connection during the SOCKS handshake".into());
+        }
+        self.buffer.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+
+    /// Read until `parse` finds a complete message
+    async fn next<T>(
+        &mut self,
+        stream: &mut TcpStream,
+        parse: impl Fn(&[u8]) -> Parsed<T>,
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        loop {
+            if let Some((message, used)) = parse(&self.buffer)? {
+                self.consume(used);
+                return Ok(message);
+            }
+            self.fill(stream).await?;
+        }
+    }
+
+    /// Bytes the client sent after the handshake, passed on once the tunnel is up
+    fn into_pending(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+/// SOCKS5 greeting: VER NMETHODS METHODS...
+fn parse_greeting(buf: &[u8]) -> Parsed<Vec<u8>> {
+    match buf.first() {
+        None => return Ok(None),
+        Some(0x05) => {}
+        Some(version) => return Err(format!("Invalid SOCKS5 greeting version: {}", version)),
+    }
+    let Some(&count) = buf.get(1) else {
+        return Ok(None);
+    };
+    if count == 0 {
+        return Err("SOCKS5 greeting offers no authentication methods".to_string());
+    }
+    let end = 2 + count as usize;
+    Ok(buf.get(2..end).map(|methods| (methods.to_vec(), end)))
+}
+
+/// RFC 1929 username/password request: VER ULEN UNAME PLEN PASSWD
+fn parse_credential(buf: &[u8]) -> Parsed<SocksCredential> {
+    match buf.first() {
+        None => return Ok(None),
+        Some(0x01) => {}
+        Some(version) => return Err(format!("Unsupported username/password auth version: {}", version)),
+    }
+    let Some(&username_len) = buf.get(1) else {
+        return Ok(None);
+    };
+    let password_at = 2 + username_len as usize;
+    let Some(&password_len) = buf.get(password_at) else {
+        return Ok(None);
+    };
+    let end = password_at + 1 + password_len as usize;
+    if buf.len() < end {
+        return Ok(None);
+    }
+
+    let text = |bytes: &[u8]| String::from_utf8(bytes.to_vec()).map_err(|_| "Username or password is not valid UTF-8".to_string());
+    let credential = SocksCredential {
+        username: text(&buf[2..password_at])?,
+        password: text(&buf[password_at + 1..end])?,
+    };
+    Ok(Some((credential, end)))
+}
+
+/// ATYP DST.ADDR DST.PORT, as found in SOCKS5 requests and replies
+fn decode_address(buf: &[u8]) -> Parsed<TargetAddress, (ReplyCode, String)> {
+    let Some(&address_type) = buf.first() else {
+        return Ok(None);
+    };
+    let (start, len) = match address_type {
+        0x01 => (1, 4),  // IPv4
+        0x04 => (1, 16), // IPv6
+        0x03 => match buf.get(1) { // Domain name
+            None => return Ok(None),
+            Some(0) => return Err((ReplyCode::GeneralFailure, "Empty domain name".to_string())),
+            Some(&len) => (2, len as usize),
+        },
+        other => {
+            return Err((ReplyCode::AddressTypeNotSupported, format!("Unsupported address type: {}", other)));
+        }
+    };
+    let end = start + len + 2;
+    if buf.len() < end {
+        return Ok(None);
+    }
+
+    let address = &buf[start..start + len];
+    let port = u16::from_be_bytes([buf[end - 2], buf[end - 1]]);
+    let target = match address_type {
+        0x01 => TargetAddress::IPv4(Ipv4Addr::new(address[0], address[1], address[2], address[3]), port),
+        0x04 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(address);
+            TargetAddress::IPv6(std::net::Ipv6Addr::from(octets), port)
+        }
+        _ => {
+            let domain = String::from_utf8(address.to_vec())
+                .map_err(|_| (ReplyCode::GeneralFailure, "Domain name is not valid UTF-8".to_string()))?;
+            TargetAddress::Domain(domain, port)
+        }
+    };
+    Ok(Some((target, end)))
+}
+
+/// SOCKS5 request: VER CMD RSV ATYP DST.ADDR DST.PORT
+///
+/// The command byte is returned raw so an unsupported command can still be
+/// answered with the right reply code.
+fn parse_request(buf: &[u8]) -> Parsed<(u8, TargetAddress), (ReplyCode, String)> {
+    match buf.first() {
+        None => return Ok(None),
+        Some(0x05) => {}
+        Some(version) => {
+            return Err((ReplyCode::GeneralFailure, format!("Invalid SOCKS5 request version: {}", version)));
+        }
+    }
+    if buf.len() < 3 {
+        return Ok(None);
+    }
+    Ok(decode_address(&buf[3..])?.map(|(target, used)| ((buf[1], target), 3 + used)))
+}
+
+/// Handle a single SOCKS connection
+///
+/// Every handshake message is framed through a [`FrameReader`], so clients
+/// that fragment or pipeline their messages are handled the same as clients
+/// that send one message per packet.
 async fn handle_socks_connection(
     mut client_stream: TcpStream,
     client_addr: SocketAddr,
     config: SocksRouterConfig,
+    connection: &TrackedConnection,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    debug!("🔌 New SOCKS5 connection from {}", client_addr);
-    
-    // Step 1: SOCKS5 greeting
-    let mut buffer = vec![0u8; 1024];
-    let n = client_stream.read(&mut buffer).await?;
-    
-    debug!("📥 Received {} bytes for greeting from {}", n, client_addr);
-    
-    if n < 2 || buffer[0] != 0x05 {
-        warn!("❌ Invalid SOCKS5 greeting from {}: {:?}", client_addr, &buffer[..n.min(10)]);
-        return Err("Invalid SOCKS5 greeting".into());
+    debug!("🔌 New SOCKS connection from {}", client_addr);
+    let mut reader = FrameReader::default();
+
+    // Peek at the version byte to tell SOCKS4 from SOCKS5
+    let version = reader
+        .next(&mut client_stream, |buf| Ok(buf.first().map(|&version| (version, 0))))
+        .await?;
+    match version {
+        0x05 => {}
+        0x04 => return handle_socks4_connection(client_stream, reader, client_addr, config, connection).await,
+        _ => {
+            warn!("❌ Invalid SOCKS greeting from {}: version {}", client_addr, version);
+            return Err("Invalid SOCKS5 greeting".into());
+        }
     }
 
-    debug!("✅ Valid SOCKS5 greeting from {}, sending auth method", client_addr);
-    // Send method selection (no auth)
-    client_stream.write_all(&[0x05, 0x00]).await?;
+    // Step 1: SOCKS5 greeting: VER NMETHODS METHODS...
+    let methods = reader.next(&mut client_stream, parse_greeting).await?;
+    debug!("📥 SOCKS5 greeting from {} offers methods {:?}", client_addr, methods);
+
+    let method = select_auth_method(&methods, &config.credentials);
+    client_stream.write_all(&[0x05, method as u8]).await?;
+    let auth = match method {
+        AuthMethod::NoAuth => None,
+        AuthMethod::UsernamePassword => {
+            let credential = reader.next(&mut client_stream, parse_credential).await?;
+            let accepted = config.credentials.is_empty() || config.credentials.contains(&credential);
+            // RFC 1929 status: 0x00 success, anything else closes the connection
+            client_stream.write_all(&[0x01, if accepted { 0x00 } else { 0x01 }]).await?;
+            if !accepted {
+                warn!("❌ SOCKS authentication failed for user {:?} from {}", credential.username, client_addr);
+                return Err("SOCKS authentication failed".into());
+            }
+            debug!("🔐 SOCKS user {:?} authenticated from {}", credential.username, client_addr);
+            Some(credential)
+        }
+        AuthMethod::NoAcceptable => {
+            warn!("❌ No acceptable SOCKS auth method from {}: {:?}", client_addr, methods);
+            return Err("No acceptable authentication method".into());
+        }
+    };
 
     // Step 2: Read SOCKS5 request
-    let n = client_stream.read(&mut buffer).await?;
-    
-    debug!("📥 Received {} bytes for request from {}", n, client_addr);
-    
-    if n < 10 || buffer[0] != 0x05 {
-        warn!("❌ Invalid SOCKS5 request from {}: {:?}", client_addr, &buffer[..n.min(20)]);
-        return Err("Invalid SOCKS5 request".into());
-    }
+    let (command, target) = loop {
+        match parse_request(reader.buffered()) {
+            Ok(Some((request, used))) => {
+                reader.consume(used);
+                break request;
+            }
+            Ok(None) => reader.fill(&mut client_stream).await?,
+            Err((code, e)) => {
+                warn!("❌ Invalid SOCKS5 request from {}: {}", client_addr, e);
+                client_stream.write_all(&socks5_reply(code)).await?;
+                return Err(e.into());
+            }
+        }
+    };
+
+    let Some(command) = SocksCommand::from_byte(command) else {
+        warn!("❌ Unsupported SOCKS command from {}: {}", client_addr, command);
+        client_stream.write_all(&socks5_reply(ReplyCode::CommandNotSupported)).await?;
+        return Err("Only CONNECT, RESOLVE and RESOLVE_PTR commands are supported".into());
+    };
+    let target = dns::unmap_synthetic(target);
+    debug!("🎯 SOCKS {:?} target from {}: {}", command, client_addr, target);
+    connection.set_target(&target);
 
-    if buffer[1] != SocksCommand::Connect as u8 {
-        warn!("❌ Unsupported SOCKS command from {}: {}", client_addr, buffer[1]);
-        // Send command not supported
-        let response = vec![0x05, 0x07, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
-        client_stream.write_all(&response).await?;
-        return Err("Only CONNECT command is supported".into());
+    let pending = reader.into_pending();
+    if !pending.is_empty() {
+        debug!("📦 {} byte(s) of early data from {}", pending.len(), client_addr);
     }
     
-    // Parse target address
-    let target = parse_target_address(&buffer[3..n])?;
-    debug!("🎯 SOCKS target from {}: {}", client_addr, target.to_string());
-    
     // Step 3: Determine which proxy to use
-    if target.is_onion() {
-        debug!("🧅 Routing .onion domain to Arti (port {}) for {}", config.arti_socks_port, client_addr);
-        handle_via_proxy(client_stream, &buffer[..n], config.arti_socks_port).await
-    } else {
-        // Check which eltord port is available (client or relay)
-        // Try to connect to client port first
-        let eltord_port = if tokio::net::TcpStream::connect(format!("127.0.0.1:{}", config.eltord_client_socks_port)).await.is_ok() {
-            debug!("🌐 Using eltord client port {} for {}", config.eltord_client_socks_port, client_addr);
-            config.eltord_client_socks_port
-        } else {
-            debug!("🌐 Using eltord relay port {} for {}", config.eltord_relay_socks_port, client_addr);
-            config.eltord_relay_socks_port
-        };
-        
-        handle_via_proxy(client_stream, &buffer[..n], eltord_port).await
+    let upstream = route_target(&target, &config, client_addr);
+    match upstream {
+        Upstream::Socks(ref candidates) => {
+            let request = encode_socks5_request(command, &target)?;
+            let auth = upstream_credential(auth.as_ref(), &upstream, &config, &target, client_addr);
+            handle_via_proxy(client_stream, &request, candidates, auth.as_ref(), command, &pending, connection).await
+        }
+        Upstream::Direct => handle_direct(client_stream, &target, command, &pending, connection).await,
+        Upstream::Block => {
+            connection.set_upstream(Upstream::Block);
+            client_stream.write_all(&socks5_reply(ReplyCode::ConnectionNotAllowed)).await?;
+            Err(format!("{} is blocked by a routing rule", target).into())
+        }
+        Upstream::KillSwitch => {
+            connection.set_upstream(Upstream::KillSwitch);
+            client_stream.write_all(&socks5_reply(ReplyCode::KillSwitch)).await?;
+            Err(format!("Kill switch refused {}: El Tor client is not ready", target).into())
+        }
     }
 }
 
-/// Forward connection through a SOCKS5 proxy
-async fn handle_via_proxy(
+/// SOCKS4 reply codes
+const SOCKS4_GRANTED: u8 = 0x5A;
+const SOCKS4_REJECTED: u8 = 0x5B;
+
+fn socks4_reply(code: u8) -> [u8; 8] {
+    [0x00, code, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+}
+
+/// Position of the NUL ending a SOCKS4 field (user id or SOCKS4a hostname)
+fn find_nul(buf: &[u8], start: usize) -> Result<Option<usize>, String> {
+    let field = buf.get(start..).unwrap_or_default();
+    match field.iter().position(|&byte| byte == 0x00) {
+        Some(len) if len <= 255 => Ok(Some(start + len)),
+        None if field.len() <= 255 => Ok(None),
+        _ => Err("SOCKS4 field too long".to_string()),
+    }
+}
+
+/// SOCKS4a marks a hostname request with the invalid address 0.0.0.x, x != 0
+fn is_socks4a_address(ip: Ipv4Addr) -> bool {
+    let [a, b, c, d] = ip.octets();
+    a == 0 && b == 0 && c == 0 && d != 0
+}
+
+/// SOCKS4/4a request: VER CD DSTPORT DSTIP USERID\0 [HOSTNAME\0]
+fn parse_socks4_request(buf: &[u8]) -> Parsed<(u8, TargetAddress)> {
+    match buf.first() {
+        None => return Ok(None),
+        Some(0x04) => {}
+        Some(version) => return Err(format!("Invalid SOCKS4 version: {}", version)),
+    }
+    if buf.len() < 8 {
+        return Ok(None);
+    }
+    let port = u16::from_be_bytes([buf[2], buf[3]]);
+    let ip = Ipv4Addr::new(buf[4], buf[5], buf[6], buf[7]);
+    let Some(user_end) = find_nul(buf, 8)? else {
+        return Ok(None);
+    };
+    if !is_socks4a_address(ip) {
+        return Ok(Some(((buf[1], TargetAddress::IPv4(ip, port)), user_end + 1)));
+    }
+
+    let Some(host_end) = find_nul(buf, user_end + 1)? else {
+        return Ok(None);
+    };
+    let host = String::from_utf8(buf[user_end + 1..host_end].to_vec())
+        .map_err(|_| "SOCKS4a hostname is not valid UTF-8".to_string())?;
+    if host.is_empty() {
+        return Err("Empty SOCKS4a hostname".to_string());
+    }
+    Ok(Some(((buf[1], TargetAddress::Domain(host, port)), host_end + 1)))
+}
+
+/// Handle a SOCKS4/SOCKS4a connection whose version byte was peeked
+///
+/// The request is translated to SOCKS5 for the upstream and routed the same
+/// way as SOCKS5 traffic. Only CONNECT is supported, and since SOCKS4 has no
+/// passwords it is refused when the router requires credentials.
+async fn handle_socks4_connection(
     mut client_stream: TcpStream,
-    request_data: &[u8],
-    proxy_port: u16,
+    mut reader: FrameReader,
+    client_addr: SocketAddr,
+    config: SocksRouterConfig,
+    connection: &TrackedConnection,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (command, target) = reader.next(&mut client_stream, parse_socks4_request).await?;
+    let target = dns::unmap_synthetic(target);
+    debug!("🎯 SOCKS4 target from {}: {}", client_addr, target);
+    connection.set_target(&target);
+
+    if command != SocksCommand::Connect as u8 {
+        client_stream.write_all(&socks4_reply(SOCKS4_REJECTED)).await?;
+        return Err(format!("Unsupported SOCKS4 command: {}", command).into());
+    }
+    if !config.credentials.is_empty() {
+        client_stream.write_all(&socks4_reply(SOCKS4_REJECTED)).await?;
+        return Err("SOCKS4 can't authenticate, router requires credentials".into());
+    }
+
+    let upstream = route_target(&target, &config, client_addr);
+    let auth = upstream_credential(None, &upstream, &config, &target, client_addr);
+    let mut upstream_stream = match connect_target(&target, &upstream, auth.as_ref()).await {
+        Ok((stream, used)) => {
+            connection.set_upstream(used);
+            stream
+        }
+        Err((_, e)) => {
+            if upstream.is_refusal() {
+                connection.set_upstream(&upstream);
+            }
+            client_stream.write_all(&socks4_reply(SOCKS4_REJECTED)).await?;
+            return Err(e.into());
+        }
+    };
+    let pending = reader.into_pending();
+    if !pending.is_empty() {
+        upstream_stream.write_all(&pending).await?;
+        connection.add_sent(pending.len());
+    }
+
+    client_stream.write_all(&socks4_reply(SOCKS4_GRANTED)).await?;
+    debug!("✅ SOCKS4 tunnel established via {}", upstream);
+    relay(client_stream, upstream_stream, connection).await;
+    Ok(())
+}
+
+/// Build a SOCKS5 request for the upstream proxy
+pub(crate) fn encode_socks5_request(
+    command: SocksCommand,
+    target: &TargetAddress,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut request = vec![0x05, command as u8, 0x00];
+    request.extend(encode_address(target)?);
+    Ok(request)
+}
+
+/// Encode ATYP, address and port as used in SOCKS5 requests and replies
+fn encode_address(target: &TargetAddress) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut request = Vec::new();
+    let port = match target {
+        TargetAddress::IPv4(ip, port) => {
+            request.push(AddressType::IPv4 as u8);
+            request.extend_from_slice(&ip.octets());
+            port
+        }
+        TargetAddress::Domain(domain, port) => {
+            if domain.len() > 255 {
+                return Err("Domain name too long".into());
+            }
+            request.push(AddressType::DomainName as u8);
+            request.push(domain.len() as u8);
+            request.extend_from_slice(domain.as_bytes());
+            port
+        }
+        TargetAddress::IPv6(ip, port) => {
+            request.push(AddressType::IPv6 as u8);
+            request.extend_from_slice(&ip.octets());
+            port
+        }
+    };
+    request.extend_from_slice(&port.to_be_bytes());
+    Ok(request)
+}
+
+/// Where a connection goes once the routing rules have been applied
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Upstream {
+    /// SOCKS5 proxies at "host:port", tried in order until one answers
+    Socks(Vec<String>),
+    Direct,
+    Block,
+    /// Refused because the kill switch is on and the El Tor client isn't ready
+    KillSwitch,
+}
+
+impl Upstream {
+    /// Blocked by a rule or the kill switch rather than sent anywhere
+    pub(crate) fn is_refusal(&self) -> bool {
+        matches!(self, Upstream::Block | Upstream::KillSwitch)
+    }
+}
+
+impl std::fmt::Display for Upstream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Upstream::Socks(addresses) => {
+                let addresses: Vec<String> = addresses.iter().map(|address| format!("socks5://{}", address)).collect();
+                write!(f, "{}", addresses.join(", "))
+            }
+            Upstream::Direct => write!(f, "direct"),
+            Upstream::Block => write!(f, "blocked"),
+            Upstream::KillSwitch => write!(f, "kill switch"),
+        }
+    }
+}
+
+/// Turn a routing action into a concrete upstream
+///
+/// When both eltord ports are eligible the cached health decides the order,
+/// client first when both are healthy.
+///
+/// With the kill switch on, direct routes are always refused and everything
+/// but Arti and blocks is refused until the El Tor client has bootstrapped.
+/// Eltord traffic then only uses the bootstrapped instance, with no relay
+/// fallback.
+pub(crate) fn resolve_upstream(action: &RouteAction, config: &SocksRouterConfig) -> Upstream {
+    let local = |port: u16| format!("127.0.0.1:{}", port);
+    if killswitch::is_enabled() {
+        match action {
+            RouteAction::Arti | RouteAction::Block => {}
+            RouteAction::Direct => return Upstream::KillSwitch,
+            RouteAction::Eltord => {
+                return match killswitch::ready_mode() {
+                    Some(EltorMode::Client) => Upstream::Socks(vec![local(config.eltord_client_socks_port)]),
+                    // "both" runs a single eltord on the relay ports
+                    Some(_) => Upstream::Socks(vec![local(config.eltord_relay_socks_port)]),
+                    None => Upstream::KillSwitch,
+                };
+            }
+            _ if !killswitch::client_ready() => return Upstream::KillSwitch,
+            _ => {}
+        }
+    }
+    match action {
+        RouteAction::Arti => Upstream::Socks(vec![local(config.arti_socks_port)]),
+        RouteAction::EltordClient => Upstream::Socks(vec![local(config.eltord_client_socks_port)]),
+        RouteAction::EltordRelay => Upstream::Socks(vec![local(config.eltord_relay_socks_port)]),
+        RouteAction::Eltord => Upstream::Socks(upstream_health::order_by_health(vec![
+            local(config.eltord_client_socks_port),
+            local(config.eltord_relay_socks_port),
+        ])),
+        RouteAction::Socks { address } => Upstream::Socks(vec![address.clone()]),
+        RouteAction::Direct => Upstream::Direct,
+        RouteAction::Block => Upstream::Block,
+    }
+}
+
+/// Pick the upstream for a target using the routing rules
+///
+/// Without a matching rule .onion goes to Arti and the rest to eltord.
+pub(crate) fn route_target(target: &TargetAddress, config: &SocksRouterConfig, client_addr: SocketAddr) -> Upstream {
+    let decision = routing::route(target);
+    let upstream = resolve_upstream(&decision.action, config);
+    if upstream == Upstream::KillSwitch {
+        killswitch::record_blocked(target);
+    }
+    match decision.rule {
+        Some(rule) => debug!("🧭 {} from {} matched rule {} -> {}", target, client_addr, rule, upstream),
+        None if target.is_onion() => debug!("🧅 Routing .onion domain to Arti ({}) for {}", upstream, client_addr),
+        None => debug!("🌐 Using eltord {} for {}", upstream, client_addr),
+    }
+    upstream
+}
+
+/// Credentials to send upstream: the client's own, else Arti's stream
+/// isolation tag when the connection goes to Arti
+pub(crate) fn upstream_credential(
+    auth: Option<&SocksCredential>,
+    upstream: &Upstream,
+    config: &SocksRouterConfig,
+    target: &TargetAddress,
+    client_addr: SocketAddr,
+) -> Option<SocksCredential> {
+    if auth.is_some() {
+        return auth.cloned();
+    }
+    match upstream {
+        Upstream::Socks(candidates) if *candidates == [format!("127.0.0.1:{}", config.arti_socks_port)] => {
+            crate::arti_config::isolation_credential(target, client_addr)
+        }
+        _ => None,
+    }
+}
+
+/// Open a CONNECT tunnel to a target through its upstream
+///
+/// Used by the SOCKS4 and HTTP front ends, which can't pass the upstream's
+/// SOCKS5 reply through as-is. Returns the stream and the upstream that
+/// carried it ("socks5://host:port" or "direct").
+pub(crate) async fn connect_target(
+    target: &TargetAddress,
+    upstream: &Upstream,
+    auth: Option<&SocksCredential>,
+) -> Result<(TcpStream, String), (ReplyCode, String)> {
+    match upstream {
+        Upstream::Socks(candidates) => {
+            let request = encode_socks5_request(SocksCommand::Connect, target)
+                .map_err(|e| (ReplyCode::GeneralFailure, e.to_string()))?;
+            match open_first_upstream(&request, candidates, auth).await? {
+                (stream, reply, proxy_addr) if reply.get(1) == Some(&(ReplyCode::Success as u8)) => {
+                    Ok((stream, format!("socks5://{}", proxy_addr)))
+                }
+                (_, reply, _) => Err((
+                    ReplyCode::GeneralFailure,
+                    format!("Proxy connection failed with status: {:?}", reply.get(1)),
+                )),
+            }
+        }
+        Upstream::Direct => match TcpStream::connect(target.to_string()).await {
+            Ok(stream) => Ok((stream, Upstream::Direct.to_string())),
+            Err(e) => {
+                let code = match e.kind() {
+                    std::io::ErrorKind::ConnectionRefused => ReplyCode::ConnectionRefused,
+                    std::io::ErrorKind::NetworkUnreachable => ReplyCode::NetworkUnreachable,
+                    _ => ReplyCode::HostUnreachable,
+                };
+                Err((code, format!("Direct connection to {} failed: {}", target, e)))
+            }
+        },
+        Upstream::Block => Err((ReplyCode::ConnectionNotAllowed, format!("{} is blocked by a routing rule", target))),
+        Upstream::KillSwitch => Err((
+            ReplyCode::KillSwitch,
+            format!("Kill switch refused {}: El Tor client is not ready", target),
+        )),
+    }
+}
+
+/// Serve a SOCKS5 request without an upstream proxy
+///
+/// CONNECT dials the target and RESOLVE uses the system resolver. There is
+/// no system reverse lookup, so RESOLVE_PTR is refused.
+async fn handle_direct(
+    mut client_stream: TcpStream,
+    target: &TargetAddress,
+    command: SocksCommand,
+    pending: &[u8],
+    connection: &TrackedConnection,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let proxy_addr = format!("127.0.0.1:{}", proxy_port);
+    connection.set_upstream(Upstream::Direct);
+    match command {
+        SocksCommand::Connect => match connect_target(target, &Upstream::Direct, None).await {
+            Ok((mut upstream_stream, _)) => {
+                if !pending.is_empty() {
+                    upstream_stream.write_all(pending).await?;
+                    connection.add_sent(pending.len());
+                }
+                client_stream.write_all(&socks5_reply(ReplyCode::Success)).await?;
+                debug!("✅ Direct tunnel established to {}", target);
+                relay(client_stream, upstream_stream, connection).await;
+                Ok(())
+            }
+            Err((code, e)) => {
+                client_stream.write_all(&socks5_reply(code)).await?;
+                Err(e.into())
+            }
+        },
+        SocksCommand::Resolve => {
+            let resolved = tokio::net::lookup_host(target.to_string()).await.ok().and_then(|mut addrs| addrs.next());
+            let Some(addr) = resolved else {
+                client_stream.write_all(&socks5_reply(ReplyCode::HostUnreachable)).await?;
+                return Err(format!("Failed to resolve {}", target).into());
+            };
+            let answer = match addr.ip() {
+                IpAddr::V4(ip) => TargetAddress::IPv4(ip, 0),
+                IpAddr::V6(ip) => TargetAddress::IPv6(ip, 0),
+            };
+            debug!("🔎 SOCKS Resolve answered directly: {} -> {}", target, addr.ip());
+            let mut reply = vec![0x05, ReplyCode::Success as u8, 0x00];
+            reply.extend(encode_address(&answer)?);
+            client_stream.write_all(&reply).await?;
+            Ok(())
+        }
+        SocksCommand::ResolvePtr => {
+            client_stream.write_all(&socks5_reply(ReplyCode::CommandNotSupported)).await?;
+            Err("RESOLVE_PTR is not supported for direct routes".into())
+        }
+    }
+}
+
+/// Pick the method to answer a greeting with
+///
+/// Username/password wins whenever it is offered, so the credentials can be
+/// passed on to Tor for `IsolateSOCKSAuth`.
+fn select_auth_method(offered: &[u8], credentials: &[SocksCredential]) -> AuthMethod {
+    if offered.contains(&(AuthMethod::UsernamePassword as u8)) {
+        AuthMethod::UsernamePassword
+    } else if credentials.is_empty() && offered.contains(&(AuthMethod::NoAuth as u8)) {
+        AuthMethod::NoAuth
+    } else {
+        AuthMethod::NoAcceptable
+    }
+}
+
+/// SOCKS5 reply with an empty IPv4 bound address
+fn socks5_reply(code: ReplyCode) -> [u8; 10] {
+    [0x05, code as u8, 0x00, AddressType::IPv4 as u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+}
+
+fn proxy_io_error(e: std::io::Error) -> (ReplyCode, String) {
+    (ReplyCode::GeneralFailure, format!("Proxy I/O error: {}", e))
+}
+
+/// Connect to an upstream SOCKS5 proxy and authenticate
+///
+/// Client credentials are replayed upstream so Tor isolates streams per
+/// username/password. On failure, returns the reply code to report to the
+/// client.
+async fn connect_upstream(
+    proxy_addr: &str,
+    auth: Option<&SocksCredential>,
+) -> Result<TcpStream, (ReplyCode, String)> {
     debug!("🔌 Connecting to proxy at {}", proxy_addr);
     
     // Connect to upstream SOCKS5 proxy
-    let mut upstream_stream = match TcpStream::connect(&proxy_addr).await {
+    let mut upstream_stream = match TcpStream::connect(proxy_addr).await {
         Ok(stream) => {
             debug!("✅ Connected to proxy at {}", proxy_addr);
             stream
         },
         Err(e) => {
             warn!("⚠️ Failed to connect to proxy {}: {}", proxy_addr, e);
-            // Send connection refused to client
-            let response = vec![0x05, 0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
-            client_stream.write_all(&response).await?;
-            return Err(format!("Failed to connect to proxy: {}", e).into());
+            return Err((ReplyCode::ConnectionRefused, format!("Failed to connect to proxy: {}", e)));
         }
     };
 
     // Forward SOCKS5 handshake to upstream
     debug!("🔐 Sending auth handshake to proxy");
-    upstream_stream.write_all(&[0x05, 0x01, 0x00]).await?;
+    let method = match auth {
+        Some(_) => AuthMethod::UsernamePassword,
+        None => AuthMethod::NoAuth,
+    };
+    upstream_stream.write_all(&[0x05, 0x01, method as u8]).await.map_err(proxy_io_error)?;
     let mut auth_response = [0u8; 2];
-    upstream_stream.read_exact(&mut auth_response).await?;
+    upstream_stream.read_exact(&mut auth_response).await.map_err(proxy_io_error)?;
     debug!("🔐 Proxy auth response: {:?}", auth_response);
 
+    if let (Some(credential), 0x02) = (auth, auth_response[1]) {
+        upstream_stream.write_all(&credential.encode()).await.map_err(proxy_io_error)?;
+        upstream_stream.read_exact(&mut auth_response).await.map_err(proxy_io_error)?;
+        debug!("🔐 Proxy username/password response: {:?}", auth_response);
+    } else if auth_response[1] != method as u8 {
+        auth_response[1] = AuthMethod::NoAcceptable as u8;
+    }
+
     if auth_response[1] != 0x00 {
         warn!("⚠️ Proxy authentication failed: {:?}", auth_response);
-        let response = vec![0x05, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
-        client_stream.write_all(&response).await?;
-        return Err("Proxy authentication failed".into());
+        return Err((ReplyCode::GeneralFailure, "Proxy authentication failed".to_string()));
     }
+    Ok(upstream_stream)
+}
 
+/// Send a request over an authenticated upstream and read its reply
+async fn request_upstream(
+    upstream_stream: &mut TcpStream,
+    request_data: &[u8],
+) -> Result<Vec<u8>, (ReplyCode, String)> {
     // Forward the original SOCKS5 request to upstream
     debug!("📤 Forwarding SOCKS request to proxy: {} bytes", request_data.len());
-    upstream_stream.write_all(request_data).await?;
+    upstream_stream.write_all(request_data).await.map_err(proxy_io_error)?;
 
-    // Read upstream response
+    // Read exactly one reply (VER REP RSV ATYP BND.ADDR BND.PORT), so data
+    // the target sends straight after it stays in the stream for the relay
     debug!("📥 Reading proxy response");
-    let mut buffer = vec![0u8; 1024];
-    let resp_n = upstream_stream.read(&mut buffer).await?;
-    debug!("📥 Proxy response: {} bytes, status: {}", resp_n, if resp_n > 1 { buffer[1] } else { 255 });
-    
+    let mut reply = vec![0u8; 4];
+    upstream_stream.read_exact(&mut reply).await.map_err(proxy_io_error)?;
+    let address_len = match reply[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => {
+            let len = upstream_stream.read_u8().await.map_err(proxy_io_error)?;
+            reply.push(len);
+            len as usize
+        }
+        other => {
+            return Err((ReplyCode::GeneralFailure, format!("Invalid address type in proxy reply: {}", other)));
+        }
+    };
+    let address_start = reply.len();
+    reply.resize(address_start + address_len + 2, 0);
+    upstream_stream.read_exact(&mut reply[address_start..]).await.map_err(proxy_io_error)?;
+    debug!("📥 Proxy response: {} bytes, status: {}", reply.len(), reply[1]);
+
+    Ok(reply)
+}
+
+/// Try each candidate upstream until one gets through the handshake, then send the request
+///
+/// Refusals, stalls and handshake failures mark the upstream unhealthy and
+/// move on to the next. Once an upstream has taken the request it is never
+/// replayed elsewhere: a slow or failed reply is the target's doing, not the
+/// upstream's. Returns the stream, the upstream's reply and which upstream
+/// answered.
+pub(crate) async fn open_first_upstream<'a>(
+    request_data: &[u8],
+    candidates: &'a [String],
+    auth: Option<&SocksCredential>,
+) -> Result<(TcpStream, Vec<u8>, &'a str), (ReplyCode, String)> {
+    open_first_upstream_within(request_data, candidates, auth, UPSTREAM_HANDSHAKE_TIMEOUT, UPSTREAM_REPLY_TIMEOUT)
+        .await
+}
+
+/// `open_first_upstream` with explicit handshake and reply limits
+async fn open_first_upstream_within<'a>(
+    request_data: &[u8],
+    candidates: &'a [String],
+    auth: Option<&SocksCredential>,
+    handshake_limit: Duration,
+    reply_limit: Duration,
+) -> Result<(TcpStream, Vec<u8>, &'a str), (ReplyCode, String)> {
+    let mut last_error = (ReplyCode::GeneralFailure, "No upstream proxy available".to_string());
+    for (index, proxy_addr) in candidates.iter().enumerate() {
+        let handshake = tokio::time::timeout(handshake_limit, connect_upstream(proxy_addr, auth))
+            .await
+            .unwrap_or_else(|_| {
+                Err((
+                    ReplyCode::TtlExpired,
+                    format!("No handshake from proxy within {}s", handshake_limit.as_secs_f32()),
+                ))
+            });
+        let mut stream = match handshake {
+            Ok(stream) => stream,
+            Err((code, e)) => {
+                upstream_health::record_failure(proxy_addr, &e);
+                if let Some(next) = candidates.get(index + 1) {
+                    warn!("⚠️ Upstream {} failed ({}), failing over to {}", proxy_addr, e, next);
+                }
+                last_error = (code, e);
+                continue;
+            }
+        };
+        upstream_health::record_success(proxy_addr);
+
+        let reply = tokio::time::timeout(reply_limit, request_upstream(&mut stream, request_data))
+            .await
+            .unwrap_or_else(|_| {
+                Err((
+                    ReplyCode::TtlExpired,
+                    format!("No reply from proxy {} within {}s", proxy_addr, reply_limit.as_secs_f32()),
+                ))
+            })?;
+        return Ok((stream, reply, proxy_addr));
+    }
+    Err(last_error)
+}
+
+/// Forward a SOCKS5 request through an upstream SOCKS5 proxy
+///
+/// RESOLVE and RESOLVE_PTR end after the upstream reply, which carries the
+/// answer in BND.ADDR.
+async fn handle_via_proxy(
+    mut client_stream: TcpStream,
+    request_data: &[u8],
+    candidates: &[String],
+    auth: Option<&SocksCredential>,
+    command: SocksCommand,
+    pending: &[u8],
+    connection: &TrackedConnection,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (mut upstream_stream, reply, proxy_addr) = match open_first_upstream(request_data, candidates, auth).await {
+        Ok(upstream) => upstream,
+        Err((code, e)) => {
+            client_stream.write_all(&socks5_reply(code)).await?;
+            return Err(e.into());
+        }
+    };
+    connection.set_upstream(format!("socks5://{}", proxy_addr));
+
     // Forward response to client
     debug!("📤 Forwarding proxy response to client");
-    client_stream.write_all(&buffer[..resp_n]).await?;
+    client_stream.write_all(&reply).await?;
 
     // Check if the proxy connection was successful
-    if resp_n >= 2 && buffer[1] != 0x00 {
-        warn!("⚠️ Proxy connection failed with status: {}", buffer[1]);
-        return Err(format!("Proxy connection failed with status: {}", buffer[1]).into());
+    if reply.len() >= 2 && reply[1] != 0x00 {
+        warn!("⚠️ Proxy connection failed with status: {}", reply[1]);
+        return Err(format!("Proxy connection failed with status: {}", reply[1]).into());
     }
 
-    debug!("✅ SOCKS tunnel established via proxy port {}", proxy_port);
+    if command != SocksCommand::Connect {
+        match parse_target_address(reply.get(3..).unwrap_or_default()) {
+            Ok(answer) => debug!("🔎 SOCKS {:?} answered via proxy {}: {}", command, proxy_addr, answer),
+            Err(e) => debug!("🔎 SOCKS {:?} reply via proxy {} not parsed: {}", command, proxy_addr, e),
+        }
+        return Ok(());
+    }
 
-    // Bidirectional copy
-    let (mut client_read, mut client_write) = client_stream.into_split();
-    let (mut upstream_read, mut upstream_write) = upstream_stream.into_split();
+    if !pending.is_empty() {
+        upstream_stream.write_all(pending).await?;
+        connection.add_sent(pending.len());
+    }
+    debug!("✅ SOCKS tunnel established via proxy {}", proxy_addr);
+    relay(client_stream, upstream_stream, connection).await;
+    Ok(())
+}
+
+/// Size of the buffer each relay direction copies through
+const RELAY_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Copy one direction until EOF, counting bytes as they go through
+async fn copy_counted(
+    mut reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    count: impl Fn(usize),
+) -> std::io::Result<()> {
+    let mut buffer = vec![0u8; RELAY_BUFFER_SIZE];
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            // Pass the half-close on so the other direction can finish
+            return writer.shutdown().await;
+        }
+        writer.write_all(&buffer[..read]).await?;
+        count(read);
+    }
+}
 
-    let client_to_upstream = tokio::spawn(async move {
-        tokio::io::copy(&mut client_read, &mut upstream_write).await
-    });
+/// Copy data both ways until both sides close
+///
+/// Bytes are added to the connection table live, and whichever side ends
+/// first becomes the close reason.
+pub(crate) async fn relay(client_stream: TcpStream, upstream_stream: TcpStream, connection: &TrackedConnection) {
+    let (client_read, client_write) = client_stream.into_split();
+    let (upstream_read, upstream_write) = upstream_stream.into_split();
 
-    let upstream_to_client = tokio::spawn(async move {
-        tokio::io::copy(&mut upstream_read, &mut client_write).await
-    });
+    let client_to_upstream = async {
+        match copy_counted(client_read, upstream_write, |bytes| connection.add_sent(bytes)).await {
+            Ok(()) => connection.close("client closed"),
+            Err(e) => connection.close(format!("client error: {}", e)),
+        }
+    };
+    let upstream_to_client = async {
+        match copy_counted(upstream_read, client_write, |bytes| connection.add_received(bytes)).await {
+            Ok(()) => connection.close("target closed"),
+            Err(e) => connection.close(format!("target error: {}", e)),
+        }
+    };
 
-    let _ = tokio::try_join!(client_to_upstream, upstream_to_client);
+    tokio::join!(client_to_upstream, upstream_to_client);
     debug!("🔌 SOCKS connection closed");
-    Ok(())
 }
 
 /// Parse target address from SOCKS5 request
-fn parse_target_address(data: &[u8]) -> Result<TargetAddress, Box<dyn std::error::Error + Send + Sync>> {
-    if data.is_empty() {
-        return Err("Empty address data".into());
+pub(crate) fn parse_target_address(data: &[u8]) -> Result<TargetAddress, Box<dyn std::error::Error + Send + Sync>> {
+    match decode_address(data) {
+        Ok(Some((target, _))) => Ok(target),
+        Ok(None) => Err("Incomplete address data".into()),
+        Err((_, e)) => Err(e.into()),
     }
-    
-    match data[0] {
-        0x01 => { // IPv4
-            if data.len() < 7 {
-                return Err("Invalid IPv4 address length".into());
-            }
-            let ip = Ipv4Addr::new(data[1], data[2], data[3], data[4]);
-            let port = u16::from_be_bytes([data[5], data[6]]);
-            Ok(TargetAddress::IPv4(ip, port))
+}
+
+/// Overrides applied to the router config on restart; unset fields keep their value
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SocksRouterConfigUpdate {
+    pub listen_port: Option<u16>,
+    pub listen_addr: Option<IpAddr>,
+    pub arti_socks_port: Option<u16>,
+    pub eltord_client_socks_port: Option<u16>,
+    pub eltord_relay_socks_port: Option<u16>,
+    /// "user:pass,user2:pass2"; an empty string turns authentication off
+    pub credentials: Option<String>,
+    /// 0 turns the HTTP proxy off
+    pub http_proxy_port: Option<u16>,
+    pub http_proxy_addr: Option<IpAddr>,
+    /// 0 turns the DNS resolver off
+    pub dns_port: Option<u16>,
+    pub dns_addr: Option<IpAddr>,
+    pub dns_onion: Option<OnionDnsMode>,
+    /// 0 turns the transparent proxy off
+    pub transparent_port: Option<u16>,
+    pub transparent_addr: Option<IpAddr>,
+}
+
+impl SocksRouterConfigUpdate {
+    pub fn apply(&self, mut config: SocksRouterConfig) -> SocksRouterConfig {
+        config.listen_port = self.listen_port.unwrap_or(config.listen_port);
+        config.listen_addr = self.listen_addr.or(config.listen_addr);
+        config.arti_socks_port = self.arti_socks_port.unwrap_or(config.arti_socks_port);
+        config.eltord_client_socks_port = self.eltord_client_socks_port.unwrap_or(config.eltord_client_socks_port);
+        config.eltord_relay_socks_port = self.eltord_relay_socks_port.unwrap_or(config.eltord_relay_socks_port);
+        if let Some(credentials) = &self.credentials {
+            config.credentials = SocksCredential::parse_list(credentials);
+        }
+        match self.http_proxy_port {
+            Some(0) => config.http_proxy_port = None,
+            Some(port) => config.http_proxy_port = Some(port),
+            None => {}
+        }
+        config.http_proxy_addr = self.http_proxy_addr.or(config.http_proxy_addr);
+        match self.dns_port {
+            Some(0) => config.dns_port = None,
+            Some(port) => config.dns_port = Some(port),
+            None => {}
+        }
+        config.dns_addr = self.dns_addr.or(config.dns_addr);
+        config.dns_onion = self.dns_onion.unwrap_or(config.dns_onion);
+        match self.transparent_port {
+            Some(0) => config.transparent_port = None,
+            Some(port) => config.transparent_port = Some(port),
+            None => {}
+        }
+        config.transparent_addr = self.transparent_addr.or(config.transparent_addr);
+        config
+    }
+}
+
+/// What the SOCKS router is doing right now
+#[derive(Debug, Clone, Serialize)]
+pub struct SocksRouterStatus {
+    pub running: bool,
+    pub bind_address: Option<String>,
+    pub http_proxy_address: Option<String>,
+    pub dns_address: Option<String>,
+    pub transparent_address: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub uptime_secs: u64,
+    pub active_connections: usize,
+    pub total_connections: u64,
+    pub arti_socks_port: u16,
+    pub eltord_client_socks_port: u16,
+    pub eltord_relay_socks_port: u16,
+    pub auth_required: bool,
+}
+
+/// A started router and what it takes to stop it
+struct RunningRouter {
+    config: SocksRouterConfig,
+    bind_addr: SocketAddr,
+    http_proxy_addr: Option<SocketAddr>,
+    dns_addr: Option<SocketAddr>,
+    transparent_addr: Option<SocketAddr>,
+    started_at: DateTime<Utc>,
+    stats: Arc<ConnectionStats>,
+    shutdown: watch::Sender<bool>,
+    tasks: Vec<tokio::task::JoinHandle<()>>,
+}
+
+impl RunningRouter {
+    fn status(&self) -> SocksRouterStatus {
+        SocksRouterStatus {
+            running: true,
+            bind_address: Some(self.bind_addr.to_string()),
+            http_proxy_address: self.http_proxy_addr.map(|addr| addr.to_string()),
+            dns_address: self.dns_addr.map(|addr| addr.to_string()),
+            transparent_address: self.transparent_addr.map(|addr| addr.to_string()),
+            started_at: Some(self.started_at),
+            uptime_secs: (Utc::now() - self.started_at).num_seconds().max(0) as u64,
+            active_connections: self.stats.active(),
+            total_connections: self.stats.total(),
+            ..stopped_status(&self.config)
         }
-        0x03 => { // Domain name
-            if data.len() < 2 {
-                return Err("Invalid domain name length".into());
+    }
+}
+
+fn stopped_status(config: &SocksRouterConfig) -> SocksRouterStatus {
+    SocksRouterStatus {
+        running: false,
+        bind_address: None,
+        http_proxy_address: None,
+        dns_address: None,
+        transparent_address: None,
+        started_at: None,
+        uptime_secs: 0,
+        active_connections: 0,
+        total_connections: 0,
+        arti_socks_port: config.arti_socks_port,
+        eltord_client_socks_port: config.eltord_client_socks_port,
+        eltord_relay_socks_port: config.eltord_relay_socks_port,
+        auth_required: !config.credentials.is_empty(),
+    }
+}
+
+#[derive(Default)]
+struct RouterState {
+    running: Option<RunningRouter>,
+    /// Config of the last start, reused when starting again without one
+    last_config: Option<SocksRouterConfig>,
+}
+
+fn router_state() -> &'static Mutex<RouterState> {
+    static STATE: OnceLock<Mutex<RouterState>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(RouterState::default()))
+}
+
+/// Serializes start/stop so two callers can't bind the router twice
+fn lifecycle_lock() -> &'static tokio::sync::Mutex<()> {
+    static LOCK: OnceLock<tokio::sync::Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| tokio::sync::Mutex::new(()))
+}
+
+/// The router gets its own runtime so it outlives whichever caller started it
+/// (eltord startup runs on a short-lived runtime in its own thread)
+fn router_runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .thread_name("socks-router")
+            .enable_all()
+            .build()
+            .expect("Failed to create SOCKS router runtime")
+    })
+}
+
+/// Bind the router (and HTTP proxy, if configured) and start serving
+async fn launch(config: SocksRouterConfig) -> Result<RunningRouter, String> {
+    info!("🔧 Creating SOCKS router with config: {:?}", config);
+    let mut router = SocksRouter::new(config.clone());
+    router.start().await?;
+    let bind_addr = router.local_addr().ok_or("SOCKS router has no local address")?;
+    let stats = router.stats();
+    let (shutdown, receiver) = watch::channel(false);
+    upstream_health::start_health_checker(config.clone());
+    killswitch::start_watcher();
+
+    // The HTTP proxy is optional - don't take the SOCKS router down with it
+    let mut tasks = Vec::new();
+    let mut http_proxy_addr = None;
+    if config.http_proxy_port.is_some() {
+        let mut http_proxy = crate::http_proxy::HttpProxy::new(config.clone(), stats.clone());
+        match http_proxy.start().await {
+            Ok(()) => {
+                http_proxy_addr = http_proxy.local_addr();
+                let receiver = receiver.clone();
+                tasks.push(tokio::spawn(async move {
+                    if let Err(e) = http_proxy.run(receiver).await {
+                        warn!("⚠️ HTTP proxy stopped: {}", e);
+                    }
+                }));
             }
-            let domain_len = data[1] as usize;
-            if data.len() < 4 + domain_len {
-                return Err("Incomplete domain name data".into());
+            Err(e) => warn!("⚠️ HTTP proxy failed to start: {}", e),
+        }
+    }
+
+    // Same for the DNS resolver
+    let mut dns_addr = None;
+    if config.dns_port.is_some() {
+        let mut dns = crate::dns::DnsServer::new(config.clone());
+        match dns.start().await {
+            Ok(()) => {
+                dns_addr = dns.local_addr();
+                let receiver = receiver.clone();
+                tasks.push(tokio::spawn(async move {
+                    if let Err(e) = dns.run(receiver).await {
+                        warn!("⚠️ DNS resolver stopped: {}", e);
+                    }
+                }));
             }
-            let domain = String::from_utf8(data[2..2 + domain_len].to_vec())?;
-            let port = u16::from_be_bytes([data[2 + domain_len], data[3 + domain_len]]);
-            Ok(TargetAddress::Domain(domain, port))
+            Err(e) => warn!("⚠️ DNS resolver failed to start: {}", e),
         }
-        0x04 => { // IPv6
-            if data.len() < 19 {
-                return Err("Invalid IPv6 address length".into());
+    }
+
+    // And the transparent proxy
+    let mut transparent_addr = None;
+    if config.transparent_port.is_some() {
+        let mut transparent = crate::transparent::TransparentProxy::new(config.clone(), stats.clone());
+        match transparent.start().await {
+            Ok(()) => {
+                transparent_addr = transparent.local_addr();
+                let receiver = receiver.clone();
+                tasks.push(tokio::spawn(async move {
+                    if let Err(e) = transparent.run(receiver).await {
+                        warn!("⚠️ Transparent proxy stopped: {}", e);
+                    }
+                }));
             }
-            let mut ipv6_bytes = [0u8; 16];
-            ipv6_bytes.copy_from_slice(&data[1..17]);
-            let ip = std::net::Ipv6Addr::from(ipv6_bytes);
-            let port = u16::from_be_bytes([data[17], data[18]]);
-            Ok(TargetAddress::IPv6(ip, port))
+            Err(e) => warn!("⚠️ Transparent proxy failed to start: {}", e),
         }
-        _ => Err(format!("Unsupported address type: {}", data[0]).into()),
     }
+
+    tasks.push(tokio::spawn(async move {
+        if let Err(e) = router.run(receiver).await {
+            warn!("⚠️ SOCKS Router stopped: {}", e);
+        }
+    }));
+
+    Ok(RunningRouter {
+        config,
+        bind_addr,
+        http_proxy_addr,
+        dns_addr,
+        transparent_addr,
+        started_at: Utc::now(),
+        stats,
+        shutdown,
+        tasks,
+    })
 }
 
-/// Create and start a SOCKS router
-pub async fn create_socks_router() -> Result<SocksRouter, String> {
-    let config = SocksRouterConfig::from_env();
-    info!("🔧 Creating SOCKS router with config: {:?}", config);
-    
-    let mut router = SocksRouter::new(config);
-    router.start().await?;
-    Ok(router)
+/// Start the SOCKS router with the given config
+///
+/// Does nothing if the router is already running; use
+/// [`restart_socks_router`] to apply a new config.
+pub async fn start_socks_router_with_config(config: SocksRouterConfig) -> Result<SocksRouterStatus, String> {
+    let _lifecycle = lifecycle_lock().lock().await;
+    if let Some(running) = &router_state().lock().unwrap().running {
+        debug!("🔀 SOCKS Router already running on {}", running.bind_addr);
+        return Ok(running.status());
+    }
+
+    let running = router_runtime()
+        .spawn(launch(config.clone()))
+        .await
+        .map_err(|e| format!("SOCKS router startup task failed: {}", e))??;
+    let status = running.status();
+    let mut state = router_state().lock().unwrap();
+    state.running = Some(running);
+    state.last_config = Some(config);
+    Ok(status)
 }
 
-/// Start the SOCKS router server
-pub async fn start_socks_router() -> Result<(), String> {
-    let mut router = create_socks_router().await?;
-    router.run().await
+/// Start the SOCKS router with the last used config, or the environment's
+pub async fn start_socks_router() -> Result<SocksRouterStatus, String> {
+    start_socks_router_with_config(socks_router_config()).await
 }
 
-/// Stop the SOCKS router (placeholder for compatibility)
+/// Stop the SOCKS router
+///
+/// The listeners close immediately; open connections get DRAIN_TIMEOUT to
+/// finish before they are cut. Stopping a stopped router is not an error.
 pub async fn stop_socks_router() -> Result<(), String> {
-    // Since we're using a simple approach, there's no global state to clean up
-    // The router stops when the task is cancelled
+    let _lifecycle = lifecycle_lock().lock().await;
+    let Some(running) = router_state().lock().unwrap().running.take() else {
+        debug!("🔀 SOCKS Router is not running");
+        return Ok(());
+    };
+
+    info!(
+        "🛑 Stopping SOCKS Router on {} ({} active connection(s))",
+        running.bind_addr,
+        running.stats.active()
+    );
+    let uptime = running.status().uptime_secs;
+    let _ = running.shutdown.send(true);
+    for task in running.tasks {
+        if let Err(e) = task.await {
+            warn!("⚠️ SOCKS Router task ended abnormally: {}", e);
+        }
+    }
+    info!("✅ SOCKS Router stopped after {}s", uptime);
     Ok(())
 }
 
-/// Check if SOCKS router is running (placeholder for compatibility)
+/// Stop the router, then start it again with `config` (or the current config)
+pub async fn restart_socks_router(config: Option<SocksRouterConfig>) -> Result<SocksRouterStatus, String> {
+    let config = config.unwrap_or_else(socks_router_config);
+    stop_socks_router().await?;
+    start_socks_router_with_config(config).await
+}
+
+/// Point the router at a new Arti SOCKS port, restarting it if it is running
+pub async fn set_arti_socks_port(port: u16) -> Result<(), String> {
+    let mut config = socks_router_config();
+    if config.arti_socks_port == port {
+        return Ok(());
+    }
+    config.arti_socks_port = port;
+    if is_socks_router_running() {
+        info!("🔄 Restarting SOCKS Router for Arti SOCKS port {}", port);
+        restart_socks_router(Some(config)).await?;
+    } else {
+        router_state().lock().unwrap().last_config = Some(config);
+    }
+    Ok(())
+}
+
+/// Config of the running router, else the last one used, else the environment's
+pub fn socks_router_config() -> SocksRouterConfig {
+    let state = router_state().lock().unwrap();
+    match (&state.running, &state.last_config) {
+        (Some(running), _) => running.config.clone(),
+        (None, Some(config)) => config.clone(),
+        (None, None) => SocksRouterConfig::from_env(),
+    }
+}
+
+pub fn get_socks_router_status() -> SocksRouterStatus {
+    let state = router_state().lock().unwrap();
+    match (&state.running, &state.last_config) {
+        (Some(running), _) => running.status(),
+        (None, Some(config)) => stopped_status(config),
+        (None, None) => stopped_status(&SocksRouterConfig::from_env()),
+    }
+}
+
+/// Check if SOCKS router is running
 pub fn is_socks_router_running() -> bool {
-    // For now, we'll assume it's not running since we don't track global state
-    // This could be enhanced with proper state tracking if needed
-    false
-}
\ No newline at end of file
+    router_state().lock().unwrap().running.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credential(username: &str, password: &str) -> SocksCredential {
+        SocksCredential {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_credential_list() {
+        let credentials = SocksCredential::parse_list("firefox:secret, wallet:p:w,broken,:nouser");
+        assert_eq!(credentials, vec![credential("firefox", "secret"), credential("wallet", "p:w")]);
+        assert!(SocksCredential::parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_select_auth_method() {
+        let required = vec![credential("firefox", "secret")];
+
+        // Open router: no-auth works, username/password is preferred for isolation
+        assert_eq!(select_auth_method(&[0x00], &[]), AuthMethod::NoAuth);
+        assert_eq!(select_auth_method(&[0x00, 0x02], &[]), AuthMethod::UsernamePassword);
+        assert_eq!(select_auth_method(&[0x01], &[]), AuthMethod::NoAcceptable);
+
+        // Credentials configured: username/password is mandatory
+        assert_eq!(select_auth_method(&[0x00], &required), AuthMethod::NoAcceptable);
+        assert_eq!(select_auth_method(&[0x00, 0x02], &required), AuthMethod::UsernamePassword);
+        assert_eq!(select_auth_method(&[], &required), AuthMethod::NoAcceptable);
+    }
+
+    #[test]
+    fn test_socks_commands() {
+        assert_eq!(SocksCommand::from_byte(0x01), Some(SocksCommand::Connect));
+        assert_eq!(SocksCommand::from_byte(0xF0), Some(SocksCommand::Resolve));
+        assert_eq!(SocksCommand::from_byte(0xF1), Some(SocksCommand::ResolvePtr));
+        // BIND and UDP ASSOCIATE stay unsupported
+        assert_eq!(SocksCommand::from_byte(0x02), None);
+        assert_eq!(SocksCommand::from_byte(0x03), None);
+    }
+
+    #[test]
+    fn test_socks4a_detection() {
+        assert!(is_socks4a_address(Ipv4Addr::new(0, 0, 0, 1)));
+        assert!(!is_socks4a_address(Ipv4Addr::new(0, 0, 0, 0)));
+        assert!(!is_socks4a_address(Ipv4Addr::new(93, 184, 216, 34)));
+    }
+
+    #[test]
+    fn test_encode_socks5_request() {
+        let request = encode_socks5_request(
+            SocksCommand::Connect,
+            &TargetAddress::Domain("example.onion".to_string(), 80),
+        )
+        .unwrap();
+        assert_eq!(&request[..5], &[0x05, 0x01, 0x00, 0x03, 13]);
+        assert_eq!(&request[request.len() - 2..], &[0x00, 80]);
+
+        // Round trip through the request parser
+        match parse_target_address(&request[3..]).unwrap() {
+            TargetAddress::Domain(domain, port) => assert_eq!((domain.as_str(), port), ("example.onion", 80)),
+            other => panic!("unexpected target {:?}", other),
+        }
+
+        let request = encode_socks5_request(
+            SocksCommand::Connect,
+            &TargetAddress::IPv4(Ipv4Addr::new(10, 0, 0, 1), 443),
+        )
+        .unwrap();
+        assert_eq!(request, vec![0x05, 0x01, 0x00, 0x01, 10, 0, 0, 1, 0x01, 0xBB]);
+    }
+
+    #[test]
+    fn test_encode_credential() {
+        assert_eq!(credential("ab", "c").encode(), vec![0x01, 2, b'a', b'b', 1, b'c']);
+    }
+
+    /// Every strict prefix of a message needs more bytes, the whole message
+    /// parses, and bytes pipelined after it are left alone
+    fn assert_framed<T, E: std::fmt::Debug>(message: &[u8], parse: impl Fn(&[u8]) -> Parsed<T, E>) -> T {
+        for len in 0..message.len() {
+            assert!(
+                matches!(parse(&message[..len]), Ok(None)),
+                "prefix of {} bytes of {:?} should be incomplete",
+                len,
+                message
+            );
+        }
+        let mut pipelined = message.to_vec();
+        pipelined.extend_from_slice(b"GET / HTTP/1.1");
+        let (_, used) = parse(&pipelined).unwrap().unwrap();
+        assert_eq!(used, message.len());
+        parse(message).unwrap().unwrap().0
+    }
+
+    #[test]
+    fn test_greeting_and_credential_framing() {
+        assert_eq!(assert_framed(&[0x05, 0x02, 0x00, 0x02], parse_greeting), vec![0x00, 0x02]);
+        assert!(parse_greeting(&[0x05, 0x00]).is_err());
+        assert!(parse_greeting(&[0x04, 0x01, 0x00]).is_err());
+
+        let parsed = assert_framed(&credential("firefox", "secret").encode(), parse_credential);
+        assert_eq!(parsed, credential("firefox", "secret"));
+        assert_eq!(assert_framed(&credential("", "").encode(), parse_credential), credential("", ""));
+        assert!(parse_credential(&[0x05, 0x01]).is_err());
+        assert!(parse_credential(&[0x01, 0x01, 0xFF, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_request_framing() {
+        let request = encode_socks5_request(SocksCommand::Resolve, &TargetAddress::Domain("example.onion".to_string(), 80)).unwrap();
+        let (command, target) = assert_framed(&request, parse_request);
+        assert_eq!((command, target.to_string()), (0xF0, "example.onion:80".to_string()));
+
+        let target = TargetAddress::IPv6(std::net::Ipv6Addr::LOCALHOST, 443);
+        let request = encode_socks5_request(SocksCommand::Connect, &target).unwrap();
+        assert_eq!(assert_framed(&request, parse_request).1.to_string(), "[::1]:443");
+
+        // Bad address types and lengths are reported with the right reply code
+        assert!(matches!(parse_request(&[0x05, 0x01, 0x00, 0x02]), Err((ReplyCode::AddressTypeNotSupported, _))));
+        assert!(matches!(parse_request(&[0x05, 0x01, 0x00, 0x03, 0x00, 0x00, 0x50]), Err((ReplyCode::GeneralFailure, _))));
+        assert!(parse_request(&[0x04, 0x01]).is_err());
+        assert!(parse_target_address(&[0x01, 10, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_socks4_framing() {
+        let mut request = vec![0x04, 0x01, 0x01, 0xBB, 93, 184, 216, 34];
+        request.extend_from_slice(b"user\0");
+        let (command, target) = assert_framed(&request, parse_socks4_request);
+        assert_eq!((command, target.to_string()), (0x01, "93.184.216.34:443".to_string()));
+
+        let mut request = vec![0x04, 0x01, 0x00, 0x50, 0, 0, 0, 1, 0x00];
+        request.extend_from_slice(b"example.onion\0");
+        assert_eq!(assert_framed(&request, parse_socks4_request).1.to_string(), "example.onion:80");
+
+        // Unterminated fields can't grow without bound
+        let mut request = vec![0x04, 0x01, 0x00, 0x50, 1, 2, 3, 4];
+        request.extend(std::iter::repeat_n(b'a', 300));
+        assert!(parse_socks4_request(&request).is_err());
+    }
+
+    fn random_target(rng: &mut impl rand::Rng) -> TargetAddress {
+        let port = rng.gen();
+        match rng.gen_range(0..3) {
+            0 => TargetAddress::IPv4(Ipv4Addr::from(rng.gen::<u32>()), port),
+            1 => TargetAddress::IPv6(std::net::Ipv6Addr::from(rng.gen::<u128>()), port),
+            _ => {
+                let len = rng.gen_range(1..=255);
+                let domain: String = (0..len).map(|_| rng.gen_range(b'a'..=b'z') as char).collect();
+                TargetAddress::Domain(domain, port)
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_requests_round_trip() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0x50C5);
+
+        for _ in 0..2000 {
+            let target = random_target(&mut rng);
+            let command = [SocksCommand::Connect, SocksCommand::Resolve, SocksCommand::ResolvePtr][rng.gen_range(0..3)];
+            let request = encode_socks5_request(command, &target).unwrap();
+
+            // Split the request at a random point, as a fragmenting client would
+            let split = rng.gen_range(0..request.len());
+            assert!(matches!(parse_request(&request[..split]), Ok(None)));
+            let ((parsed_command, parsed), used) = parse_request(&request).unwrap().unwrap();
+            assert_eq!(used, request.len());
+            assert_eq!(parsed_command, command as u8);
+            assert_eq!(parsed.to_string(), target.to_string());
+            assert_eq!(parse_target_address(&request[3..]).unwrap().to_string(), target.to_string());
+        }
+    }
+
+    #[test]
+    fn test_fuzz_parsers_never_panic() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0xF022);
+
+        for _ in 0..20000 {
+            // Mostly valid messages with a few bytes flipped, plus pure noise
+            let mut buf = if rng.gen_bool(0.5) {
+                encode_socks5_request(SocksCommand::Connect, &random_target(&mut rng)).unwrap()
+            } else {
+                (0..rng.gen_range(0..64)).map(|_| rng.gen()).collect()
+            };
+            for _ in 0..rng.gen_range(0..4) {
+                if !buf.is_empty() {
+                    let at = rng.gen_range(0..buf.len());
+                    buf[at] = rng.gen();
+                }
+            }
+            if let Some(version) = buf.first_mut() {
+                *version = [0x01, 0x04, 0x05][rng.gen_range(0..3)];
+            }
+
+            for parsed in [
+                parse_greeting(&buf).map(|p| p.map(|(_, used)| used)),
+                parse_credential(&buf).map(|p| p.map(|(_, used)| used)),
+                parse_socks4_request(&buf).map(|p| p.map(|(_, used)| used)),
+                parse_request(&buf).map(|p| p.map(|(_, used)| used)).map_err(|(_, e)| e),
+                decode_address(&buf).map(|p| p.map(|(_, used)| used)).map_err(|(_, e)| e),
+            ] {
+                if let Ok(Some(used)) = parsed {
+                    assert!(used <= buf.len());
+                }
+            }
+            let _ = parse_target_address(&buf);
+        }
+    }
+
+    /// Fake upstream SOCKS5 proxy that expects `request`, then echoes early data back
+    async fn fake_upstream(request: Vec<u8>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream.write_all(&[0x05, 0x00]).await.unwrap();
+            let mut received = vec![0u8; request.len()];
+            stream.read_exact(&mut received).await.unwrap();
+            assert_eq!(received, request);
+            stream.write_all(&socks5_reply(ReplyCode::Success)).await.unwrap();
+            let mut early = [0u8; 5];
+            stream.read_exact(&mut early).await.unwrap();
+            stream.write_all(&early).await.unwrap();
+        });
+        port
+    }
+
+    /// Run a client that sends the handshake plus "hello" in the given chunks
+    async fn run_handshake(chunk_size: usize) -> Vec<u8> {
+        let target = TargetAddress::Domain("example.com".to_string(), 443);
+        let request = encode_socks5_request(SocksCommand::Connect, &target).unwrap();
+        let upstream_port = fake_upstream(request.clone()).await;
+        let config = SocksRouterConfig {
+            eltord_client_socks_port: upstream_port,
+            eltord_relay_socks_port: upstream_port,
+            ..Default::default()
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let router_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, client_addr) = listener.accept().await.unwrap();
+            let connection = crate::connections::track("socks", client_addr);
+            let _ = handle_socks_connection(stream, client_addr, config, &connection).await;
+        });
+
+        let mut client = TcpStream::connect(router_addr).await.unwrap();
+        client.set_nodelay(true).unwrap();
+        let mut message = vec![0x05, 0x01, 0x00];
+        message.extend_from_slice(&request);
+        message.extend_from_slice(b"hello");
+        for chunk in message.chunks(chunk_size) {
+            client.write_all(chunk).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+
+        let mut response = vec![0u8; 2 + 10 + 5];
+        client.read_exact(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_fragmented_and_pipelined_handshake() {
+        let mut expected = vec![0x05, 0x00];
+        expected.extend_from_slice(&socks5_reply(ReplyCode::Success));
+        expected.extend_from_slice(b"hello");
+
+        // One byte per write, then everything (early data included) in one write
+        assert_eq!(run_handshake(1).await, expected);
+        assert_eq!(run_handshake(usize::MAX).await, expected);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_upstream_fails_over() {
+        // Accepts TCP, then never answers the greeting
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let silent_addr = silent.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (_stream, _) = silent.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+
+        let target = TargetAddress::Domain("example.com".to_string(), 443);
+        let request = encode_socks5_request(SocksCommand::Connect, &target).unwrap();
+        let working_addr = format!("127.0.0.1:{}", fake_upstream(request.clone()).await);
+
+        let candidates = vec![silent_addr, working_addr.clone()];
+        let limit = Duration::from_millis(200);
+        let (_, reply, proxy_addr) =
+            open_first_upstream_within(&request, &candidates, None, limit, limit).await.unwrap();
+        assert_eq!(proxy_addr, working_addr);
+        assert_eq!(reply, socks5_reply(ReplyCode::Success));
+    }
+
+    #[tokio::test]
+    async fn test_slow_reply_does_not_fail_over() {
+        // Completes the greeting, then sits on the request like a slow target
+        let slow = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let slow_addr = slow.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = slow.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream.write_all(&[0x05, 0x00]).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+
+        // Would fail the test if the request were replayed to it
+        let unused = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let unused_addr = unused.local_addr().unwrap().to_string();
+
+        let target = TargetAddress::Domain("example.com".to_string(), 443);
+        let request = encode_socks5_request(SocksCommand::Connect, &target).unwrap();
+        let candidates = vec![slow_addr, unused_addr];
+        let limit = Duration::from_millis(200);
+        let result = open_first_upstream_within(&request, &candidates, None, limit, limit).await;
+        assert!(matches!(result, Err((ReplyCode::TtlExpired, _))));
+        assert!(tokio::time::timeout(Duration::from_millis(50), unused.accept()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_router_lifecycle() {
+        let config = SocksRouterConfig {
+            listen_port: 0,
+            ..Default::default()
+        };
+        let status = start_socks_router_with_config(config).await.unwrap();
+        assert!(status.running);
+        let bind_address = status.bind_address.unwrap();
+
+        // An open connection is counted, and a second start is a no-op
+        let client = TcpStream::connect(&bind_address).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let status = start_socks_router().await.unwrap();
+        assert_eq!(status.bind_address.as_deref(), Some(bind_address.as_str()));
+        assert_eq!(status.active_connections, 1);
+        drop(client);
+
+        // Restart applies the update and keeps the rest of the config
+        let update = SocksRouterConfigUpdate {
+            arti_socks_port: Some(18999),
+            ..Default::default()
+        };
+        let status = restart_socks_router(Some(update.apply(socks_router_config()))).await.unwrap();
+        assert!(status.running);
+        assert_eq!(status.arti_socks_port, 18999);
+        assert_eq!(status.total_connections, 0);
+
+        stop_socks_router().await.unwrap();
+        assert!(!is_socks_router_running());
+        assert!(!get_socks_router_status().running);
+        assert!(stop_socks_router().await.is_ok());
+    }
+}
@@ -18,18 +18,16 @@ pub struct LogEntry {
     pub mode: Option<String>, // "client", "relay", or None for system logs
 }
 
-// Wallet state for tracking phoenixd process and configuration
+// Wallet configuration - the phoenixd process itself lives in the process registry
 #[derive(Debug, Clone)]
 pub struct WalletState {
     pub use_phoenixd_embedded: bool,
-    pub phoenixd_process: Arc<Mutex<Option<tokio::process::Child>>>,
 }
 
 impl WalletState {
     pub fn new(use_phoenixd_embedded: bool) -> Self {
         Self {
             use_phoenixd_embedded,
-            phoenixd_process: Arc::new(Mutex::new(None)),
         }
     }
 }
@@ -0,0 +1,604 @@
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+use crate::state::{AppState, LogEntry};
+
+/// How often the supervisor checks on processes it can't wait() on directly
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Restarts a crashed service. Implementations re-register the new PID with [`watch`].
+pub type RestartFn = Arc<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// Backoff and crash-loop settings shared by every supervised service
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up once this many crashes happen inside `crash_window`
+    pub max_crashes: usize,
+    pub crash_window: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(60),
+            max_crashes: 5,
+            crash_window: Duration::from_secs(600),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Read overrides from APP_ELTOR_SUPERVISOR_MAX_CRASHES / APP_ELTOR_SUPERVISOR_CRASH_WINDOW_SECS
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        if let Some(max) = std::env::var("APP_ELTOR_SUPERVISOR_MAX_CRASHES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            policy.max_crashes = max;
+        }
+        if let Some(secs) = std::env::var("APP_ELTOR_SUPERVISOR_CRASH_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+        {
+            policy.crash_window = Duration::from_secs(secs);
+        }
+        policy
+    }
+
+    /// Delay before the nth restart inside the crash window (n starts at 1)
+    pub fn backoff(&self, crashes_in_window: usize) -> Duration {
+        let exponent = crashes_in_window.saturating_sub(1).min(16) as u32;
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_backoff)
+    }
+}
+
+/// Lifecycle state of a supervised service
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceState {
+    Running,
+    Restarting,
+    /// Hit the crash-loop limit; needs a manual start
+    Failed,
+}
+
+/// Snapshot of a supervised service for the API
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub pid: Option<u32>,
+    pub state: ServiceState,
+    pub restart_count: u32,
+    pub crash_count: u32,
+    pub last_exit_code: Option<i32>,
+    pub last_crash_at: Option<DateTime<Utc>>,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Crash and restart notifications
+#[derive(Debug, Clone, Serialize)]
+pub struct SupervisorEvent {
+    pub timestamp: DateTime<Utc>,
+    pub service: String,
+    /// "crashed", "restarted", "restart_failed" or "gave_up"
+    pub kind: String,
+    pub pid: Option<u32>,
+    pub exit_code: Option<i32>,
+    pub restart_count: u32,
+    pub message: String,
+}
+
+struct Service {
+    status: ServiceStatus,
+    restart: RestartFn,
+    recent_crashes: VecDeque<Instant>,
+    /// Set by the poller on the first tick a process looks dead, so a reaper
+    /// thread gets a chance to report the real exit code first
+    suspected_dead: bool,
+}
+
+struct Supervisor {
+    services: Mutex<HashMap<String, Service>>,
+    sender: broadcast::Sender<SupervisorEvent>,
+    app_state: Mutex<Option<AppState>>,
+    /// None when the supervisor thread couldn't be started; supervision is off
+    runtime: Option<tokio::runtime::Handle>,
+    policy: Mutex<RestartPolicy>,
+}
+
+static SUPERVISOR: OnceLock<Supervisor> = OnceLock::new();
+
+/// Start the supervisor's own runtime on a dedicated thread and run the poller on it
+fn start_runtime() -> Result<tokio::runtime::Handle, String> {
+    let (handle_tx, handle_rx) = std::sync::mpsc::channel();
+    std::thread::Builder::new()
+        .name("process-supervisor".to_string())
+        .spawn(move || {
+            let rt = match tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(2)
+                .enable_all()
+                .build()
+            {
+                Ok(rt) => rt,
+                Err(e) => {
+                    let _ = handle_tx.send(Err(format!("Failed to create supervisor runtime: {}", e)));
+                    return;
+                }
+            };
+            let _ = handle_tx.send(Ok(rt.handle().clone()));
+            rt.block_on(poll_loop());
+        })
+        .map_err(|e| format!("Failed to spawn supervisor thread: {}", e))?;
+    handle_rx
+        .recv()
+        .map_err(|_| "Supervisor thread exited before its runtime started".to_string())?
+}
+
+fn supervisor() -> &'static Supervisor {
+    SUPERVISOR.get_or_init(|| {
+        // Processes are started from sync code, Tauri's runtime and axum's runtime,
+        // so the supervisor keeps its own long-lived runtime on a dedicated thread
+        let runtime = match start_runtime() {
+            Ok(runtime) => Some(runtime),
+            Err(e) => {
+                error!("❌ {} - crashed services won't be restarted", e);
+                None
+            }
+        };
+
+        let (sender, _) = broadcast::channel(100);
+        Supervisor {
+            services: Mutex::new(HashMap::new()),
+            sender,
+            app_state: Mutex::new(None),
+            runtime,
+            policy: Mutex::new(RestartPolicy::from_env()),
+        }
+    })
+}
+
+/// Handle a crash on the supervisor runtime, so a restart's backoff doesn't hold up the caller
+fn spawn_crash_handler(name: String, pid: u32, exit_code: Option<i32>) {
+    if let Some(runtime) = &supervisor().runtime {
+        runtime.spawn(handle_crash(name, pid, exit_code));
+    }
+}
+
+/// Route supervisor crash reports into the app log stream
+pub fn attach_app_state(state: AppState) {
+    *supervisor().app_state.lock().unwrap() = Some(state);
+}
+
+/// Start (or keep) supervising a service under the given name
+///
+/// Calling this again for a name that is already supervised updates the PID
+/// and restart function but keeps the crash history, which is how restarts
+/// report the new process back.
+pub fn watch(name: &str, pid: u32, restart: RestartFn) {
+    let mut services = supervisor().services.lock().unwrap();
+    match services.get_mut(name) {
+        Some(service) => {
+            service.status.pid = Some(pid);
+            service.status.state = ServiceState::Running;
+            service.status.started_at = Utc::now();
+            service.restart = restart;
+            service.suspected_dead = false;
+        }
+        None => {
+            services.insert(
+                name.to_string(),
+                Service {
+                    status: ServiceStatus {
+                        name: name.to_string(),
+                        pid: Some(pid),
+                        state: ServiceState::Running,
+                        restart_count: 0,
+                        crash_count: 0,
+                        last_exit_code: None,
+                        last_crash_at: None,
+                        started_at: Utc::now(),
+                    },
+                    restart,
+                    recent_crashes: VecDeque::new(),
+                    suspected_dead: false,
+                },
+            );
+        }
+    }
+    info!("👁️ Supervising {} (PID: {})", name, pid);
+}
+
+/// Stop supervising a service - call before an intentional shutdown
+pub fn unwatch(name: &str) {
+    if supervisor().services.lock().unwrap().remove(name).is_some() {
+        info!("👁️ No longer supervising {}", name);
+    }
+}
+
+/// Report that a supervised process exited (from a thread that wait()ed on it)
+///
+/// Exits of processes that were unwatched first are ignored.
+pub fn record_exit(name: &str, pid: u32, exit_code: Option<i32>) {
+    let sup = supervisor();
+    let is_crash = {
+        let services = sup.services.lock().unwrap();
+        services
+            .get(name)
+            .map(|s| s.status.pid == Some(pid) && s.status.state == ServiceState::Running)
+            .unwrap_or(false)
+    };
+    if is_crash {
+        spawn_crash_handler(name.to_string(), pid, exit_code);
+    }
+}
+
+/// Status of every supervised service
+pub fn get_service_statuses() -> Vec<ServiceStatus> {
+    let mut statuses: Vec<_> = supervisor()
+        .services
+        .lock()
+        .unwrap()
+        .values()
+        .map(|s| s.status.clone())
+        .collect();
+    statuses.sort_by(|a, b| a.name.cmp(&b.name));
+    statuses
+}
+
+/// Subscribe to crash/restart events
+pub fn subscribe_supervisor() -> broadcast::Receiver<SupervisorEvent> {
+    supervisor().sender.subscribe()
+}
+
+async fn poll_loop() {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+        let Some(sup) = SUPERVISOR.get() else {
+            continue;
+        };
+
+        let mut dead = Vec::new();
+        {
+            let mut services = sup.services.lock().unwrap();
+            for service in services.values_mut() {
+                let Some(pid) = service.status.pid else {
+                    continue;
+                };
+                if service.status.state != ServiceState::Running {
+                    continue;
+                }
+                if crate::eltor::is_process_running(pid) {
+                    service.suspected_dead = false;
+                } else if service.suspected_dead {
+                    dead.push((service.status.name.clone(), pid));
+                } else {
+                    service.suspected_dead = true;
+                }
+            }
+        }
+
+        for (name, pid) in dead {
+            spawn_crash_handler(name, pid, None);
+        }
+    }
+}
+
+async fn handle_crash(name: String, pid: u32, exit_code: Option<i32>) {
+    let sup = supervisor();
+    let policy = sup.policy.lock().unwrap().clone();
+
+    let (restart, crashes_in_window, restart_count) = {
+        let mut services = sup.services.lock().unwrap();
+        let Some(service) = services.get_mut(&name) else {
+            return;
+        };
+        // Already handled by the reaper or the poller
+        if service.status.pid != Some(pid) || service.status.state != ServiceState::Running {
+            return;
+        }
+
+        let now = Instant::now();
+        service.recent_crashes.push_back(now);
+        while let Some(oldest) = service.recent_crashes.front() {
+            if now.duration_since(*oldest) > policy.crash_window {
+                service.recent_crashes.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        service.status.crash_count += 1;
+        service.status.last_exit_code = exit_code;
+        service.status.last_crash_at = Some(Utc::now());
+        service.status.state = if service.recent_crashes.len() > policy.max_crashes {
+            ServiceState::Failed
+        } else {
+            ServiceState::Restarting
+        };
+
+        (
+            service.restart.clone(),
+            service.recent_crashes.len(),
+            service.status.restart_count,
+        )
+    };
+
+    let exit_text = exit_code
+        .map(|c| format!("exit code {}", c))
+        .unwrap_or_else(|| "unknown exit status".to_string());
+    emit(SupervisorEvent {
+        timestamp: Utc::now(),
+        service: name.clone(),
+        kind: "crashed".to_string(),
+        pid: Some(pid),
+        exit_code,
+        restart_count,
+        message: format!("{} (PID: {}) exited unexpectedly with {}", name, pid, exit_text),
+    });
+
+    if crashes_in_window > policy.max_crashes {
+        emit(SupervisorEvent {
+            timestamp: Utc::now(),
+            service: name.clone(),
+            kind: "gave_up".to_string(),
+            pid: Some(pid),
+            exit_code,
+            restart_count,
+            message: format!(
+                "{} crashed {} times in {}s - not restarting again",
+                name,
+                crashes_in_window,
+                policy.crash_window.as_secs()
+            ),
+        });
+        return;
+    }
+
+    let backoff = policy.backoff(crashes_in_window);
+    info!("🔁 Restarting {} in {}s", name, backoff.as_secs());
+    tokio::time::sleep(backoff).await;
+
+    // Someone may have stopped or restarted the service while we waited
+    {
+        let services = sup.services.lock().unwrap();
+        match services.get(&name) {
+            Some(s) if s.status.state == ServiceState::Restarting && s.status.pid == Some(pid) => {}
+            _ => return,
+        }
+    }
+
+    let result = restart().await;
+
+    let (new_pid, restart_count) = {
+        let mut services = sup.services.lock().unwrap();
+        let Some(service) = services.get_mut(&name) else {
+            return;
+        };
+        service.status.restart_count += 1;
+        // A successful restart re-registers through watch(), which updates the PID
+        let new_pid = service.status.pid.filter(|p| *p != pid);
+        if result.is_err() || new_pid.is_none() {
+            // Let the poller treat the failed restart as another crash
+            service.status.state = ServiceState::Running;
+            service.suspected_dead = true;
+        }
+        (new_pid, service.status.restart_count)
+    };
+
+    match (result, new_pid) {
+        (Ok(()), Some(new_pid)) => emit(SupervisorEvent {
+            timestamp: Utc::now(),
+            service: name.clone(),
+            kind: "restarted".to_string(),
+            pid: Some(new_pid),
+            exit_code: None,
+            restart_count,
+            message: format!("{} restarted (PID: {}, restart #{})", name, new_pid, restart_count),
+        }),
+        (result, _) => emit(SupervisorEvent {
+            timestamp: Utc::now(),
+            service: name.clone(),
+            kind: "restart_failed".to_string(),
+            pid: None,
+            exit_code: None,
+            restart_count,
+            message: format!(
+                "Failed to restart {}: {}",
+                name,
+                result.err().unwrap_or_else(|| "process did not start".to_string())
+            ),
+        }),
+    }
+}
+
+fn emit(event: SupervisorEvent) {
+    let level = match event.kind.as_str() {
+        "restarted" => "INFO",
+        "gave_up" => "ERROR",
+        _ => "WARN",
+    };
+    match level {
+        "INFO" => info!("✅ {}", event.message),
+        "ERROR" => error!("❌ {}", event.message),
+        _ => warn!("⚠️ {}", event.message),
+    }
+
+    let sup = supervisor();
+    if let Some(state) = sup.app_state.lock().unwrap().as_ref() {
+        let mode = event
+            .service
+            .strip_prefix("eltord-")
+            .map(|mode| mode.to_string());
+        state.add_log(LogEntry {
+            timestamp: event.timestamp,
+            level: level.to_string(),
+            message: event.message.clone(),
+            source: "supervisor".to_string(),
+            mode,
+        });
+    }
+
+    let _ = sup.sender.send(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        let policy = RestartPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3), Duration::from_secs(8));
+        assert_eq!(policy.backoff(10), Duration::from_secs(60));
+        assert_eq!(policy.backoff(1000), Duration::from_secs(60));
+    }
+
+    /// Restart quickly and give up after three crashes, so tests don't sit in backoff
+    fn use_fast_policy() {
+        *supervisor().policy.lock().unwrap() = RestartPolicy {
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+            max_crashes: 3,
+            crash_window: Duration::from_secs(600),
+        };
+    }
+
+    /// A real, long-lived child, so the poller never sees its PID as dead
+    fn spawn_sleeper(children: &Mutex<Vec<std::process::Child>>) -> u32 {
+        let child = std::process::Command::new("sleep").arg("600").spawn().unwrap();
+        let pid = child.id();
+        children.lock().unwrap().push(child);
+        pid
+    }
+
+    /// Restart function that brings the service back as a fresh sleeper
+    fn restart_sleeper(name: &'static str, children: Arc<Mutex<Vec<std::process::Child>>>) -> RestartFn {
+        Arc::new(move || respawn_sleeper(name, children.clone()))
+    }
+
+    fn respawn_sleeper(
+        name: &'static str,
+        children: Arc<Mutex<Vec<std::process::Child>>>,
+    ) -> BoxFuture<'static, Result<(), String>> {
+        Box::pin(async move {
+            let pid = spawn_sleeper(&children);
+            watch(name, pid, restart_sleeper(name, children));
+            Ok(())
+        })
+    }
+
+    /// Next event for one service, skipping events from other tests
+    async fn next_event(events: &mut broadcast::Receiver<SupervisorEvent>, name: &str) -> SupervisorEvent {
+        tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let event = events.recv().await.unwrap();
+                if event.service == name {
+                    return event;
+                }
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    fn service_status(name: &str) -> ServiceStatus {
+        get_service_statuses().into_iter().find(|s| s.name == name).unwrap()
+    }
+
+    fn stop_sleepers(name: &str, children: &Mutex<Vec<std::process::Child>>) {
+        unwatch(name);
+        for mut child in children.lock().unwrap().drain(..) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crash_restarts_service() {
+        use_fast_policy();
+        let name = "test-restart-service";
+        let children = Arc::new(Mutex::new(Vec::new()));
+        let pid = spawn_sleeper(&children);
+        let mut events = subscribe_supervisor();
+        watch(name, pid, restart_sleeper(name, children.clone()));
+
+        record_exit(name, pid, Some(1));
+        let crashed = next_event(&mut events, name).await;
+        assert_eq!(crashed.kind, "crashed");
+        assert_eq!(crashed.exit_code, Some(1));
+
+        let restarted = next_event(&mut events, name).await;
+        assert_eq!(restarted.kind, "restarted");
+        let new_pid = restarted.pid.unwrap();
+        assert_ne!(new_pid, pid);
+
+        let status = service_status(name);
+        assert_eq!(status.pid, Some(new_pid));
+        assert_eq!(status.state, ServiceState::Running);
+        assert_eq!(status.restart_count, 1);
+        assert_eq!(status.crash_count, 1);
+        assert_eq!(status.last_exit_code, Some(1));
+
+        // Exits for a stale PID or an unwatched service are ignored
+        record_exit(name, pid, Some(1));
+        assert_eq!(service_status(name).crash_count, 1);
+        stop_sleepers(name, &children);
+        record_exit(name, new_pid, Some(1));
+        assert!(get_service_statuses().iter().all(|s| s.name != name));
+    }
+
+    #[tokio::test]
+    async fn test_crash_loop_gives_up() {
+        use_fast_policy();
+        let name = "test-crash-loop-service";
+        let children = Arc::new(Mutex::new(Vec::new()));
+        let mut pid = spawn_sleeper(&children);
+        let mut events = subscribe_supervisor();
+        watch(name, pid, restart_sleeper(name, children.clone()));
+
+        // Every crash up to the limit is restarted
+        for crash in 1..=3 {
+            record_exit(name, pid, Some(1));
+            assert_eq!(next_event(&mut events, name).await.kind, "crashed");
+            let restarted = next_event(&mut events, name).await;
+            assert_eq!(restarted.kind, "restarted");
+            assert_eq!(restarted.restart_count, crash);
+            pid = restarted.pid.unwrap();
+        }
+
+        // One more inside the window and the supervisor stops restarting
+        record_exit(name, pid, Some(1));
+        assert_eq!(next_event(&mut events, name).await.kind, "crashed");
+        let gave_up = next_event(&mut events, name).await;
+        assert_eq!(gave_up.kind, "gave_up");
+        assert_eq!(gave_up.pid, Some(pid));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let status = service_status(name);
+        assert_eq!(status.state, ServiceState::Failed);
+        assert_eq!(status.crash_count, 4);
+        assert_eq!(status.restart_count, 3);
+        assert_eq!(children.lock().unwrap().len(), 4);
+
+        // A failed service ignores further exits
+        record_exit(name, pid, Some(1));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(service_status(name).crash_count, 4);
+        stop_sleepers(name, &children);
+    }
+}
@@ -0,0 +1,553 @@
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::connections::TrackedConnection;
+use crate::socks::{self, ConnectionStats, SocksRouterConfig, TargetAddress};
+
+/// nftables table that holds every rule we generate, so removing it undoes them all
+const NFT_TABLE: &str = "eltor_transparent";
+
+/// Destination the client originally asked for, before the firewall sent it to us
+///
+/// REDIRECT leaves it in conntrack (`SO_ORIGINAL_DST`); TPROXY keeps it as
+/// the socket's local address.
+#[cfg(target_os = "linux")]
+fn original_destination(stream: &TcpStream) -> std::io::Result<SocketAddr> {
+    use std::net::{Ipv6Addr, SocketAddrV4, SocketAddrV6};
+    use std::os::fd::AsRawFd;
+
+    let fd = stream.as_raw_fd();
+    let local_addr = stream.local_addr()?;
+    let result = match local_addr {
+        SocketAddr::V4(_) => {
+            let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            let mut len = std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+            let ret = unsafe {
+                libc::getsockopt(fd, libc::SOL_IP, libc::SO_ORIGINAL_DST, &mut addr as *mut _ as *mut libc::c_void, &mut len)
+            };
+            (ret == 0).then(|| {
+                let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+                SocketAddr::V4(SocketAddrV4::new(ip, u16::from_be(addr.sin_port)))
+            })
+        }
+        SocketAddr::V6(_) => {
+            let mut addr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            let mut len = std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t;
+            let ret = unsafe {
+                libc::getsockopt(fd, libc::SOL_IPV6, libc::IP6T_SO_ORIGINAL_DST, &mut addr as *mut _ as *mut libc::c_void, &mut len)
+            };
+            (ret == 0).then(|| {
+                let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+                SocketAddr::V6(SocketAddrV6::new(ip, u16::from_be(addr.sin6_port), 0, 0))
+            })
+        }
+    };
+    match result {
+        Some(destination) => Ok(destination),
+        None => {
+            let e = std::io::Error::last_os_error();
+            // No NAT entry (or no conntrack at all): the connection came in
+            // through TPROXY, or directly
+            if matches!(e.raw_os_error(), Some(libc::ENOENT) | Some(libc::ENOPROTOOPT)) {
+                Ok(local_addr)
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn original_destination(_stream: &TcpStream) -> std::io::Result<SocketAddr> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Transparent proxying needs Linux",
+    ))
+}
+
+/// Bind the listener, allowing TPROXY'd connections when we have CAP_NET_ADMIN
+async fn bind_listener(bind_addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = match bind_addr {
+        SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+    };
+    socket.set_reuseaddr(true)?;
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let (level, option) = match bind_addr {
+            SocketAddr::V4(_) => (libc::SOL_IP, libc::IP_TRANSPARENT),
+            SocketAddr::V6(_) => (libc::SOL_IPV6, libc::IPV6_TRANSPARENT),
+        };
+        let enable: libc::c_int = 1;
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                option,
+                &enable as *const _ as *const libc::c_void,
+                std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+        if ret != 0 {
+            // REDIRECT still works without it
+            debug!("🪞 TPROXY unavailable on {}: {}", bind_addr, std::io::Error::last_os_error());
+        }
+    }
+
+    socket.bind(bind_addr)?;
+    socket.listen(1024)
+}
+
+/// Whether a "redirected" connection was really made straight to our own port
+fn is_own_address(destination: SocketAddr, listen_addr: SocketAddr) -> bool {
+    destination.port() == listen_addr.port()
+        && (destination.ip() == listen_addr.ip() || destination.ip().is_loopback() || listen_addr.ip().is_unspecified())
+}
+
+/// Handle one redirected connection
+///
+/// There is no handshake: the target is the original destination, routed
+/// the same way as SOCKS traffic. Only IP addresses are known here, so
+/// domain rules only match names the DNS resolver handed out as synthetic
+/// .onion addresses.
+async fn handle_transparent_connection(
+    client_stream: TcpStream,
+    client_addr: SocketAddr,
+    listen_addr: SocketAddr,
+    config: SocksRouterConfig,
+    connection: &TrackedConnection,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let destination = original_destination(&client_stream)?;
+    if is_own_address(destination, listen_addr) {
+        return Err("Connection was not redirected to the transparent listener".into());
+    }
+    let target = match destination {
+        SocketAddr::V4(addr) => TargetAddress::IPv4(*addr.ip(), addr.port()),
+        SocketAddr::V6(addr) => TargetAddress::IPv6(*addr.ip(), addr.port()),
+    };
+    let target = crate::dns::unmap_synthetic(target);
+    debug!("🪞 Transparent connection from {} to {}", client_addr, target);
+    connection.set_target(&target);
+
+    let upstream = socks::route_target(&target, &config, client_addr);
+    if upstream.is_refusal() {
+        connection.set_upstream(&upstream);
+    }
+    // No way to report errors to the client: closing the connection has to do
+    let auth = socks::upstream_credential(None, &upstream, &config, &target, client_addr);
+    let (upstream_stream, used) = socks::connect_target(&target, &upstream, auth.as_ref())
+        .await
+        .map_err(|(_, e)| e)?;
+    connection.set_upstream(used);
+    socks::relay(client_stream, upstream_stream, connection).await;
+    Ok(())
+}
+
+/// Listener for connections redirected by nftables/iptables (Linux only)
+pub struct TransparentProxy {
+    config: SocksRouterConfig,
+    listener: Option<TcpListener>,
+    stats: Arc<ConnectionStats>,
+}
+
+impl TransparentProxy {
+    pub fn new(config: SocksRouterConfig, stats: Arc<ConnectionStats>) -> Self {
+        Self {
+            config,
+            listener: None,
+            stats,
+        }
+    }
+
+    /// Bind the transparent proxy port
+    pub async fn start(&mut self) -> Result<(), String> {
+        if !cfg!(target_os = "linux") {
+            return Err("Transparent proxy mode is only supported on Linux".to_string());
+        }
+        let port = self.config.transparent_port.ok_or("Transparent proxy port not configured")?;
+        let ip = self.config.transparent_addr.unwrap_or(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+        let bind_addr = SocketAddr::new(ip, port);
+
+        let listener = bind_listener(bind_addr)
+            .await
+            .map_err(|e| format!("Failed to bind transparent proxy to {}: {}", bind_addr, e))?;
+        info!("🪞 Transparent proxy started on {}", bind_addr);
+        self.listener = Some(listener);
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref()?.local_addr().ok()
+    }
+
+    /// Accept redirected connections until `shutdown` fires, then drain
+    pub async fn run(&mut self, mut shutdown: watch::Receiver<bool>) -> Result<(), String> {
+        let listener = self.listener.take().ok_or("Transparent proxy not started")?;
+        let listen_addr = listener.local_addr().map_err(|e| e.to_string())?;
+        let mut connections = JoinSet::new();
+
+        loop {
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                accepted = listener.accept() => match accepted {
+                    Ok((client_stream, client_addr)) => {
+                        let config = self.config.clone();
+                        let guard = self.stats.open();
+                        let connection = crate::connections::track("transparent", client_addr);
+                        connections.spawn(async move {
+                            let _guard = guard;
+                            match handle_transparent_connection(client_stream, client_addr, listen_addr, config, &connection).await {
+                                Ok(()) => connection.close("closed"),
+                                Err(e) => {
+                                    warn!("⚠️ Transparent connection error from {}: {}", client_addr, e);
+                                    connection.close(e);
+                                }
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("❌ Failed to accept transparent connection: {}", e);
+                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                    }
+                },
+            }
+        }
+
+        drop(listener);
+        info!("🪞 Transparent proxy stopped accepting connections");
+        socks::drain_connections(connections, "transparent").await;
+        Ok(())
+    }
+}
+
+/// Which traffic the generated rules send to the transparent proxy
+///
+/// Each selector given adds its own match, so traffic matching any of
+/// them is redirected.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TransparentRulesRequest {
+    /// Local user (name or uid) whose connections are redirected
+    pub user: Option<String>,
+    /// cgroup v2 path, e.g. "system.slice/myapp.service"
+    pub cgroup: Option<String>,
+    /// Incoming interface for forwarded traffic, e.g. a network namespace's veth
+    pub interface: Option<String>,
+    /// Transparent proxy port; defaults to the running listener's
+    pub port: Option<u16>,
+    /// DNS resolver port; defaults to the running resolver's
+    pub dns_port: Option<u16>,
+    /// Reject UDP other than DNS, since Tor can't carry it (default true)
+    pub block_udp: Option<bool>,
+}
+
+/// Generated nftables ruleset, loadable with `nft -f`
+///
+/// Shared by the transparent proxy and kill switch rule generators.
+#[derive(Debug, Clone, Serialize)]
+pub struct NftRules {
+    pub ruleset: String,
+    pub warnings: Vec<String>,
+}
+
+fn is_safe_name(value: &str, extra: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || extra.contains(c))
+}
+
+/// uid for a user name or number, from /etc/passwd
+pub(crate) fn resolve_uid(user: &str) -> Result<u32, String> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(uid);
+    }
+    if !is_safe_name(user, "_-.") {
+        return Err(format!("Invalid user name: {:?}", user));
+    }
+    let passwd = std::fs::read_to_string("/etc/passwd").map_err(|e| format!("Failed to read /etc/passwd: {}", e))?;
+    passwd
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.first() == Some(&user))
+        .and_then(|fields| fields.get(2)?.parse().ok())
+        .ok_or_else(|| format!("Unknown user: {}", user))
+}
+
+#[cfg(unix)]
+pub(crate) fn own_uid() -> Option<u32> {
+    Some(unsafe { libc::geteuid() })
+}
+
+#[cfg(not(unix))]
+pub(crate) fn own_uid() -> Option<u32> {
+    None
+}
+
+/// Build the nftables ruleset for a request
+///
+/// Local and private destinations are left alone. IPv6 is redirected too,
+/// which fails closed when the listener only binds IPv4.
+pub fn generate_rules(
+    request: &TransparentRulesRequest,
+    config: &SocksRouterConfig,
+) -> Result<NftRules, String> {
+    let port = request
+        .port
+        .or(config.transparent_port)
+        .filter(|&port| port != 0)
+        .ok_or("No transparent proxy port configured")?;
+    let dns_port = request.dns_port.or(config.dns_port).filter(|&port| port != 0);
+    let mut warnings = Vec::new();
+
+    // Matches for locally generated traffic (nat output) and forwarded traffic (nat prerouting)
+    let mut local_matches = Vec::new();
+    if let Some(user) = &request.user {
+        let uid = resolve_uid(user.trim())?;
+        if own_uid() == Some(uid) {
+            return Err(format!(
+                "User {} runs El Tor itself; redirecting it would loop the router's own traffic",
+                user
+            ));
+        }
+        local_matches.push(format!("meta skuid {}", uid));
+    }
+    if let Some(cgroup) = &request.cgroup {
+        let cgroup = cgroup.trim().trim_matches('/');
+        if !is_safe_name(cgroup, "_-.@:/") || cgroup.split('/').any(|part| part.is_empty() || part == "..") {
+            return Err(format!("Invalid cgroup path: {:?}", cgroup));
+        }
+        let level = cgroup.split('/').count();
+        local_matches.push(format!("socket cgroupv2 level {} \"{}\"", level, cgroup));
+    }
+    let mut forward_matches = Vec::new();
+    if let Some(interface) = &request.interface {
+        let interface = interface.trim();
+        if interface.len() > 15 || !is_safe_name(interface, "_-.") {
+            return Err(format!("Invalid interface name: {:?}", interface));
+        }
+        forward_matches.push(format!("iifname \"{}\"", interface));
+        let bound_to_loopback = config.transparent_addr.is_none_or(|ip| ip.is_loopback());
+        if bound_to_loopback && request.port.is_none() {
+            warnings.push(format!(
+                "The transparent listener is bound to loopback; forwarded traffic from {} needs it on 0.0.0.0",
+                interface
+            ));
+        }
+    }
+    if local_matches.is_empty() && forward_matches.is_empty() {
+        return Err("Choose a user, cgroup or interface to redirect".to_string());
+    }
+    if dns_port.is_none() {
+        warnings.push("No DNS resolver port configured; DNS queries will not go through El Tor".to_string());
+    }
+    let block_udp = request.block_udp.unwrap_or(true);
+
+    let mut rules = vec![
+        "# Generated by El Tor: sends selected traffic to the transparent proxy".to_string(),
+        format!("table inet {}", NFT_TABLE),
+        format!("delete table inet {}", NFT_TABLE),
+        format!("table inet {} {{", NFT_TABLE),
+        "\tset private_v4 {".to_string(),
+        "\t\ttype ipv4_addr; flags interval;".to_string(),
+        "\t\telements = { 0.0.0.0/8, 10.0.0.0/8, 127.0.0.0/8, 169.254.0.0/16, 172.16.0.0/12, 192.168.0.0/16, 224.0.0.0/4 }".to_string(),
+        "\t}".to_string(),
+        "\tset private_v6 {".to_string(),
+        "\t\ttype ipv6_addr; flags interval;".to_string(),
+        "\t\telements = { ::1, fc00::/7, fe80::/10, ff00::/8 }".to_string(),
+        "\t}".to_string(),
+        String::new(),
+        "\tchain redirect_traffic {".to_string(),
+    ];
+    if let Some(dns_port) = dns_port {
+        rules.push(format!("\t\tmeta l4proto {{ tcp, udp }} th dport 53 redirect to :{}", dns_port));
+    }
+    rules.extend([
+        "\t\tip daddr @private_v4 return".to_string(),
+        "\t\tip6 daddr @private_v6 return".to_string(),
+        format!("\t\tmeta l4proto tcp redirect to :{}", port),
+        "\t}".to_string(),
+    ]);
+    if block_udp {
+        rules.extend([
+            String::new(),
+            "\tchain block_udp {".to_string(),
+            "\t\tip daddr @private_v4 return".to_string(),
+            "\t\tip6 daddr @private_v6 return".to_string(),
+            "\t\tmeta l4proto udp reject".to_string(),
+            "\t}".to_string(),
+        ]);
+    }
+
+    let mut hook = |name: &str, kind: &str, hook: &str, priority: &str, matches: &[String], target: &str| {
+        if matches.is_empty() {
+            return;
+        }
+        rules.push(String::new());
+        rules.push(format!("\tchain {} {{", name));
+        rules.push(format!("\t\ttype {} hook {} priority {}; policy accept;", kind, hook, priority));
+        for selector in matches {
+            rules.push(format!("\t\t{} jump {}", selector, target));
+        }
+        rules.push("\t}".to_string());
+    };
+    hook("nat_output", "nat", "output", "dstnat", &local_matches, "redirect_traffic");
+    hook("nat_prerouting", "nat", "prerouting", "dstnat", &forward_matches, "redirect_traffic");
+    if block_udp {
+        hook("filter_output", "filter", "output", "filter", &local_matches, "block_udp");
+        hook("filter_forward", "filter", "forward", "filter", &forward_matches, "block_udp");
+    }
+    rules.push("}".to_string());
+
+    Ok(NftRules {
+        ruleset: rules.join("\n") + "\n",
+        warnings,
+    })
+}
+
+/// Run `nft` with optional stdin, returning stderr on failure
+pub(crate) async fn run_nft(args: &[&str], stdin: Option<&str>) -> Result<(), String> {
+    if !cfg!(target_os = "linux") {
+        return Err("nftables rules can only be applied on Linux".to_string());
+    }
+    let mut child = tokio::process::Command::new("nft")
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run nft: {}", e))?;
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(input.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write rules to nft: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("Failed to run nft: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("nft failed: {}", String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// Load the generated rules (needs root or CAP_NET_ADMIN)
+pub async fn apply_rules(rules: &NftRules) -> Result<(), String> {
+    run_nft(&["-f", "-"], Some(&rules.ruleset)).await?;
+    info!("🪞 Applied transparent proxy nftables rules (table inet {})", NFT_TABLE);
+    Ok(())
+}
+
+/// Delete an inet table and every rule in it; fine if it doesn't exist
+pub(crate) async fn delete_table(table: &str) -> Result<(), String> {
+    // Creating the table first makes the delete succeed when it doesn't exist
+    let script = format!("table inet {0}\ndelete table inet {0}\n", table);
+    run_nft(&["-f", "-"], Some(&script)).await
+}
+
+/// Remove every rule we applied; fine if there are none
+pub async fn remove_rules() -> Result<(), String> {
+    delete_table(NFT_TABLE).await?;
+    info!("🪞 Removed transparent proxy nftables rules");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SocksRouterConfig {
+        SocksRouterConfig {
+            transparent_port: Some(18047),
+            dns_port: Some(18053),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_generate_rules() {
+        let request = TransparentRulesRequest {
+            user: Some("4242".to_string()),
+            cgroup: Some("/system.slice/headless.service/".to_string()),
+            ..Default::default()
+        };
+        let rules = generate_rules(&request, &config()).unwrap();
+        let ruleset = rules.ruleset;
+        assert!(ruleset.contains("meta skuid 4242 jump redirect_traffic"));
+        assert!(ruleset.contains("socket cgroupv2 level 2 \"system.slice/headless.service\" jump redirect_traffic"));
+        assert!(ruleset.contains("th dport 53 redirect to :18053"));
+        assert!(ruleset.contains("meta l4proto tcp redirect to :18047"));
+        assert!(ruleset.contains("meta skuid 4242 jump block_udp"));
+        assert!(!ruleset.contains("nat_prerouting"));
+        assert!(rules.warnings.is_empty());
+
+        // Forwarded traffic from a namespace, no UDP blocking, no resolver
+        let request = TransparentRulesRequest {
+            interface: Some("veth-eltor".to_string()),
+            port: Some(9040),
+            block_udp: Some(false),
+            ..Default::default()
+        };
+        let config = SocksRouterConfig {
+            dns_port: None,
+            ..config()
+        };
+        let rules = generate_rules(&request, &config).unwrap();
+        assert!(rules.ruleset.contains("iifname \"veth-eltor\" jump redirect_traffic"));
+        assert!(rules.ruleset.contains("redirect to :9040"));
+        assert!(!rules.ruleset.contains("block_udp"));
+        assert!(!rules.ruleset.contains("dport 53"));
+        assert_eq!(rules.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_generate_rules_rejects_bad_input() {
+        let reject = |request: TransparentRulesRequest| generate_rules(&request, &config()).is_err();
+        assert!(reject(TransparentRulesRequest::default()));
+        assert!(reject(TransparentRulesRequest {
+            interface: Some("eth0\" accept; #".to_string()),
+            ..Default::default()
+        }));
+        assert!(reject(TransparentRulesRequest {
+            cgroup: Some("system.slice/../x".to_string()),
+            ..Default::default()
+        }));
+        assert!(reject(TransparentRulesRequest {
+            user: Some(own_uid().unwrap_or(0).to_string()),
+            ..Default::default()
+        }));
+        // No port anywhere
+        let request = TransparentRulesRequest {
+            user: Some("4242".to_string()),
+            ..Default::default()
+        };
+        assert!(generate_rules(&request, &SocksRouterConfig::default()).is_err());
+    }
+
+    #[test]
+    fn test_is_own_address() {
+        let listen: SocketAddr = "127.0.0.1:18047".parse().unwrap();
+        assert!(is_own_address(listen, listen));
+        assert!(!is_own_address("93.184.216.34:443".parse().unwrap(), listen));
+        assert!(is_own_address("192.168.1.5:18047".parse().unwrap(), "0.0.0.0:18047".parse().unwrap()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_original_destination_without_redirect() {
+        // Without NAT the original destination is the listener itself
+        let listener = bind_listener("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let listen_addr = listener.local_addr().unwrap();
+        let _client = TcpStream::connect(listen_addr).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let destination = original_destination(&stream).unwrap();
+        assert!(is_own_address(destination, listen_addr));
+    }
+}
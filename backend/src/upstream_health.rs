@@ -0,0 +1,271 @@
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::routing::{self, RouteAction};
+use crate::socks::SocksRouterConfig;
+
+/// How often every known upstream is probed
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// How long a probe may take before the upstream counts as down
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Last known state of one upstream SOCKS proxy
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamHealth {
+    /// "arti", "eltord_client", "eltord_relay" or "custom"
+    pub name: String,
+    /// "host:port"
+    pub address: String,
+    pub healthy: bool,
+    /// Round trip of the last successful probe
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    pub last_checked: Option<DateTime<Utc>>,
+}
+
+impl UpstreamHealth {
+    fn new(name: &str, address: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            address: address.to_string(),
+            healthy: false,
+            latency_ms: None,
+            consecutive_failures: 0,
+            last_error: None,
+            last_checked: None,
+        }
+    }
+
+    fn record(&mut self, result: Result<Option<Duration>, String>) {
+        let was_healthy = self.healthy && self.last_checked.is_some();
+        self.last_checked = Some(Utc::now());
+        match result {
+            Ok(latency) => {
+                if !was_healthy {
+                    info!("💚 Upstream {} ({}) is healthy", self.name, self.address);
+                }
+                self.healthy = true;
+                self.consecutive_failures = 0;
+                self.last_error = None;
+                if let Some(latency) = latency {
+                    self.latency_ms = Some(latency.as_millis() as u64);
+                }
+            }
+            Err(e) => {
+                if was_healthy {
+                    warn!("💔 Upstream {} ({}) is down: {}", self.name, self.address, e);
+                }
+                self.healthy = false;
+                self.consecutive_failures += 1;
+                self.last_error = Some(e);
+            }
+        }
+    }
+}
+
+fn health() -> &'static Mutex<HashMap<String, UpstreamHealth>> {
+    static HEALTH: OnceLock<Mutex<HashMap<String, UpstreamHealth>>> = OnceLock::new();
+    HEALTH.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+static CHECKER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Router config the checker probes, updated whenever the router (re)starts
+fn checker_config() -> &'static Mutex<Option<SocksRouterConfig>> {
+    static CONFIG: OnceLock<Mutex<Option<SocksRouterConfig>>> = OnceLock::new();
+    CONFIG.get_or_init(|| Mutex::new(None))
+}
+
+/// Check an upstream is listening and speaks SOCKS5 (greeting with no-auth)
+pub async fn probe(address: &str) -> Result<Duration, String> {
+    let started = Instant::now();
+    let handshake = async {
+        let mut stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Connection failed: {}", e))?;
+        stream
+            .write_all(&[0x05, 0x01, 0x00])
+            .await
+            .map_err(|e| format!("Handshake failed: {}", e))?;
+        let mut response = [0u8; 2];
+        stream
+            .read_exact(&mut response)
+            .await
+            .map_err(|e| format!("Handshake failed: {}", e))?;
+        if response[0] != 0x05 || response[1] == 0xFF {
+            return Err(format!("Unexpected SOCKS greeting reply: {:?}", response));
+        }
+        Ok(started.elapsed())
+    };
+    tokio::time::timeout(PROBE_TIMEOUT, handshake)
+        .await
+        .unwrap_or_else(|_| Err(format!("No answer within {}s", PROBE_TIMEOUT.as_secs())))
+}
+
+/// Upstreams worth probing: the built-in ports plus custom SOCKS rules
+fn probe_targets(config: &SocksRouterConfig) -> Vec<(String, String)> {
+    let local = |port: u16| format!("127.0.0.1:{}", port);
+    let mut targets = vec![
+        ("arti".to_string(), local(config.arti_socks_port)),
+        ("eltord_client".to_string(), local(config.eltord_client_socks_port)),
+        ("eltord_relay".to_string(), local(config.eltord_relay_socks_port)),
+    ];
+    for rule in routing::get_routing_config().rules {
+        if let RouteAction::Socks { address } = rule.action {
+            if !targets.iter().any(|(_, known)| *known == address) {
+                targets.push(("custom".to_string(), address));
+            }
+        }
+    }
+    targets
+}
+
+/// Probe every upstream once and update the cache
+pub async fn check_all(config: &SocksRouterConfig) {
+    let targets = probe_targets(config);
+    let results = futures::future::join_all(targets.iter().map(|(_, address)| probe(address))).await;
+
+    let mut health = health().lock().unwrap();
+    // Drop custom upstreams whose rules were removed
+    health.retain(|address, _| targets.iter().any(|(_, known)| known == address));
+    for ((name, address), result) in targets.into_iter().zip(results) {
+        health
+            .entry(address.clone())
+            .or_insert_with(|| UpstreamHealth::new(&name, &address))
+            .record(result.map(Some));
+    }
+}
+
+/// Start probing upstreams in the background (once per process)
+///
+/// Later calls only swap in the new config, e.g. after a router restart.
+pub fn start_health_checker(config: SocksRouterConfig) {
+    *checker_config().lock().unwrap() = Some(config);
+    if CHECKER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    info!("🩺 Upstream health checker started (every {}s)", CHECK_INTERVAL.as_secs());
+    tokio::spawn(async move {
+        loop {
+            let config = checker_config().lock().unwrap().clone();
+            if let Some(config) = config {
+                check_all(&config).await;
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Cached health of an upstream; None until it has been checked
+pub fn is_healthy(address: &str) -> Option<bool> {
+    health()
+        .lock()
+        .unwrap()
+        .get(address)
+        .filter(|h| h.last_checked.is_some())
+        .map(|h| h.healthy)
+}
+
+/// A real connection got through the upstream's handshake
+pub(crate) fn record_success(address: &str) {
+    if let Some(entry) = health().lock().unwrap().get_mut(address) {
+        entry.record(Ok(None));
+    }
+}
+
+/// A real connection was refused or failed mid-handshake
+pub(crate) fn record_failure(address: &str, error: &str) {
+    debug!("🩺 Marking upstream {} unhealthy: {}", address, error);
+    if let Some(entry) = health().lock().unwrap().get_mut(address) {
+        entry.record(Err(error.to_string()));
+    }
+}
+
+/// Order candidates healthy first, then unchecked, then unhealthy
+///
+/// The order is stable, so routing preference breaks ties. Unhealthy
+/// upstreams stay in the list as a last resort since the cache may be stale.
+pub fn order_by_health(candidates: Vec<String>) -> Vec<String> {
+    sort_by_health(candidates, is_healthy)
+}
+
+fn sort_by_health(mut candidates: Vec<String>, lookup: impl Fn(&str) -> Option<bool>) -> Vec<String> {
+    candidates.sort_by_key(|address| match lookup(address) {
+        Some(true) => 0,
+        None => 1,
+        Some(false) => 2,
+    });
+    candidates
+}
+
+/// Every upstream we know about, sorted by address
+pub fn get_upstream_health() -> Vec<UpstreamHealth> {
+    let mut upstreams: Vec<_> = health().lock().unwrap().values().cloned().collect();
+    upstreams.sort_by(|a, b| a.address.cmp(&b.address));
+    upstreams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_sort_by_health() {
+        let candidates = vec!["client".to_string(), "relay".to_string(), "other".to_string()];
+        let lookup = |address: &str| match address {
+            "client" => Some(false),
+            "relay" => Some(true),
+            _ => None,
+        };
+        assert_eq!(sort_by_health(candidates.clone(), lookup), vec!["relay", "other", "client"]);
+
+        // Nothing known yet: preference order is kept
+        assert_eq!(sort_by_health(candidates.clone(), |_| None), candidates);
+    }
+
+    #[test]
+    fn test_health_transitions() {
+        let mut upstream = UpstreamHealth::new("eltord_client", "127.0.0.1:18058");
+        upstream.record(Err("refused".to_string()));
+        upstream.record(Err("refused".to_string()));
+        assert!(!upstream.healthy);
+        assert_eq!(upstream.consecutive_failures, 2);
+
+        upstream.record(Ok(Some(Duration::from_millis(12))));
+        assert!(upstream.healthy);
+        assert_eq!(upstream.consecutive_failures, 0);
+        assert_eq!(upstream.latency_ms, Some(12));
+        assert_eq!(upstream.last_error, None);
+    }
+
+    #[tokio::test]
+    async fn test_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            stream.read_exact(&mut greeting).await.unwrap();
+            stream.write_all(&[0x05, 0x00]).await.unwrap();
+        });
+        assert!(probe(&address).await.is_ok());
+
+        // Listening but not speaking SOCKS
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\n").await.unwrap();
+        });
+        assert!(probe(&address).await.is_err());
+    }
+}
@@ -7,6 +7,9 @@ use log::info;
 use crate::paths::PathConfig;
 use crate::state::{AppState, LogEntry};
 
+/// Name phoenixd is registered under with the process registry and supervisor
+pub const PHOENIXD_SERVICE_NAME: &str = "phoenixd";
+
 // Function to read phoenixd logs from stdout
 pub async fn read_phoenixd_logs(
     mut reader: AsyncBufReader<tokio::process::ChildStdout>,
@@ -77,25 +80,9 @@ pub async fn read_phoenixd_stderr_logs(
 
 pub async fn start_phoenixd(state: AppState) -> Result<(), String> {
     // Check if phoenixd is already running
-    {
-        let mut process_guard = state.wallet_state.phoenixd_process.lock().unwrap();
-        if let Some(ref mut child) = *process_guard {
-            match child.try_wait() {
-                Ok(Some(_)) => {
-                    // Process has exited, clear it
-                    *process_guard = None;
-                }
-                Ok(None) => {
-                    // Process is still running
-                    info!("⚠️ Phoenixd process already running, skipping startup");
-                    return Ok(());
-                }
-                Err(_) => {
-                    // Error checking process, assume it's dead
-                    *process_guard = None;
-                }
-            }
-        }
+    if crate::processes::is_running(PHOENIXD_SERVICE_NAME) {
+        info!("⚠️ Phoenixd process already running, skipping startup");
+        return Ok(());
     }
 
     // Get the path to the phoenixd binary
@@ -126,8 +113,6 @@ pub async fn start_phoenixd(state: AppState) -> Result<(), String> {
         .spawn()
         .map_err(|e| format!("Failed to start phoenixd: {}", e))?;
     
-    let pid = child.id().unwrap_or(0);
-    
     // Set up log readers for phoenixd
     if let Some(stdout) = child.stdout.take() {
         let reader = AsyncBufReader::new(stdout);
@@ -145,13 +130,18 @@ pub async fn start_phoenixd(state: AppState) -> Result<(), String> {
         });
     }
     
-    // Store the phoenixd process
-    {
-        let mut process_guard = state.wallet_state.phoenixd_process.lock().unwrap();
-        *process_guard = Some(child);
-    }
+    // Register the phoenixd process and reap it when it exits
+    let pid = track_phoenixd(child)?;
     
     info!("✅ Phoenixd process started with PID: {}", pid);
+
+    // The exit watcher reports crashes so the supervisor can restart it
+    let state_for_restart = state.clone();
+    crate::supervisor::watch(
+        PHOENIXD_SERVICE_NAME,
+        pid,
+        std::sync::Arc::new(move || restart_phoenixd(state_for_restart.clone())),
+    );
     
     // Add startup log
     state.add_log(LogEntry {
@@ -165,36 +155,48 @@ pub async fn start_phoenixd(state: AppState) -> Result<(), String> {
     Ok(())
 }
 
+/// Restart phoenixd after a crash (used by the supervisor)
+fn restart_phoenixd(state: AppState) -> futures::future::BoxFuture<'static, Result<(), String>> {
+    Box::pin(start_phoenixd(state))
+}
+
+/// Register a spawned phoenixd with the process registry and reap it in the background
+///
+/// The exit status is reported to the supervisor, which ignores it unless
+/// phoenixd is being supervised.
+pub(crate) fn track_phoenixd(mut child: tokio::process::Child) -> Result<u32, String> {
+    let pid = child.id().ok_or("Phoenixd exited before it could be registered")?;
+    crate::processes::register(PHOENIXD_SERVICE_NAME, pid, None)?;
+
+    tokio::spawn(async move {
+        let exit_code = child.wait().await.ok().and_then(|status| status.code());
+        info!("🧹 Phoenixd process {} exited (code: {:?})", pid, exit_code);
+        crate::processes::unregister_pid(PHOENIXD_SERVICE_NAME, pid);
+        crate::supervisor::record_exit(PHOENIXD_SERVICE_NAME, pid, exit_code);
+    });
+
+    Ok(pid)
+}
+
 pub async fn stop_phoenixd(state: AppState) -> Result<(), String> {
-    // Take the process out of the mutex first to avoid holding the lock across await
-    let mut child = {
-        let mut process_guard = state.wallet_state.phoenixd_process.lock().unwrap();
-        process_guard.take()
-    };
-    
-    if let Some(ref mut child) = child {
-        match child.kill().await {
-            Ok(_) => {
-                info!("🛑 Killing phoenixd process...");
-                let _ = child.wait().await; // Wait for process to actually terminate
-                info!("✅ Phoenixd process terminated successfully");
-                
-                // Add shutdown log
-                state.add_log(LogEntry {
-                    timestamp: Utc::now(),
-                    level: "INFO".to_string(),
-                    message: "Phoenixd wallet process terminated".to_string(),
-                    source: "system".to_string(),
-                    mode: None, // Wallet logs are system-wide
-                });
-                
-                Ok(())
-            }
-            Err(e) => {
-                Err(format!("Failed to kill phoenixd process: {}", e))
-            }
+    crate::supervisor::unwatch(PHOENIXD_SERVICE_NAME);
+
+    match crate::processes::terminate(PHOENIXD_SERVICE_NAME) {
+        Ok(Some(record)) => {
+            info!("✅ Phoenixd process terminated successfully (PID: {})", record.pid);
+            
+            // Add shutdown log
+            state.add_log(LogEntry {
+                timestamp: Utc::now(),
+                level: "INFO".to_string(),
+                message: "Phoenixd wallet process terminated".to_string(),
+                source: "system".to_string(),
+                mode: None, // Wallet logs are system-wide
+            });
+            
+            Ok(())
         }
-    } else {
-        Err("No phoenixd process is currently running".to_string())
+        Ok(None) => Err("No phoenixd process is currently running".to_string()),
+        Err(e) => Err(format!("Failed to kill phoenixd process: {}", e)),
     }
 }
@@ -119,15 +119,20 @@ async fn test_log_event(app_handle: AppHandle) -> Result<String, String> {
 }
 
 #[command]
-fn activate_eltord_invoke(mode: String, enable_logging: Option<bool>) -> Result<String, String>  {
+fn activate_eltord_invoke(mode: String, enable_logging: Option<bool>) -> Result<eltor_backend::ActivationJob, String>  {
     info!(
         "🔧 Current working directory: {:?}",
         std::env::current_dir()
     );
     // info!("🚀 Starting activation with mode: {:?}", mode);
     let enable_logging = enable_logging.unwrap_or(false);
-    eltor_backend::eltor::activate_eltord_process(mode, enable_logging);
-    Ok("Activation started".to_string())
+    // Progress is emitted as "eltord-activation" events, or poll get_activation_job_invoke
+    activate_eltord(mode, enable_logging)
+}
+
+#[command]
+fn get_activation_job_invoke(id: String) -> Result<eltor_backend::ActivationJob, String> {
+    eltor_backend::get_activation_job(&id).ok_or_else(|| format!("Unknown activation job: {}", id))
 }
 
 #[command]
@@ -136,7 +141,7 @@ async fn deactivate_eltord_invoke(
 ) -> Result<String, String> {
     info!("🛑 deactivate_eltord_invoke command called with mode: {:?}", mode);
     
-    // Use the async PID file-based deactivation with graceful shutdown
+    // Use the async registry-based deactivation with graceful shutdown
     eltor_backend::eltor::deactivate_eltord_process(mode).await
 }
 
@@ -147,8 +152,8 @@ async fn get_eltord_status_invoke(
     // Get the path config for this Tauri instance
     let path_config = create_tauri_path_config(Some(&app_handle))?;
     
-    // Use the backend function that checks PID files directly
-    let status = eltor_backend::eltor::get_eltord_status_from_pid_files(&path_config).await;
+    // Use the backend function that checks the process registry directly
+    let status = eltor_backend::eltor::get_eltord_status_from_registry(&path_config).await;
 
     // Return the status structure with client_running and relay_running
     Ok(serde_json::json!({
@@ -159,6 +164,112 @@ async fn get_eltord_status_invoke(
     }))
 }
 
+#[command]
+fn get_bootstrap_status_invoke(mode: Option<String>) -> Result<serde_json::Value, String> {
+    // Without a mode, return every mode we are tracking
+    match mode {
+        Some(mode) => serde_json::to_value(eltor_backend::get_bootstrap_status(&mode))
+            .map_err(|e| e.to_string()),
+        None => serde_json::to_value(eltor_backend::bootstrap::get_all_bootstrap_status())
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[command]
+fn get_bandwidth_invoke(mode: Option<String>) -> Result<serde_json::Value, String> {
+    // With a mode, return its full history; without, current rates for every mode
+    match mode {
+        Some(mode) => serde_json::to_value(eltor_backend::get_bandwidth_history(&mode))
+            .map_err(|e| e.to_string()),
+        None => serde_json::to_value(eltor_backend::bandwidth::get_all_bandwidth())
+            .map_err(|e| e.to_string()),
+    }
+}
+
+#[command]
+async fn get_circuits_invoke(
+    app_handle: AppHandle,
+    mode: Option<String>,
+) -> Result<Vec<eltor_backend::Circuit>, String> {
+    let path_config = create_tauri_path_config(Some(&app_handle))?;
+    let mode = EltorMode::from_str(&mode.unwrap_or_else(|| "client".to_string()))?;
+    eltor_backend::get_circuits(&mode, &path_config).await
+}
+
+#[command]
+async fn new_identity_invoke(app_handle: AppHandle) -> Result<eltor_backend::NewIdentityResult, String> {
+    let path_config = create_tauri_path_config(Some(&app_handle))?;
+    eltor_backend::new_identity(&path_config).await
+}
+
+#[command]
+fn get_supervisor_status_invoke() -> Vec<eltor_backend::ServiceStatus> {
+    eltor_backend::get_service_statuses()
+}
+
+#[command]
+fn get_processes_invoke() -> Vec<eltor_backend::ProcessInfo> {
+    eltor_backend::list_processes()
+}
+
+#[command]
+fn get_socks_router_status_invoke() -> eltor_backend::SocksRouterStatus {
+    eltor_backend::get_socks_router_status()
+}
+
+#[command]
+async fn start_socks_router_invoke() -> Result<eltor_backend::SocksRouterStatus, String> {
+    eltor_backend::start_socks_router().await
+}
+
+#[command]
+async fn stop_socks_router_invoke() -> Result<eltor_backend::SocksRouterStatus, String> {
+    eltor_backend::stop_socks_router().await?;
+    Ok(eltor_backend::get_socks_router_status())
+}
+
+#[command]
+async fn restart_socks_router_invoke(
+    config: Option<eltor_backend::SocksRouterConfigUpdate>,
+) -> Result<eltor_backend::SocksRouterStatus, String> {
+    // Unset fields keep the running router's values
+    let config = config.map(|update| update.apply(eltor_backend::socks::socks_router_config()));
+    eltor_backend::restart_socks_router(config).await
+}
+
+#[command]
+fn get_arti_config_invoke(app_handle: AppHandle) -> Result<eltor_backend::ArtiConfigInfo, String> {
+    let path_config = create_tauri_path_config(Some(&app_handle))?;
+    eltor_backend::load_arti_settings(&path_config)?;
+    Ok(eltor_backend::get_arti_config(&path_config))
+}
+
+#[command]
+async fn update_arti_config_invoke(
+    app_handle: AppHandle,
+    settings: eltor_backend::ArtiSettings,
+) -> Result<eltor_backend::ArtiConfigInfo, String> {
+    let path_config = create_tauri_path_config(Some(&app_handle))?;
+    eltor_backend::apply_arti_settings(&path_config, settings).await
+}
+
+#[command]
+async fn reset_arti_storage_invoke(app_handle: AppHandle) -> Result<eltor_backend::ArtiConfigInfo, String> {
+    let path_config = create_tauri_path_config(Some(&app_handle))?;
+    eltor_backend::reset_arti_storage(&path_config).await
+}
+
+#[command]
+fn get_kill_switch_invoke() -> eltor_backend::KillSwitchStatus {
+    eltor_backend::get_kill_switch_status()
+}
+
+#[command]
+fn set_kill_switch_invoke(app_handle: AppHandle, enabled: bool) -> Result<eltor_backend::KillSwitchStatus, String> {
+    let path_config = create_tauri_path_config(Some(&app_handle))?;
+    eltor_backend::set_kill_switch(&path_config, enabled)
+}
+
 #[command]
 async fn get_eltord_logs_invoke(
     tauri_state: State<'_, TauriState>,
@@ -547,10 +658,11 @@ fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
     let show_i = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
     let activate_i = MenuItem::with_id(app, "activate", "Activate", true, None::<&str>)?;
     let deactivate_i = MenuItem::with_id(app, "deactivate", "Deactivate", true, None::<&str>)?;
+    let new_identity_i = MenuItem::with_id(app, "new_identity", "New Identity", true, None::<&str>)?;
 
     let menu = Menu::with_items(
         app,
-        &[&show_i, &hide_i, &activate_i, &deactivate_i, &quit_i],
+        &[&show_i, &hide_i, &activate_i, &deactivate_i, &new_identity_i, &quit_i],
     )?;
 
     let app_clone = app.clone();
@@ -712,6 +824,25 @@ fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
                     }
                 });
             }
+            "new_identity" => {
+                let app_handle = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    let result = match create_tauri_path_config(Some(&app_handle)) {
+                        Ok(path_config) => eltor_backend::new_identity(&path_config).await,
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(result) => {
+                            info!("✅ {}", result.message);
+                            let _ = app_handle.emit("eltord-new-identity", &result);
+                        }
+                        Err(err) => {
+                            info!("❌ {}", err);
+                            let _ = app_handle.emit("eltord-error", &err);
+                        }
+                    }
+                });
+            }
             _ => {}
         })
         .build(app)?;
@@ -983,24 +1114,35 @@ async fn update_relay_payment_rate(
 
     info!("✅ Payment rate updated to {} msats/min ({} sats/min)", rate_msats, rateSatsPerMin);
 
+    // Hot-apply to a running relay so existing circuits survive the price change
+    let applied = eltor_backend::apply_torrc_changes(
+        &EltorMode::Relay,
+        &path_config,
+        &["PaymentRateMsats"],
+    )
+    .await;
+
     Ok(serde_json::json!({
-        "message": format!("Payment rate updated to {} msats/min ({} sats/min)", rate_msats, rateSatsPerMin),
-        "rate_msats": rate_msats
+        "message": format!(
+            "Payment rate updated to {} msats/min ({} sats/min){}",
+            rate_msats,
+            rateSatsPerMin,
+            eltor_backend::live_config::restart_note(std::slice::from_ref(&applied))
+        ),
+        "rate_msats": rate_msats,
+        "restart_required": applied.restart_required,
+        "applied": applied
     }))
 }
 
 #[command]
 async fn detect_phoenix_config(
-    tauri_state: State<'_, TauriState>,
+    _tauri_state: State<'_, TauriState>,
 ) -> Result<serde_json::Value, String> {
     info!("🔍 detect_phoenix_config called");
 
-    // Check if Phoenix process is running in our state
-    let backend_state = tauri_state.backend_state.read().await;
-    let phoenixd_process = backend_state.wallet_state.phoenixd_process.lock().unwrap();
-    let is_running = phoenixd_process.is_some();
-    drop(phoenixd_process);
-    drop(backend_state);
+    // Check if Phoenix process is running in the process registry
+    let is_running = eltor_backend::processes::is_running(eltor_backend::wallet::PHOENIXD_SERVICE_NAME);
 
     // Try to get existing Phoenix config from ~/.phoenix/phoenix.conf
     let home_dir = dirs::home_dir().ok_or("Could not get home directory")?;
@@ -1139,6 +1281,23 @@ fn main() {
             // Initialize the Tauri state
             let tauri_state = TauriState::new();
 
+            // Forward Arti's captured output to the frontend log view
+            let app_handle_for_arti_logs = app.handle().clone();
+            let state_for_arti_logs = tauri_state.backend_state.clone();
+            tauri::async_runtime::spawn(async move {
+                let mut receiver = eltor_backend::get_log_receiver(state_for_arti_logs).await;
+                loop {
+                    match receiver.recv().await {
+                        Ok(entry) if entry.source == eltor_backend::arti_logs::ARTI_LOG_SOURCE => {
+                            let _ = app_handle_for_arti_logs.emit("eltord-log", &TauriLogEntry::from(entry));
+                        }
+                        Ok(_) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+
             // Initialize phoenixd asynchronously after the runtime is available
             let app_handle = app.handle().clone();
             let state_for_init = tauri_state.clone();
@@ -1233,6 +1392,66 @@ fn main() {
                 }
             });
 
+            // Forward bootstrap progress to the frontend as it changes
+            let app_handle_for_bootstrap = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut receiver = eltor_backend::subscribe_bootstrap();
+                loop {
+                    match receiver.recv().await {
+                        Ok(status) => {
+                            let _ = app_handle_for_bootstrap.emit("eltord-bootstrap", &status);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Forward kill switch toggles, engage/release and refused connections
+            let app_handle_for_kill_switch = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut receiver = eltor_backend::subscribe_kill_switch();
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => {
+                            let _ = app_handle_for_kill_switch.emit("socks-kill-switch", &event);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Forward activation job state changes to the frontend
+            let app_handle_for_activation = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut receiver = eltor_backend::subscribe_activation();
+                loop {
+                    match receiver.recv().await {
+                        Ok(job) => {
+                            let _ = app_handle_for_activation.emit("eltord-activation", &job);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Forward supervisor crash/restart events to the frontend
+            let app_handle_for_supervisor = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut receiver = eltor_backend::subscribe_supervisor();
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) => {
+                            let _ = app_handle_for_supervisor.emit("process-supervisor", &event);
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+
             // Store the state for Tauri commands
             app.manage(tauri_state.clone());
 
@@ -1291,8 +1510,24 @@ fn main() {
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
             activate_eltord_invoke,
+            get_activation_job_invoke,
             deactivate_eltord_invoke,
             get_eltord_status_invoke,
+            get_bootstrap_status_invoke,
+            get_bandwidth_invoke,
+            get_circuits_invoke,
+            new_identity_invoke,
+            get_supervisor_status_invoke,
+            get_processes_invoke,
+            get_socks_router_status_invoke,
+            start_socks_router_invoke,
+            stop_socks_router_invoke,
+            restart_socks_router_invoke,
+            get_kill_switch_invoke,
+            set_kill_switch_invoke,
+            get_arti_config_invoke,
+            update_arti_config_invoke,
+            reset_arti_storage_invoke,
             get_eltord_logs_invoke,
             stream_eltord_logs_invoke,
             stop_eltord_logs_invoke,